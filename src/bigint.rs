@@ -0,0 +1,267 @@
+use std::cmp::Ordering;
+use std::fmt::{self, Display, Formatter};
+
+/// Arbitrary precision integer used by the interpreter's big integer mode.
+/// The magnitude is stored little-endian in base 2^32 without trailing zero limbs,
+/// so zero is an empty vector and is never negative.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BigInt {
+    negative: bool,
+    magnitude: Vec<u32>,
+}
+
+impl BigInt {
+    pub fn from_i64(n: i64) -> Self {
+        let mut value = n.unsigned_abs();
+        let mut magnitude = Vec::new();
+        while value > 0 {
+            magnitude.push(value as u32);
+            value >>= 32;
+        }
+        BigInt {
+            negative: n < 0,
+            magnitude,
+        }
+    }
+
+    pub fn parse(text: &str) -> Option<Self> {
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text.strip_prefix('+').unwrap_or(text)),
+        };
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let mut magnitude = Vec::new();
+        for chunk in digits.as_bytes().chunks(9) {
+            let chunk = std::str::from_utf8(chunk).unwrap();
+            let scale = 10u32.pow(chunk.len() as u32);
+            mul_small_add(&mut magnitude, scale, chunk.parse().unwrap());
+        }
+        Some(BigInt::new(negative, magnitude))
+    }
+
+    fn new(negative: bool, mut magnitude: Vec<u32>) -> Self {
+        while let Some(0) = magnitude.last() {
+            magnitude.pop();
+        }
+        BigInt {
+            negative: negative && !magnitude.is_empty(),
+            magnitude,
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.magnitude.is_empty()
+    }
+
+    pub fn to_i64(&self) -> Option<i64> {
+        if self.magnitude.len() > 2 {
+            return None;
+        }
+        let mut value: u64 = 0;
+        for limb in self.magnitude.iter().rev() {
+            value = (value << 32) | u64::from(*limb);
+        }
+        if self.negative {
+            if value <= i64::MAX as u64 + 1 {
+                Some((value as i64).wrapping_neg())
+            } else {
+                None
+            }
+        } else if value <= i64::MAX as u64 {
+            Some(value as i64)
+        } else {
+            None
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        self.to_string().parse().unwrap()
+    }
+
+    pub fn neg(&self) -> Self {
+        BigInt::new(!self.negative, self.magnitude.clone())
+    }
+
+    pub fn add(&self, other: &BigInt) -> Self {
+        if self.negative == other.negative {
+            return BigInt::new(
+                self.negative,
+                add_magnitude(&self.magnitude, &other.magnitude),
+            );
+        }
+        match cmp_magnitude(&self.magnitude, &other.magnitude) {
+            Ordering::Less => BigInt::new(
+                other.negative,
+                sub_magnitude(&other.magnitude, &self.magnitude),
+            ),
+            _ => BigInt::new(
+                self.negative,
+                sub_magnitude(&self.magnitude, &other.magnitude),
+            ),
+        }
+    }
+
+    pub fn sub(&self, other: &BigInt) -> Self {
+        self.add(&other.neg())
+    }
+
+    pub fn mul(&self, other: &BigInt) -> Self {
+        let mut result = vec![0u32; self.magnitude.len() + other.magnitude.len()];
+        for (i, a) in self.magnitude.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, b) in other.magnitude.iter().enumerate() {
+                let t = u64::from(*a) * u64::from(*b) + u64::from(result[i + j]) + carry;
+                result[i + j] = t as u32;
+                carry = t >> 32;
+            }
+            result[i + other.magnitude.len()] = carry as u32;
+        }
+        BigInt::new(self.negative != other.negative, result)
+    }
+
+    /// Quotient and remainder truncated toward zero like Rust's `/` and `%`,
+    /// `None` when dividing by zero.
+    pub fn div_rem(&self, other: &BigInt) -> Option<(BigInt, BigInt)> {
+        if other.is_zero() {
+            return None;
+        }
+        let mut quotient = vec![0u32; self.magnitude.len()];
+        let mut remainder: Vec<u32> = Vec::new();
+        for i in (0..self.magnitude.len() * 32).rev() {
+            shift_left_one(&mut remainder);
+            if (self.magnitude[i / 32] >> (i % 32)) & 1 == 1 {
+                if remainder.is_empty() {
+                    remainder.push(1);
+                } else {
+                    remainder[0] |= 1;
+                }
+            }
+            if cmp_magnitude(&remainder, &other.magnitude) != Ordering::Less {
+                remainder = sub_magnitude(&remainder, &other.magnitude);
+                quotient[i / 32] |= 1 << (i % 32);
+            }
+        }
+        Some((
+            BigInt::new(self.negative != other.negative, quotient),
+            BigInt::new(self.negative, remainder),
+        ))
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &BigInt) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_magnitude(&self.magnitude, &other.magnitude),
+            (true, true) => cmp_magnitude(&other.magnitude, &self.magnitude),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &BigInt) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Display for BigInt {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+        // peel off base 10^9 chunks, least significant first
+        let mut chunks = Vec::new();
+        let mut magnitude = self.magnitude.clone();
+        while !magnitude.is_empty() {
+            chunks.push(div_small(&mut magnitude, 1_000_000_000));
+        }
+        if self.negative {
+            write!(f, "-")?;
+        }
+        write!(f, "{}", chunks.pop().unwrap())?;
+        for chunk in chunks.iter().rev() {
+            write!(f, "{:09}", chunk)?;
+        }
+        Ok(())
+    }
+}
+
+fn cmp_magnitude(a: &[u32], b: &[u32]) -> Ordering {
+    a.len()
+        .cmp(&b.len())
+        .then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len().max(b.len()) + 1);
+    let mut carry = 0u64;
+    for i in 0..a.len().max(b.len()) {
+        let t = u64::from(*a.get(i).unwrap_or(&0)) + u64::from(*b.get(i).unwrap_or(&0)) + carry;
+        result.push(t as u32);
+        carry = t >> 32;
+    }
+    if carry > 0 {
+        result.push(carry as u32);
+    }
+    result
+}
+
+// requires a >= b
+fn sub_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for (i, limb) in a.iter().enumerate() {
+        let mut t = i64::from(*limb) - i64::from(*b.get(i).unwrap_or(&0)) - borrow;
+        borrow = 0;
+        if t < 0 {
+            t += 1 << 32;
+            borrow = 1;
+        }
+        result.push(t as u32);
+    }
+    while let Some(0) = result.last() {
+        result.pop();
+    }
+    result
+}
+
+fn shift_left_one(magnitude: &mut Vec<u32>) {
+    let mut carry = 0;
+    for limb in magnitude.iter_mut() {
+        let next = *limb >> 31;
+        *limb = (*limb << 1) | carry;
+        carry = next;
+    }
+    if carry > 0 {
+        magnitude.push(carry);
+    }
+}
+
+fn mul_small_add(magnitude: &mut Vec<u32>, factor: u32, addend: u32) {
+    let mut carry = u64::from(addend);
+    for limb in magnitude.iter_mut() {
+        let t = u64::from(*limb) * u64::from(factor) + carry;
+        *limb = t as u32;
+        carry = t >> 32;
+    }
+    if carry > 0 {
+        magnitude.push(carry as u32);
+    }
+}
+
+// divides in place and returns the remainder
+fn div_small(magnitude: &mut Vec<u32>, divisor: u32) -> u32 {
+    let mut remainder = 0u64;
+    for limb in magnitude.iter_mut().rev() {
+        let t = (remainder << 32) | u64::from(*limb);
+        *limb = (t / u64::from(divisor)) as u32;
+        remainder = t % u64::from(divisor);
+    }
+    while let Some(0) = magnitude.last() {
+        magnitude.pop();
+    }
+    remainder as u32
+}
@@ -0,0 +1,9 @@
+//! Debug adapter for MiniPL, speaking the Debug Adapter Protocol over stdin and stdout.
+
+fn main() {
+    let stdin = std::io::BufReader::new(std::io::stdin());
+    if let Err(e) = mini_pl::dap::run(stdin, std::io::stdout()) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,14 @@
+//! Language server for MiniPL, speaking LSP over stdin and stdout.
+
+fn main() {
+    let stdin = std::io::stdin();
+    let stdout = std::io::stdout();
+    match mini_pl::lsp::run(stdin.lock(), stdout.lock()) {
+        Ok(true) => {}
+        Ok(false) => std::process::exit(1),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,242 @@
+use crate::nodes::DataType;
+use crate::tokens::Value;
+use phf::phf_map;
+
+pub struct Builtin {
+    pub params: &'static [DataType],
+    pub returns: DataType,
+    pub function: fn(&[Value]) -> Result<Value, String>,
+}
+
+pub const BUILTINS: phf::Map<&'static str, Builtin> = phf_map! {
+    "abs" => Builtin {
+        params: &[DataType::Int],
+        returns: DataType::Int,
+        function: abs,
+    },
+    "min" => Builtin {
+        params: &[DataType::Int, DataType::Int],
+        returns: DataType::Int,
+        function: min,
+    },
+    "max" => Builtin {
+        params: &[DataType::Int, DataType::Int],
+        returns: DataType::Int,
+        function: max,
+    },
+    "pow" => Builtin {
+        params: &[DataType::Int, DataType::Int],
+        returns: DataType::Int,
+        function: pow,
+    },
+    "sqrt" => Builtin {
+        params: &[DataType::Int],
+        returns: DataType::Int,
+        function: sqrt,
+    },
+    "to_string" => Builtin {
+        params: &[DataType::Int],
+        returns: DataType::Str,
+        function: to_string,
+    },
+    "to_int" => Builtin {
+        params: &[DataType::Str],
+        returns: DataType::Int,
+        function: to_int,
+    },
+    "to_real" => Builtin {
+        params: &[DataType::Real],
+        returns: DataType::Real,
+        function: to_real,
+    },
+    "trunc" => Builtin {
+        params: &[DataType::Real],
+        returns: DataType::Int,
+        function: trunc,
+    },
+    "round" => Builtin {
+        params: &[DataType::Real],
+        returns: DataType::Int,
+        function: round,
+    },
+    "length" => Builtin {
+        params: &[DataType::Str],
+        returns: DataType::Int,
+        function: length,
+    },
+    "substring" => Builtin {
+        params: &[DataType::Str, DataType::Int, DataType::Int],
+        returns: DataType::Str,
+        function: substring,
+    },
+    "index_of" => Builtin {
+        params: &[DataType::Str, DataType::Str],
+        returns: DataType::Int,
+        function: index_of,
+    },
+    "upper" => Builtin {
+        params: &[DataType::Str],
+        returns: DataType::Str,
+        function: upper,
+    },
+    "lower" => Builtin {
+        params: &[DataType::Str],
+        returns: DataType::Str,
+        function: lower,
+    },
+};
+
+fn int(value: &Value) -> i64 {
+    match value {
+        Value::Number(n) => *n,
+        _ => unreachable!(),
+    }
+}
+
+// int arguments are accepted wherever a real is expected
+fn real(value: &Value) -> f64 {
+    match value {
+        Value::Real(r) => *r,
+        Value::Number(n) => *n as f64,
+        _ => unreachable!(),
+    }
+}
+
+fn string(value: &Value) -> &str {
+    match value {
+        Value::String(s) => s,
+        _ => unreachable!(),
+    }
+}
+
+fn abs(args: &[Value]) -> Result<Value, String> {
+    let n = int(&args[0]);
+    n.checked_abs()
+        .map(Value::Number)
+        .ok_or_else(|| format!("abs({}) does not fit in an int", n))
+}
+
+fn min(args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Number(int(&args[0]).min(int(&args[1]))))
+}
+
+fn max(args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Number(int(&args[0]).max(int(&args[1]))))
+}
+
+fn pow(args: &[Value]) -> Result<Value, String> {
+    let (base, exponent) = (int(&args[0]), int(&args[1]));
+    if exponent < 0 {
+        return Err(format!("pow: negative exponent {}", exponent));
+    }
+    // anything beyond 2^64 overflows, only the parity matters for bases -1, 0 and 1
+    let clamped = if exponent > 64 {
+        64 + exponent % 2
+    } else {
+        exponent
+    };
+    base.checked_pow(clamped as u32)
+        .map(Value::Number)
+        .ok_or_else(|| format!("pow({}, {}) does not fit in an int", base, exponent))
+}
+
+// integer square root, rounded down
+fn sqrt(args: &[Value]) -> Result<Value, String> {
+    let n = int(&args[0]);
+    if n < 0 {
+        return Err(format!("sqrt of negative number {}", n));
+    }
+    let (mut low, mut high) = (0, n.min(3_037_000_499) + 1);
+    while high - low > 1 {
+        let mid = (low + high) / 2;
+        if mid * mid <= n {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    Ok(Value::Number(low))
+}
+
+fn to_string(args: &[Value]) -> Result<Value, String> {
+    Ok(Value::String(int(&args[0]).to_string()))
+}
+
+fn to_int(args: &[Value]) -> Result<Value, String> {
+    let s = string(&args[0]);
+    s.trim()
+        .parse()
+        .map(Value::Number)
+        .map_err(|_| format!("cannot convert \"{}\" to int", s))
+}
+
+fn to_real(args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Real(real(&args[0])))
+}
+
+fn real_to_int(r: f64, name: &str) -> Result<Value, String> {
+    // i64::MAX is not exactly representable, 2^63 is the first value out of range
+    if r.is_nan() || r < i64::MIN as f64 || r >= -(i64::MIN as f64) {
+        return Err(format!("{}({:?}) does not fit in an int", name, r));
+    }
+    Ok(Value::Number(r as i64))
+}
+
+fn trunc(args: &[Value]) -> Result<Value, String> {
+    real_to_int(real(&args[0]).trunc(), "trunc")
+}
+
+fn round(args: &[Value]) -> Result<Value, String> {
+    real_to_int(real(&args[0]).round(), "round")
+}
+
+// strings are indexed by character, not by byte
+
+fn length(args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Number(string(&args[0]).chars().count() as i64))
+}
+
+fn substring(args: &[Value]) -> Result<Value, String> {
+    let s = string(&args[0]);
+    let (start, len) = (int(&args[1]), int(&args[2]));
+    let count = s.chars().count() as i64;
+    if start < 0 || len < 0 || start > count || len > count - start {
+        return Err(format!(
+            "substring({}, {}) out of bounds for string of length {}",
+            start, len, count
+        ));
+    }
+    let result = s.chars().skip(start as usize).take(len as usize).collect();
+    Ok(Value::String(result))
+}
+
+// character position of the first occurrence, -1 when not found
+fn index_of(args: &[Value]) -> Result<Value, String> {
+    let s = string(&args[0]);
+    let position = match s.find(string(&args[1])) {
+        Some(byte_pos) => s[..byte_pos].chars().count() as i64,
+        None => -1,
+    };
+    Ok(Value::Number(position))
+}
+
+fn upper(args: &[Value]) -> Result<Value, String> {
+    Ok(Value::String(string(&args[0]).to_uppercase()))
+}
+
+fn lower(args: &[Value]) -> Result<Value, String> {
+    Ok(Value::String(string(&args[0]).to_lowercase()))
+}
+
+pub fn char_at(s: &str, index: i64) -> Result<char, String> {
+    if index >= 0 {
+        if let Some(c) = s.chars().nth(index as usize) {
+            return Ok(c);
+        }
+    }
+    Err(format!(
+        "index {} out of bounds for string of length {}",
+        index,
+        s.chars().count()
+    ))
+}
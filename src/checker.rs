@@ -0,0 +1,313 @@
+use crate::builtins::BUILTINS;
+use crate::nodes::{
+    Assign, BinOp, BoolExpr, Call, DataType, DeclAssign, ForLoop, IfStatement, Index, Node,
+    Program, Read, UnaryOp, Var, VarDecl,
+};
+use crate::tokens::{Span, TokenType, Value};
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SemanticError {
+    pub message: String,
+    pub span: Span,
+}
+
+impl SemanticError {
+    pub(crate) fn new(message: String, span: Span) -> Self {
+        SemanticError { message, span }
+    }
+}
+
+impl Display for SemanticError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Semantic error at {}: {}", self.span, self.message)
+    }
+}
+
+impl std::error::Error for SemanticError {}
+
+type CheckResult = Result<Option<DataType>, SemanticError>;
+
+/// Static checks run before interpretation: declarations, types and builtin calls.
+#[derive(Default)]
+pub struct Checker {
+    symbols: HashMap<String, DataType>,
+    loop_vars: Vec<String>,
+}
+
+impl Checker {
+    pub fn new() -> Self {
+        Checker {
+            symbols: HashMap::new(),
+            loop_vars: Vec::new(),
+        }
+    }
+
+    pub fn check(&mut self, node: &Node) -> CheckResult {
+        match node {
+            Node::Program(n) => self.check_program(n),
+            Node::VarDecl(n) => self.check_var_decl(n),
+            Node::DeclAssign(n) => self.check_decl_assign(n),
+            Node::Assign(n) => self.check_assign(n),
+            Node::IfStatement(n) => self.check_if_statement(n),
+            Node::ForLoop(n) => self.check_for_loop(n),
+            Node::PrintVar(n) => self.check_var(&n.var_node).map(|_| None),
+            Node::PrintStr(_) => Ok(None),
+            Node::Read(n) => self.check_read(n),
+            Node::BinOp(n) => self.check_bin_op(n),
+            Node::UnaryOp(n) => self.check_unary_op(n),
+            Node::BoolExpr(n) => self.check_bool_expr(n),
+            Node::Call(n) => self.check_call(n),
+            Node::Var(n) => self.check_var(n).map(Some),
+            Node::Num(_) => Ok(Some(DataType::Int)),
+            Node::Real(_) => Ok(Some(DataType::Real)),
+            Node::Str(_) => Ok(Some(DataType::Str)),
+            Node::Char(_) => Ok(Some(DataType::Char)),
+            Node::Index(n) => self.check_index(n),
+            Node::NoOp => Ok(None),
+        }
+    }
+
+    fn check_program(&mut self, program: &Program) -> CheckResult {
+        self.check_statements(&program.children)
+    }
+
+    fn check_statements(&mut self, statements: &[Node]) -> CheckResult {
+        for statement in statements {
+            self.check(statement)?;
+        }
+        Ok(None)
+    }
+
+    fn expr(&mut self, node: &Node) -> Result<DataType, SemanticError> {
+        match self.check(node)? {
+            Some(t) => Ok(t),
+            None => Err(SemanticError::new(
+                String::from("expected an expression"),
+                node.span().unwrap_or_default(),
+            )),
+        }
+    }
+
+    fn expect(&mut self, node: &Node, expected: DataType, context: &str) -> CheckResult {
+        let found = self.expr(node)?;
+        if !expected.accepts(found) {
+            return Err(SemanticError::new(
+                format!("{} expects {}, found {}", context, expected, found),
+                node.span().unwrap_or_default(),
+            ));
+        }
+        Ok(Some(found))
+    }
+
+    fn declare(&mut self, var: &Var, data_type: DataType) -> CheckResult {
+        let name = var_name(var);
+        if self.symbols.contains_key(&name) {
+            return Err(SemanticError::new(
+                format!("variable {} declared twice", name),
+                var.token.span,
+            ));
+        }
+        self.symbols.insert(name, data_type);
+        Ok(None)
+    }
+
+    fn check_var(&mut self, var: &Var) -> Result<DataType, SemanticError> {
+        let name = var_name(var);
+        match self.symbols.get(&name) {
+            Some(t) => Ok(*t),
+            None => Err(SemanticError::new(
+                format!("variable {} used before declaration", name),
+                var.token.span,
+            )),
+        }
+    }
+
+    fn check_assignable(&mut self, var: &Var) -> Result<DataType, SemanticError> {
+        let data_type = self.check_var(var)?;
+        let name = var_name(var);
+        if self.loop_vars.contains(&name) {
+            return Err(SemanticError::new(
+                format!("loop variable {} cannot be assigned inside the loop", name),
+                var.token.span,
+            ));
+        }
+        Ok(data_type)
+    }
+
+    fn check_var_decl(&mut self, var_decl: &VarDecl) -> CheckResult {
+        self.declare(&var_decl.var_node, var_decl.type_node.data_type())
+    }
+
+    fn check_decl_assign(&mut self, decl_assign: &DeclAssign) -> CheckResult {
+        let data_type = decl_assign.type_node.data_type();
+        self.expect(&decl_assign.right, data_type, "declaration")?;
+        self.declare(&decl_assign.left, data_type)
+    }
+
+    fn check_assign(&mut self, assign: &Assign) -> CheckResult {
+        let data_type = self.check_assignable(&assign.left)?;
+        self.expect(&assign.right, data_type, "assignment")?;
+        Ok(None)
+    }
+
+    fn check_read(&mut self, read: &Read) -> CheckResult {
+        match self.check_assignable(&read.var_node)? {
+            DataType::Int | DataType::Str | DataType::Real => Ok(None),
+            found => Err(SemanticError::new(
+                format!("read expects int, real or string, found {}", found),
+                read.var_node.token.span,
+            )),
+        }
+    }
+
+    fn check_if_statement(&mut self, if_statement: &IfStatement) -> CheckResult {
+        self.expect(&if_statement.bool_expr, DataType::Bool, "if condition")?;
+        self.check_statements(&if_statement.statements)?;
+        self.check_statements(&if_statement.else_statements)
+    }
+
+    fn check_for_loop(&mut self, for_loop: &ForLoop) -> CheckResult {
+        if self.check_assignable(&for_loop.var_node)? != DataType::Int {
+            return Err(SemanticError::new(
+                String::from("loop variable must be declared as integer"),
+                for_loop.var_node.token.span,
+            ));
+        }
+        self.expect(&for_loop.start, DataType::Int, "for range")?;
+        self.expect(&for_loop.end, DataType::Int, "for range")?;
+        self.loop_vars.push(var_name(&for_loop.var_node));
+        let result = self.check_statements(&for_loop.statements);
+        self.loop_vars.pop();
+        result
+    }
+
+    fn check_bin_op(&mut self, bin_op: &BinOp) -> CheckResult {
+        let left = self.expr(&bin_op.left)?;
+        let right = self.expr(&bin_op.right)?;
+        match (&bin_op.op.type_, left, right) {
+            (_, DataType::Int, DataType::Int) => Ok(Some(DataType::Int)),
+            (TokenType::Plus, _, _)
+            | (TokenType::Minus, _, _)
+            | (TokenType::Mul, _, _)
+            | (TokenType::Div, _, _)
+                if left.is_numeric() && right.is_numeric() =>
+            {
+                Ok(Some(DataType::Real))
+            }
+            (TokenType::Plus, DataType::Str, DataType::Str)
+            | (TokenType::Plus, DataType::Str, DataType::Char)
+            | (TokenType::Plus, DataType::Char, DataType::Str)
+            | (TokenType::Plus, DataType::Char, DataType::Char) => Ok(Some(DataType::Str)),
+            (_, left, right) => Err(SemanticError::new(
+                format!(
+                    "operator {} cannot be applied to {} and {}",
+                    bin_op.op.value, left, right
+                ),
+                bin_op.op.span,
+            )),
+        }
+    }
+
+    fn check_unary_op(&mut self, unary_op: &UnaryOp) -> CheckResult {
+        let context = format!("unary {}", unary_op.op.value);
+        // numeric operand, the result keeps its type
+        self.expect(&unary_op.expr, DataType::Real, &context)
+    }
+
+    fn check_bool_expr(&mut self, bool_expr: &BoolExpr) -> CheckResult {
+        let context = format!("operator {}", bool_expr.op.value);
+        match bool_expr.op.type_ {
+            TokenType::Not => {
+                self.expect(&bool_expr.right, DataType::Bool, &context)?;
+            }
+            TokenType::And | TokenType::Or => {
+                self.expect(&bool_expr.left, DataType::Bool, &context)?;
+                self.expect(&bool_expr.right, DataType::Bool, &context)?;
+            }
+            _ => {
+                let left = self.expr(&bool_expr.left)?;
+                let right = self.expr(&bool_expr.right)?;
+                if left != right && !(left.is_numeric() && right.is_numeric()) {
+                    return Err(SemanticError::new(
+                        format!("{} cannot compare {} and {}", context, left, right),
+                        bool_expr.op.span,
+                    ));
+                }
+            }
+        }
+        Ok(Some(DataType::Bool))
+    }
+
+    fn check_index(&mut self, index: &Index) -> CheckResult {
+        self.expect(&index.target, DataType::Str, "indexing")?;
+        self.expect(&index.index, DataType::Int, "index")?;
+        Ok(Some(DataType::Char))
+    }
+
+    fn check_call(&mut self, call: &Call) -> CheckResult {
+        let name = var_name_of(&call.value);
+        let builtin = match BUILTINS.get(&name[..]) {
+            Some(b) => b,
+            None => {
+                return Err(SemanticError::new(
+                    format!("unknown function {}", name),
+                    call.token.span,
+                ));
+            }
+        };
+        if builtin.params.len() != call.args.len() {
+            return Err(SemanticError::new(
+                format!(
+                    "{} expects {} argument(s), found {}",
+                    name,
+                    builtin.params.len(),
+                    call.args.len()
+                ),
+                call.token.span,
+            ));
+        }
+        for (i, (arg, param)) in call.args.iter().zip(builtin.params).enumerate() {
+            let context = format!("argument {} of {}", i + 1, name);
+            self.expect(arg, *param, &context)?;
+        }
+        Ok(Some(builtin.returns))
+    }
+}
+
+fn var_name(var: &Var) -> String {
+    var_name_of(&var.value)
+}
+
+fn var_name_of(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.to_lowercase(),
+        _ => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    fn check(text: &str) -> CheckResult {
+        let mut parser = Parser::new(Scanner::new(text.to_string()));
+        Checker::new().check(&parser.parse().unwrap())
+    }
+
+    #[test]
+    fn rejects_invalid_programs() {
+        assert!(check("var a : int := abs(1) + max(2, 3);").is_ok());
+        assert!(check("var a : int := abs(1, 2);").is_err());
+        assert!(check("var a : int := abs(\"1\");").is_err());
+        assert!(check("var a : string := to_string(1) + 1;").is_err());
+        assert!(check("var a : int := nope(1);").is_err());
+        assert!(check("var a : int; var a : string;").is_err());
+        assert!(check("a := 1;").is_err());
+        assert!(check("var b : bool := 1 < \"1\";").is_err());
+        assert!(check("var i : int; for i in 0..3 do i := 1; end for;").is_err());
+    }
+}
@@ -0,0 +1,672 @@
+//! A debug adapter speaking the Debug Adapter Protocol over stdio, with the same
+//! `Content-Length` framing as the language server. The program runs on the
+//! adapter's thread, so requests are read while it is paused at a statement.
+
+use crate::checker::Checker;
+use crate::debugger::{self, DebugAction, Debugger};
+use crate::interpreter::{Interpreter, Io};
+use crate::json::Json;
+use crate::lsp::{read_message, write_message};
+use crate::nodes::Node;
+use crate::parser::Parser;
+use crate::scanner::Scanner;
+use crate::tokens::{Span, Value};
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::io::{self, BufRead, Write};
+use std::rc::Rc;
+
+// MiniPL has no threads or procedures, so there is one thread with one frame
+const THREAD_ID: usize = 1;
+const FRAME_ID: usize = 1;
+const GLOBALS_REFERENCE: usize = 1;
+
+/// Serves one debugging session until the client disconnects or closes the input.
+pub fn run<R: BufRead + 'static, W: Write + 'static>(input: R, output: W) -> io::Result<()> {
+    let session = Rc::new(RefCell::new(Session {
+        input: Box::new(input),
+        output: Box::new(output),
+        seq: 0,
+        program: None,
+        configured: false,
+        breakpoints: BTreeSet::new(),
+        last_action: DebugAction::StepIn,
+        disconnected: false,
+    }));
+    loop {
+        let message = session.borrow_mut().read()?;
+        let message = match message {
+            Some(message) => message,
+            None => return Ok(()),
+        };
+        let next = session.borrow_mut().handle(&message, None)?;
+        match next {
+            Next::Start => execute(&session)?,
+            Next::Quit => return Ok(()),
+            Next::Wait | Next::Resume(_) => {}
+        }
+        if session.borrow().disconnected {
+            return Ok(());
+        }
+    }
+}
+
+struct Program {
+    path: String,
+    source: String,
+    stop_on_entry: bool,
+    big_integers: bool,
+    input: Vec<String>,
+    // lines a statement starts on, where breakpoints can stop
+    lines: BTreeSet<usize>,
+}
+
+// what the caller does after a request was answered
+enum Next {
+    Wait,
+    Start,
+    Resume(DebugAction),
+    Quit,
+}
+
+struct Session {
+    input: Box<dyn BufRead>,
+    output: Box<dyn Write>,
+    seq: usize,
+    program: Option<Program>,
+    configured: bool,
+    breakpoints: BTreeSet<usize>,
+    last_action: DebugAction,
+    disconnected: bool,
+}
+
+impl Session {
+    fn read(&mut self) -> io::Result<Option<Json>> {
+        loop {
+            let body = match read_message(&mut self.input)? {
+                Some(body) => body,
+                None => return Ok(None),
+            };
+            // the protocol has no way to answer a message that cannot be parsed
+            if let Ok(message) = Json::parse(&body) {
+                return Ok(Some(message));
+            }
+        }
+    }
+
+    fn send(&mut self, kind: &str, mut members: Vec<(&str, Json)>) -> io::Result<()> {
+        self.seq += 1;
+        members.insert(0, ("seq", self.seq.into()));
+        members.insert(1, ("type", kind.into()));
+        write_message(&mut self.output, &Json::object(members))
+    }
+
+    fn respond(&mut self, request: &Json, result: Result<Json, String>) -> io::Result<()> {
+        let request_seq = request.get("seq").cloned().unwrap_or(Json::Null);
+        let command = request.get("command").cloned().unwrap_or(Json::Null);
+        let mut members = vec![("request_seq", request_seq), ("command", command)];
+        match result {
+            Ok(body) => {
+                members.push(("success", true.into()));
+                members.push(("body", body));
+            }
+            Err(message) => {
+                members.push(("success", false.into()));
+                members.push(("message", message.into()));
+            }
+        }
+        self.send("response", members)
+    }
+
+    fn event(&mut self, event: &str, body: Json) -> io::Result<()> {
+        self.send("event", vec![("event", event.into()), ("body", body)])
+    }
+
+    // answers a request, `paused` is where the program stopped and its variables
+    fn handle(
+        &mut self,
+        request: &Json,
+        paused: Option<(Span, &mut HashMap<String, Value>)>,
+    ) -> io::Result<Next> {
+        let command = request.get("command").and_then(Json::as_str).unwrap_or("");
+        let arguments = request.get("arguments").unwrap_or(&Json::Null);
+        let mut next = Next::Wait;
+        let result = match (command, paused) {
+            ("initialize", _) => {
+                let capabilities = Json::object(vec![
+                    ("supportsConfigurationDoneRequest", true.into()),
+                    ("supportsSetVariable", true.into()),
+                    ("supportsTerminateRequest", true.into()),
+                ]);
+                self.respond(request, Ok(capabilities))?;
+                return self
+                    .event("initialized", Json::object(vec![]))
+                    .map(|_| next);
+            }
+            ("launch", _) => {
+                let result = self.launch(arguments);
+                if result.is_ok() && self.configured {
+                    next = Next::Start;
+                }
+                result
+            }
+            ("configurationDone", _) => {
+                self.configured = true;
+                if self.program.is_some() {
+                    next = Next::Start;
+                }
+                Ok(Json::object(vec![]))
+            }
+            ("setBreakpoints", _) => Ok(self.set_breakpoints(arguments)),
+            ("setExceptionBreakpoints", _) => {
+                Ok(Json::object(vec![("breakpoints", Json::Array(Vec::new()))]))
+            }
+            ("threads", _) => {
+                let thread = Json::object(vec![("id", THREAD_ID.into()), ("name", "main".into())]);
+                Ok(Json::object(vec![("threads", Json::Array(vec![thread]))]))
+            }
+            ("stackTrace", paused) => {
+                let frames = match paused {
+                    Some((span, _)) => vec![self.frame(span)],
+                    None => Vec::new(),
+                };
+                Ok(Json::object(vec![
+                    ("totalFrames", frames.len().into()),
+                    ("stackFrames", Json::Array(frames)),
+                ]))
+            }
+            ("scopes", _) => {
+                let globals = Json::object(vec![
+                    ("name", "Globals".into()),
+                    ("variablesReference", GLOBALS_REFERENCE.into()),
+                    ("expensive", false.into()),
+                ]);
+                Ok(Json::object(vec![("scopes", Json::Array(vec![globals]))]))
+            }
+            ("variables", Some((_, scope))) => {
+                let mut names: Vec<&String> = scope.keys().collect();
+                names.sort();
+                let variables = names
+                    .into_iter()
+                    .map(|name| variable(name, &scope[name]))
+                    .collect();
+                Ok(Json::object(vec![("variables", Json::Array(variables))]))
+            }
+            ("setVariable", Some((_, scope))) => set_variable(scope, arguments),
+            ("continue", Some(_)) => {
+                next = Next::Resume(DebugAction::Continue);
+                Ok(Json::object(vec![("allThreadsContinued", true.into())]))
+            }
+            ("next", Some(_)) | ("stepIn", Some(_)) | ("stepOut", Some(_)) => {
+                next = Next::Resume(match command {
+                    "next" => DebugAction::StepOver,
+                    "stepIn" => DebugAction::StepIn,
+                    _ => DebugAction::StepOut,
+                });
+                Ok(Json::object(vec![]))
+            }
+            ("variables", None)
+            | ("setVariable", None)
+            | ("continue", None)
+            | ("next", None)
+            | ("stepIn", None)
+            | ("stepOut", None) => Err(String::from("the program is not paused")),
+            ("disconnect", _) | ("terminate", _) => {
+                self.disconnected = true;
+                next = Next::Quit;
+                Ok(Json::object(vec![]))
+            }
+            _ => Err(format!("unsupported request {}", command)),
+        };
+        if let Next::Resume(action) = next {
+            self.last_action = action;
+        }
+        self.respond(request, result)?;
+        Ok(next)
+    }
+
+    fn launch(&mut self, arguments: &Json) -> Result<Json, String> {
+        let path = arguments
+            .get("program")
+            .and_then(Json::as_str)
+            .ok_or("launch needs the path of the program")?;
+        let source = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let tree = Parser::new(Scanner::new(source.clone()))
+            .parse()
+            .and_then(|tree| {
+                Checker::new().check(&tree)?;
+                Ok(tree)
+            })
+            .map_err(|e| format!("{}: {}", path, e))?;
+        let input = match arguments.get("input").and_then(Json::as_str) {
+            Some(input) => std::fs::read_to_string(input)
+                .map_err(|e| format!("{}: {}", input, e))?
+                .lines()
+                .map(String::from)
+                .collect(),
+            None => Vec::new(),
+        };
+        let mut lines = BTreeSet::new();
+        if let Node::Program(program) = &tree {
+            statement_lines(&program.children, &mut lines);
+        }
+        let flag = |name| arguments.get(name).and_then(Json::as_bool).unwrap_or(false);
+        self.program = Some(Program {
+            path: path.to_string(),
+            source,
+            stop_on_entry: flag("stopOnEntry"),
+            big_integers: flag("bigInt"),
+            input,
+            lines,
+        });
+        Ok(Json::object(vec![]))
+    }
+
+    // replaces the breakpoints, lines without a statement are reported unverified
+    fn set_breakpoints(&mut self, arguments: &Json) -> Json {
+        let requested: Vec<usize> = arguments
+            .get("breakpoints")
+            .and_then(Json::as_array)
+            .unwrap_or(&[])
+            .iter()
+            .filter_map(|b| b.get("line").and_then(Json::as_usize))
+            .collect();
+        let lines = self.program.as_ref().map(|program| &program.lines);
+        let breakpoints = requested
+            .into_iter()
+            .map(|line| {
+                let verified = lines.is_none_or(|lines| lines.contains(&line));
+                let mut members = vec![("verified", verified.into()), ("line", line.into())];
+                if !verified {
+                    members.push(("message", "no statement starts on this line".into()));
+                }
+                (line, Json::object(members))
+            })
+            .collect::<Vec<_>>();
+        self.breakpoints = breakpoints
+            .iter()
+            .filter(|(_, b)| b.get("verified") == Some(&Json::Bool(true)))
+            .map(|(line, _)| *line)
+            .collect();
+        let breakpoints = breakpoints.into_iter().map(|(_, b)| b).collect();
+        Json::object(vec![("breakpoints", Json::Array(breakpoints))])
+    }
+
+    fn frame(&self, span: Span) -> Json {
+        let path = self.program.as_ref().map_or("", |p| p.path.as_str());
+        let name = std::path::Path::new(path)
+            .file_name()
+            .map_or(String::new(), |name| name.to_string_lossy().to_string());
+        Json::object(vec![
+            ("id", FRAME_ID.into()),
+            ("name", "main".into()),
+            (
+                "source",
+                Json::object(vec![("name", name.into()), ("path", path.into())]),
+            ),
+            ("line", span.line.into()),
+            ("column", span.column.into()),
+        ])
+    }
+}
+
+fn variable(name: &str, value: &Value) -> Json {
+    Json::object(vec![
+        ("name", name.into()),
+        ("value", debugger::show(value).into()),
+        ("type", debugger::type_name(value).into()),
+        ("variablesReference", 0.into()),
+    ])
+}
+
+fn set_variable(scope: &mut HashMap<String, Value>, arguments: &Json) -> Result<Json, String> {
+    let name = arguments
+        .get("name")
+        .and_then(Json::as_str)
+        .ok_or("setVariable needs a name")?
+        .to_lowercase();
+    let text = arguments
+        .get("value")
+        .and_then(Json::as_str)
+        .ok_or("setVariable needs a value")?;
+    let current = scope
+        .get(&name)
+        .ok_or_else(|| format!("unknown variable {}", name))?;
+    let value = debugger::parse_value(current, text)
+        .ok_or_else(|| format!("{} is not a valid {}", text, debugger::type_name(current)))?;
+    let body = variable(&name, &value);
+    scope.insert(name, value);
+    Ok(body)
+}
+
+fn statement_lines(statements: &[Node], lines: &mut BTreeSet<usize>) {
+    for statement in statements {
+        if let Some(span) = statement.span() {
+            lines.insert(span.line);
+        }
+        match statement {
+            Node::ForLoop(n) => statement_lines(&n.statements, lines),
+            Node::IfStatement(n) => {
+                statement_lines(&n.statements, lines);
+                statement_lines(&n.else_statements, lines);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn execute(session: &Rc<RefCell<Session>>) -> io::Result<()> {
+    let (source, big_integers, input) = {
+        let session = session.borrow();
+        let program = session.program.as_ref().unwrap();
+        let input = program.input.iter().cloned().collect();
+        (program.source.clone(), program.big_integers, input)
+    };
+    let mut interpreter = Interpreter::new(Parser::new(Scanner::new(source)));
+    interpreter.big_integers = big_integers;
+    interpreter.io = Box::new(Console {
+        session: session.clone(),
+        input,
+    });
+    interpreter.debugger = Some(Box::new(Adapter {
+        session: session.clone(),
+        entered: false,
+    }));
+    let result = interpreter.interpret();
+
+    let mut session = session.borrow_mut();
+    if session.disconnected {
+        return Ok(());
+    }
+    let exit_code: usize = match result {
+        Ok(_) => 0,
+        Err(e) => {
+            output(&mut session, "stderr", format!("{}\n", e))?;
+            1
+        }
+    };
+    session.event("exited", Json::object(vec![("exitCode", exit_code.into())]))?;
+    session.event("terminated", Json::object(vec![]))
+}
+
+fn output(session: &mut Session, category: &str, text: String) -> io::Result<()> {
+    session.event(
+        "output",
+        Json::object(vec![("category", category.into()), ("output", text.into())]),
+    )
+}
+
+// the program's `print` becomes output events, `read` takes lines from the launch input
+struct Console {
+    session: Rc<RefCell<Session>>,
+    input: VecDeque<String>,
+}
+
+impl Io for Console {
+    fn print(&mut self, line: &str) {
+        let _ = output(
+            &mut self.session.borrow_mut(),
+            "stdout",
+            format!("{}\n", line),
+        );
+    }
+
+    fn read_line(&mut self) -> Option<String> {
+        self.input.pop_front()
+    }
+}
+
+struct Adapter {
+    session: Rc<RefCell<Session>>,
+    entered: bool,
+}
+
+impl Debugger for Adapter {
+    fn is_breakpoint(&self, line: usize) -> bool {
+        self.session.borrow().breakpoints.contains(&line)
+    }
+
+    fn on_statement(&mut self, span: Span, scope: &mut HashMap<String, Value>) -> DebugAction {
+        let mut session = self.session.borrow_mut();
+        let breakpoint = session.breakpoints.contains(&span.line);
+        let reason = if !self.entered {
+            self.entered = true;
+            let stop_on_entry = session.program.as_ref().is_some_and(|p| p.stop_on_entry);
+            match (stop_on_entry, breakpoint) {
+                (true, _) => "entry",
+                (false, true) => "breakpoint",
+                // execution always starts paused, run on to the first breakpoint
+                (false, false) => {
+                    session.last_action = DebugAction::Continue;
+                    return DebugAction::Continue;
+                }
+            }
+        } else if session.last_action == DebugAction::Continue {
+            "breakpoint"
+        } else {
+            "step"
+        };
+        let stopped = Json::object(vec![
+            ("reason", reason.into()),
+            ("threadId", THREAD_ID.into()),
+            ("allThreadsStopped", true.into()),
+        ]);
+        if session.event("stopped", stopped).is_err() {
+            return DebugAction::Stop;
+        }
+        loop {
+            let message = match session.read() {
+                Ok(Some(message)) => message,
+                _ => return DebugAction::Stop,
+            };
+            match session.handle(&message, Some((span, &mut *scope))) {
+                Ok(Next::Resume(action)) => return action,
+                Ok(Next::Wait) | Ok(Next::Start) => {}
+                Ok(Next::Quit) | Err(_) => return DebugAction::Stop,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // runs a scripted session, returning everything the adapter sent
+    fn session(requests: &[(&str, String)]) -> Vec<Json> {
+        let mut input = Vec::new();
+        for (seq, (command, arguments)) in requests.iter().enumerate() {
+            let message = format!(
+                r#"{{"seq":{},"type":"request","command":"{}","arguments":{}}}"#,
+                seq + 1,
+                command,
+                arguments
+            );
+            write!(
+                input,
+                "Content-Length: {}\r\n\r\n{}",
+                message.len(),
+                message
+            )
+            .unwrap();
+        }
+        let output = Rc::new(RefCell::new(Vec::new()));
+        run(io::Cursor::new(input), Shared(output.clone())).unwrap();
+        let output = output.borrow();
+        let mut output = &output[..];
+        let mut messages = Vec::new();
+        while let Some(body) = read_message(&mut output).unwrap() {
+            messages.push(Json::parse(&body).unwrap());
+        }
+        messages
+    }
+
+    fn response(messages: &[Json], request_seq: usize) -> &Json {
+        messages
+            .iter()
+            .find(|m| m.get("request_seq").and_then(Json::as_usize) == Some(request_seq))
+            .unwrap()
+    }
+
+    // events with their reason, output or exit code
+    fn events(messages: &[Json]) -> Vec<String> {
+        messages
+            .iter()
+            .filter_map(|m| {
+                let event = m.get("event")?.as_str()?;
+                let detail = match event {
+                    "stopped" => m.path(&["body", "reason"]),
+                    "output" => m.path(&["body", "output"]),
+                    "exited" => m.path(&["body", "exitCode"]),
+                    _ => None,
+                };
+                Some(match detail {
+                    Some(detail) => format!("{} {}", event, detail),
+                    None => event.to_string(),
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn debugs_a_scripted_session() {
+        let dir = std::env::temp_dir();
+        let program = dir.join(format!("mini-pl-dap-{}.mpl", std::process::id()));
+        let input = dir.join(format!("mini-pl-dap-{}.in", std::process::id()));
+        let source = "var n : int;\nread n;\nvar i : int;\nfor i in 0..n - 1 do\n    print i;\nend for;\n\nprint n;\n";
+        std::fs::write(&program, source).unwrap();
+        std::fs::write(&input, "3\n").unwrap();
+        let launch = format!(
+            r#"{{"program":"{}","input":"{}"}}"#,
+            program.display(),
+            input.display()
+        );
+        let messages = session(&[
+            ("initialize", String::from(r#"{"adapterID":"mini-pl"}"#)),
+            ("launch", launch),
+            (
+                "setBreakpoints",
+                String::from(r#"{"source":{},"breakpoints":[{"line":5},{"line":7}]}"#),
+            ),
+            ("configurationDone", String::from("{}")),
+            ("stackTrace", String::from(r#"{"threadId":1}"#)),
+            ("scopes", String::from(r#"{"frameId":1}"#)),
+            ("variables", String::from(r#"{"variablesReference":1}"#)),
+            (
+                "setVariable",
+                String::from(r#"{"variablesReference":1,"name":"n","value":"1"}"#),
+            ),
+            (
+                "setBreakpoints",
+                String::from(r#"{"source":{},"breakpoints":[]}"#),
+            ),
+            ("next", String::from(r#"{"threadId":1}"#)),
+            ("stepOut", String::from(r#"{"threadId":1}"#)),
+            ("continue", String::from(r#"{"threadId":1}"#)),
+            ("disconnect", String::from("{}")),
+        ]);
+        std::fs::remove_file(&program).unwrap();
+        std::fs::remove_file(&input).unwrap();
+
+        assert!(messages
+            .iter()
+            .all(|m| m.get("success") != Some(&Json::Bool(false))));
+        assert_eq!(
+            events(&messages),
+            [
+                "initialized",
+                "stopped \"breakpoint\"",
+                "output \"0\\n\"",
+                "stopped \"step\"",
+                "output \"1\\n\"",
+                "output \"2\\n\"",
+                "stopped \"step\"",
+                "output \"1\\n\"",
+                "exited 0",
+                "terminated"
+            ]
+        );
+
+        let breakpoints = response(&messages, 3)
+            .path(&["body", "breakpoints"])
+            .unwrap();
+        let verified: Vec<bool> = breakpoints
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|b| b.get("verified").and_then(Json::as_bool).unwrap())
+            .collect();
+        assert_eq!(verified, [true, false]);
+
+        let frame = &response(&messages, 5)
+            .path(&["body", "stackFrames"])
+            .unwrap();
+        let frame = &frame.as_array().unwrap()[0];
+        assert_eq!(frame.get("line").and_then(Json::as_usize), Some(5));
+        assert_eq!(frame.get("column").and_then(Json::as_usize), Some(5));
+
+        let variables = response(&messages, 7).path(&["body", "variables"]).unwrap();
+        let variables: Vec<String> = variables
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|v| {
+                let name = v.get("name").and_then(Json::as_str).unwrap();
+                let value = v.get("value").and_then(Json::as_str).unwrap();
+                format!("{} = {}", name, value)
+            })
+            .collect();
+        assert_eq!(variables, ["i = 0", "n = 3"]);
+    }
+
+    #[test]
+    fn stops_at_a_loop_breakpoint_in_every_iteration() {
+        let program =
+            std::env::temp_dir().join(format!("mini-pl-dap-loop-{}.mpl", std::process::id()));
+        let source =
+            "var n : int := 3;\nvar i : int;\n\nfor i in 0..n - 1 do\n    print i;\nend for;\n";
+        std::fs::write(&program, source).unwrap();
+        let launch = format!(r#"{{"program":"{}"}}"#, program.display());
+        let messages = session(&[
+            ("initialize", String::from(r#"{"adapterID":"mini-pl"}"#)),
+            ("launch", launch),
+            (
+                "setBreakpoints",
+                String::from(r#"{"source":{},"breakpoints":[{"line":5}]}"#),
+            ),
+            ("configurationDone", String::from("{}")),
+            ("continue", String::from(r#"{"threadId":1}"#)),
+            ("continue", String::from(r#"{"threadId":1}"#)),
+            ("continue", String::from(r#"{"threadId":1}"#)),
+            ("disconnect", String::from("{}")),
+        ]);
+        std::fs::remove_file(&program).unwrap();
+
+        assert_eq!(
+            events(&messages),
+            [
+                "initialized",
+                "stopped \"breakpoint\"",
+                "output \"0\\n\"",
+                "stopped \"breakpoint\"",
+                "output \"1\\n\"",
+                "stopped \"breakpoint\"",
+                "output \"2\\n\"",
+                "exited 0",
+                "terminated"
+            ]
+        );
+    }
+}
@@ -0,0 +1,394 @@
+use crate::nodes::{Node, Var};
+use crate::tokens::{Span, Value};
+use std::collections::BTreeSet;
+use std::fmt::{self, Display, Formatter};
+
+/// What a point in the control flow graph does.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Entry,
+    Exit,
+    /// `var x : T` or `var x : T := e`, both define `x`.
+    Declaration,
+    Assignment,
+    Read,
+    Print,
+    /// The condition of an `if`, branching to the `then` and `else` statements.
+    Condition,
+    /// The range bounds of a `for`, evaluated once.
+    LoopBounds,
+    /// Before every iteration, branching to the next iteration or past the loop.
+    LoopTest,
+    /// The write of the control variable that starts an iteration.
+    LoopIteration,
+}
+
+/// One point of the control flow graph: a simple statement or a part of a compound one.
+#[derive(Debug, Clone)]
+pub struct Point {
+    pub kind: Kind,
+    /// Span of the statement, for a `LoopIteration` the span of the control variable.
+    pub span: Span,
+    /// The variable written here.
+    pub def: Option<String>,
+    /// The variables read here.
+    pub uses: BTreeSet<String>,
+    pub successors: Vec<usize>,
+    pub predecessors: Vec<usize>,
+}
+
+/// The control flow graph of a program, with one point per simple statement and
+/// per decision. Point 0 is the entry and point 1 the exit.
+#[derive(Debug, Clone)]
+pub struct Cfg {
+    pub points: Vec<Point>,
+}
+
+pub const ENTRY: usize = 0;
+pub const EXIT: usize = 1;
+
+impl Cfg {
+    pub fn new(program: &Node) -> Self {
+        let mut cfg = Cfg { points: Vec::new() };
+        cfg.add(Kind::Entry, Span::default(), None, BTreeSet::new(), vec![]);
+        cfg.add(Kind::Exit, Span::default(), None, BTreeSet::new(), vec![]);
+        let first = match program {
+            Node::Program(program) => cfg.block(&program.children, EXIT),
+            _ => EXIT,
+        };
+        cfg.points[ENTRY].successors.push(first);
+        for i in 0..cfg.points.len() {
+            for successor in cfg.points[i].successors.clone() {
+                cfg.points[successor].predecessors.push(i);
+            }
+        }
+        cfg
+    }
+
+    /// The points that define `name`.
+    pub fn definitions_of(&self, name: &str) -> Vec<usize> {
+        (0..self.points.len())
+            .filter(|&i| self.points[i].def.as_deref() == Some(name))
+            .collect()
+    }
+
+    fn add(
+        &mut self,
+        kind: Kind,
+        span: Span,
+        def: Option<String>,
+        uses: BTreeSet<String>,
+        successors: Vec<usize>,
+    ) -> usize {
+        self.points.push(Point {
+            kind,
+            span,
+            def,
+            uses,
+            successors,
+            predecessors: Vec::new(),
+        });
+        self.points.len() - 1
+    }
+
+    // built back to front: returns the first point of the statements, which continue at `next`
+    fn block(&mut self, statements: &[Node], mut next: usize) -> usize {
+        for statement in statements.iter().rev() {
+            next = self.statement(statement, next);
+        }
+        next
+    }
+
+    fn statement(&mut self, statement: &Node, next: usize) -> usize {
+        let span = statement.span().unwrap_or_default();
+        let none = BTreeSet::new();
+        match statement {
+            Node::VarDecl(n) => {
+                let def = Some(name(&n.var_node));
+                self.add(Kind::Declaration, span, def, none, vec![next])
+            }
+            Node::DeclAssign(n) => {
+                let def = Some(name(&n.left));
+                self.add(Kind::Declaration, span, def, uses(&n.right), vec![next])
+            }
+            Node::Assign(n) => {
+                let def = Some(name(&n.left));
+                self.add(Kind::Assignment, span, def, uses(&n.right), vec![next])
+            }
+            Node::Read(n) => {
+                let def = Some(name(&n.var_node));
+                self.add(Kind::Read, span, def, none, vec![next])
+            }
+            Node::PrintVar(n) => {
+                let uses = Some(name(&n.var_node)).into_iter().collect();
+                self.add(Kind::Print, span, None, uses, vec![next])
+            }
+            Node::PrintStr(_) => self.add(Kind::Print, span, None, none, vec![next]),
+            Node::IfStatement(n) => {
+                let then = self.block(&n.statements, next);
+                let otherwise = self.block(&n.else_statements, next);
+                let condition = uses(&n.bool_expr);
+                self.add(
+                    Kind::Condition,
+                    span,
+                    None,
+                    condition,
+                    vec![then, otherwise],
+                )
+            }
+            Node::ForLoop(n) => {
+                let test = self.add(Kind::LoopTest, span, None, none.clone(), vec![]);
+                let body = self.block(&n.statements, test);
+                let var = &n.var_node;
+                let iteration = self.add(
+                    Kind::LoopIteration,
+                    var.token.span,
+                    Some(name(var)),
+                    none,
+                    vec![body],
+                );
+                self.points[test].successors = vec![iteration, next];
+                let mut bounds = uses(&n.start);
+                bounds.extend(uses(&n.end));
+                self.add(Kind::LoopBounds, span, None, bounds, vec![test])
+            }
+            _ => next,
+        }
+    }
+}
+
+impl Display for Cfg {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        for (i, point) in self.points.iter().enumerate() {
+            write!(f, "{:>3} {:?}", i, point.kind)?;
+            if point.kind != Kind::Entry && point.kind != Kind::Exit {
+                write!(f, " at {}", point.span)?;
+            }
+            if let Some(def) = &point.def {
+                write!(f, " def {}", def)?;
+            }
+            if !point.uses.is_empty() {
+                let uses: Vec<&str> = point.uses.iter().map(String::as_str).collect();
+                write!(f, " use {}", uses.join(" "))?;
+            }
+            if !point.successors.is_empty() {
+                let successors: Vec<String> =
+                    point.successors.iter().map(usize::to_string).collect();
+                write!(f, " -> {}", successors.join(" "))?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// The facts that hold where control enters and leaves each point of a graph.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Solution<T> {
+    pub before: Vec<BTreeSet<T>>,
+    pub after: Vec<BTreeSet<T>>,
+}
+
+/// Which definitions, as point indices, may reach each point: a forward analysis
+/// where a point generates its own definition and kills the others of its variable.
+pub fn reaching_definitions(cfg: &Cfg) -> Solution<usize> {
+    let n = cfg.points.len();
+    let mut solution = Solution {
+        before: vec![BTreeSet::new(); n],
+        after: vec![BTreeSet::new(); n],
+    };
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for i in 0..n {
+            let point = &cfg.points[i];
+            let before: BTreeSet<usize> = point
+                .predecessors
+                .iter()
+                .flat_map(|&p| solution.after[p].iter().copied())
+                .collect();
+            let after: BTreeSet<usize> = match &point.def {
+                Some(def) => before
+                    .iter()
+                    .copied()
+                    .filter(|&d| cfg.points[d].def.as_ref() != Some(def))
+                    .chain(Some(i))
+                    .collect(),
+                None => before.clone(),
+            };
+            if before != solution.before[i] || after != solution.after[i] {
+                solution.before[i] = before;
+                solution.after[i] = after;
+                changed = true;
+            }
+        }
+    }
+    solution
+}
+
+/// Which variables are live at each point, i.e. may be read before they are written
+/// again: a backward analysis. Nothing is live at the exit, so a value the program
+/// never reads again is dead even if it stays in the interpreter's variables.
+pub fn liveness(cfg: &Cfg) -> Solution<String> {
+    let n = cfg.points.len();
+    let mut solution = Solution {
+        before: vec![BTreeSet::new(); n],
+        after: vec![BTreeSet::new(); n],
+    };
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for i in (0..n).rev() {
+            let point = &cfg.points[i];
+            let after: BTreeSet<String> = point
+                .successors
+                .iter()
+                .flat_map(|&s| solution.before[s].iter().cloned())
+                .collect();
+            let mut before: BTreeSet<String> = after
+                .iter()
+                .filter(|var| point.def.as_ref() != Some(*var))
+                .cloned()
+                .collect();
+            before.extend(point.uses.iter().cloned());
+            if before != solution.before[i] || after != solution.after[i] {
+                solution.before[i] = before;
+                solution.after[i] = after;
+                changed = true;
+            }
+        }
+    }
+    solution
+}
+
+/// The variables an expression reads.
+pub fn uses(expr: &Node) -> BTreeSet<String> {
+    let mut vars = BTreeSet::new();
+    collect_uses(expr, &mut vars);
+    vars
+}
+
+fn collect_uses(expr: &Node, vars: &mut BTreeSet<String>) {
+    match expr {
+        Node::Var(var) => {
+            vars.insert(name(var));
+        }
+        Node::BinOp(n) => {
+            collect_uses(&n.left, vars);
+            collect_uses(&n.right, vars);
+        }
+        Node::BoolExpr(n) => {
+            collect_uses(&n.left, vars);
+            collect_uses(&n.right, vars);
+        }
+        Node::UnaryOp(n) => collect_uses(&n.expr, vars),
+        Node::Index(n) => {
+            collect_uses(&n.target, vars);
+            collect_uses(&n.index, vars);
+        }
+        Node::Call(n) => {
+            for arg in &n.args {
+                collect_uses(arg, vars);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The variables `statements` write, including in nested statements.
+pub fn defined(statements: &[Node]) -> BTreeSet<String> {
+    let mut names = BTreeSet::new();
+    for statement in statements {
+        match statement {
+            Node::VarDecl(n) => {
+                names.insert(name(&n.var_node));
+            }
+            Node::DeclAssign(n) => {
+                names.insert(name(&n.left));
+            }
+            Node::Assign(n) => {
+                names.insert(name(&n.left));
+            }
+            Node::Read(n) => {
+                names.insert(name(&n.var_node));
+            }
+            Node::ForLoop(n) => {
+                names.insert(name(&n.var_node));
+                names.extend(defined(&n.statements));
+            }
+            Node::IfStatement(n) => {
+                names.extend(defined(&n.statements));
+                names.extend(defined(&n.else_statements));
+            }
+            _ => {}
+        }
+    }
+    names
+}
+
+pub(crate) fn name(var: &Var) -> String {
+    match &var.value {
+        Value::String(s) => s.to_lowercase(),
+        _ => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    #[test]
+    fn analyses_a_loop() {
+        let text = "var n : int := 0;
+var i : int;
+for i in 1..3 do
+    if i < 2 do
+        n := n + i;
+    end if;
+end for;
+print n;";
+        let tree = Parser::new(Scanner::new(text.to_string())).parse().unwrap();
+        let cfg = Cfg::new(&tree);
+        let expected = "  0 Entry -> 9
+  1 Exit
+  2 Print at 8:1 use n -> 1
+  3 LoopTest at 3:1 -> 6 2
+  4 Assignment at 5:9 def n use i n -> 3
+  5 Condition at 4:5 use i -> 4 3
+  6 LoopIteration at 3:5 def i -> 5
+  7 LoopBounds at 3:1 -> 3
+  8 Declaration at 2:1 def i -> 7
+  9 Declaration at 1:1 def n -> 8
+";
+        assert_eq!(cfg.to_string(), expected);
+
+        // both the initial value and the one from the loop reach the print
+        let reaching = reaching_definitions(&cfg);
+        let print = cfg
+            .points
+            .iter()
+            .position(|p| p.kind == Kind::Print)
+            .unwrap();
+        let mut reaching_n: Vec<Span> = reaching.before[print]
+            .iter()
+            .filter(|&&d| cfg.points[d].def.as_deref() == Some("n"))
+            .map(|&d| cfg.points[d].span)
+            .collect();
+        reaching_n.sort_by_key(|span| span.start);
+        let lines: Vec<usize> = reaching_n.iter().map(|span| span.line).collect();
+        assert_eq!(lines, [1, 5]);
+
+        let live = liveness(&cfg);
+        let test = cfg
+            .points
+            .iter()
+            .position(|p| p.kind == Kind::LoopTest)
+            .unwrap();
+        let live_at_test: Vec<&str> = live.before[test].iter().map(String::as_str).collect();
+        assert_eq!(live_at_test, ["n"]);
+        assert!(live.after[print].is_empty());
+        assert_eq!(cfg.definitions_of("i").len(), 2);
+    }
+}
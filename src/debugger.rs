@@ -0,0 +1,390 @@
+use crate::scanner::Scanner;
+use crate::tokens::{Span, TokenType, Value};
+use std::collections::{BTreeSet, HashMap};
+use std::io::{Read, Write};
+
+/// How execution continues after a pause.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DebugAction {
+    /// Run until the next breakpoint.
+    Continue,
+    /// Pause at the next statement, including the first one in a `for` or `if` body.
+    StepIn,
+    /// Pause at the next statement that is not nested deeper than this one.
+    StepOver,
+    /// Pause at the first statement after the enclosing `for` or `if`.
+    StepOut,
+    /// Abandon the program with a runtime error.
+    Stop,
+}
+
+/// Drives an interpreter in debug mode. Frontends decide where breakpoints are
+/// and what to do when execution pauses.
+pub trait Debugger {
+    fn is_breakpoint(&self, line: usize) -> bool;
+
+    /// Called before the statement at `span` runs whenever stepping or a
+    /// breakpoint pauses there. Changes made to `scope` are seen by the program.
+    fn on_statement(&mut self, span: Span, scope: &mut HashMap<String, Value>) -> DebugAction;
+}
+
+const HELP: &str = "\
+commands:
+  break <line>, b     pause when execution reaches a line
+  delete <line>       remove a breakpoint
+  continue, c         run until the next breakpoint
+  step, s             step into for and if bodies
+  next, n             step over for and if statements
+  finish, f           run until the enclosing for or if is done
+  print [name], p     show a variable, or all of them
+  set <name> = <value>
+                      change a variable, strings and chars are written as literals
+  list, l             show the source around the current line
+  quit, q             stop the program
+";
+
+/// A command line debugger reading commands from `input`, one per line.
+pub struct Console<R, W> {
+    source: String,
+    input: R,
+    output: W,
+    breakpoints: BTreeSet<usize>,
+}
+
+impl<R: Read, W: Write> Console<R, W> {
+    pub fn new(source: String, input: R, output: W) -> Self {
+        Console {
+            source,
+            input,
+            output,
+            breakpoints: BTreeSet::new(),
+        }
+    }
+
+    // read byte by byte so nothing meant for the program's `read` is buffered here
+    fn read_command(&mut self) -> Option<String> {
+        let mut line = Vec::new();
+        let mut byte = [0];
+        loop {
+            match self.input.read(&mut byte) {
+                Ok(1) if byte[0] == b'\n' => break,
+                Ok(1) => line.push(byte[0]),
+                _ if line.is_empty() => return None,
+                _ => break,
+            }
+        }
+        Some(String::from_utf8_lossy(&line).trim().to_string())
+    }
+
+    fn show_lines(&mut self, current: usize, context: usize) {
+        let first = current.saturating_sub(context).max(1);
+        let lines: Vec<(usize, String)> = self
+            .source
+            .lines()
+            .enumerate()
+            .map(|(i, text)| (i + 1, text.to_string()))
+            .skip(first - 1)
+            .take(current + context + 1 - first)
+            .collect();
+        for (number, text) in lines {
+            let marker = if number == current { "->" } else { "  " };
+            let _ = writeln!(self.output, "{} {:>4} | {}", marker, number, text);
+        }
+    }
+
+    // runs one command, returning the action if it resumes execution
+    fn command(
+        &mut self,
+        line: &str,
+        span: Span,
+        scope: &mut HashMap<String, Value>,
+    ) -> Option<DebugAction> {
+        let (command, argument) = match line.split_once(' ') {
+            Some((command, argument)) => (command, argument.trim()),
+            None => (line, ""),
+        };
+        let result = match command {
+            "continue" | "c" => return Some(DebugAction::Continue),
+            "step" | "s" => return Some(DebugAction::StepIn),
+            "next" | "n" => return Some(DebugAction::StepOver),
+            "finish" | "f" => return Some(DebugAction::StepOut),
+            "quit" | "q" => return Some(DebugAction::Stop),
+            "break" | "b" | "delete" => match argument.parse::<usize>() {
+                Ok(line) if command == "delete" => {
+                    self.breakpoints.remove(&line);
+                    Ok(format!("deleted breakpoint at line {}", line))
+                }
+                Ok(line) => {
+                    self.breakpoints.insert(line);
+                    Ok(format!("breakpoint at line {}", line))
+                }
+                Err(_) => Err(String::from("expected a line number")),
+            },
+            "print" | "p" if argument.is_empty() => {
+                let mut names: Vec<&String> = scope.keys().collect();
+                names.sort();
+                let values: Vec<String> = names
+                    .into_iter()
+                    .map(|name| format!("{} = {}", name, show(&scope[name])))
+                    .collect();
+                Ok(values.join("\n"))
+            }
+            "print" | "p" => match scope.get(&argument.to_lowercase()) {
+                Some(value) => Ok(format!("{} = {}", argument, show(value))),
+                None => Err(format!("unknown variable {}", argument)),
+            },
+            "set" => set(scope, argument),
+            "list" | "l" => {
+                self.show_lines(span.line, 3);
+                return None;
+            }
+            "help" | "h" => Ok(HELP.trim_end().to_string()),
+            "" => return None,
+            _ => Err(format!("unknown command {}, try help", command)),
+        };
+        let _ = match result {
+            Ok(text) if text.is_empty() => Ok(()),
+            Ok(text) => writeln!(self.output, "{}", text),
+            Err(message) => writeln!(self.output, "error: {}", message),
+        };
+        None
+    }
+}
+
+impl<R: Read, W: Write> Debugger for Console<R, W> {
+    fn is_breakpoint(&self, line: usize) -> bool {
+        self.breakpoints.contains(&line)
+    }
+
+    fn on_statement(&mut self, span: Span, scope: &mut HashMap<String, Value>) -> DebugAction {
+        let _ = writeln!(self.output, "stopped at {}", span);
+        self.show_lines(span.line, 0);
+        loop {
+            let _ = write!(self.output, "(debug) ");
+            let _ = self.output.flush();
+            let line = match self.read_command() {
+                Some(line) => line,
+                None => return DebugAction::Stop,
+            };
+            if let Some(action) = self.command(&line, span, scope) {
+                return action;
+            }
+        }
+    }
+}
+
+// `name = value`, where the value must have the variable's type
+fn set(scope: &mut HashMap<String, Value>, argument: &str) -> Result<String, String> {
+    let (name, text) = match argument.split_once('=') {
+        Some((name, text)) => (name.trim().trim_end_matches(':').trim(), text.trim()),
+        None => return Err(String::from("expected set <name> = <value>")),
+    };
+    let current = scope
+        .get(&name.to_lowercase())
+        .ok_or_else(|| format!("unknown variable {}", name))?;
+    let value = parse_value(current, text)
+        .ok_or_else(|| format!("{} is not a valid {}", text, type_name(current)))?;
+    let shown = format!("{} = {}", name, show(&value));
+    scope.insert(name.to_lowercase(), value);
+    Ok(shown)
+}
+
+pub(crate) fn parse_value(current: &Value, text: &str) -> Option<Value> {
+    match current {
+        Value::Number(_) | Value::BigNumber(_) => text.parse().ok().map(Value::Number),
+        Value::Real(_) => text.parse().ok().map(Value::Real),
+        Value::Boolean(_) => text.parse().ok().map(Value::Boolean),
+        Value::String(_) | Value::Char(_) => {
+            let mut tokens = Scanner::new(text.to_string());
+            let token = tokens.next()?.ok()?;
+            let expected = match current {
+                Value::String(_) => TokenType::StringLiteral,
+                _ => TokenType::CharLiteral,
+            };
+            if token.type_ != expected || tokens.next().is_some() {
+                return None;
+            }
+            Some(token.value)
+        }
+        Value::None => None,
+    }
+}
+
+pub(crate) fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Number(_) | Value::BigNumber(_) => "int",
+        Value::Real(_) => "real",
+        Value::Boolean(_) => "bool",
+        Value::String(_) => "string",
+        Value::Char(_) => "char",
+        Value::None => "value",
+    }
+}
+
+// strings and chars are quoted so their boundaries are visible
+pub(crate) fn show(value: &Value) -> String {
+    match value {
+        Value::String(s) => format!("{:?}", s),
+        Value::Char(c) => format!("{:?}", c),
+        value => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Interpreter;
+    use crate::parser::Parser;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    const PROGRAM: &str = "var n : int := 0;
+var i : int;
+for i in 0..2 do
+    if i > 0 do
+        n := n + i;
+    end if;
+end for;
+var s : string := \"x\";";
+
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // records the lines it pauses at and answers with scripted actions
+    struct Script {
+        actions: Vec<DebugAction>,
+        breakpoints: Vec<usize>,
+        pauses: Rc<RefCell<Vec<usize>>>,
+    }
+
+    impl Debugger for Script {
+        fn is_breakpoint(&self, line: usize) -> bool {
+            self.breakpoints.contains(&line)
+        }
+
+        fn on_statement(&mut self, span: Span, _: &mut HashMap<String, Value>) -> DebugAction {
+            self.pauses.borrow_mut().push(span.line);
+            self.actions.remove(0)
+        }
+    }
+
+    fn pauses(actions: Vec<DebugAction>, breakpoints: Vec<usize>) -> Vec<usize> {
+        let pauses = Rc::new(RefCell::new(Vec::new()));
+        let mut interpreter = Interpreter::new(Parser::new(Scanner::new(PROGRAM.to_string())));
+        interpreter.debugger = Some(Box::new(Script {
+            actions,
+            breakpoints,
+            pauses: pauses.clone(),
+        }));
+        interpreter.interpret().unwrap();
+        let pauses = pauses.borrow().clone();
+        pauses
+    }
+
+    #[test]
+    fn steps_through_blocks() {
+        use DebugAction::*;
+        assert_eq!(pauses(vec![Continue], vec![]), [1]);
+        assert_eq!(
+            pauses(vec![StepOver, StepOver, StepOver, StepOver], vec![]),
+            [1, 2, 3, 8]
+        );
+        // the if only runs its body from the second iteration on
+        assert_eq!(
+            pauses(
+                vec![StepOver, StepOver, StepIn, StepIn, StepIn, StepOut, Continue],
+                vec![]
+            ),
+            [1, 2, 3, 4, 4, 5, 4]
+        );
+        assert_eq!(
+            pauses(
+                vec![Continue, StepOver, Continue, Continue, Continue],
+                vec![5, 8]
+            ),
+            [1, 5, 4, 5, 8]
+        );
+        // the loop body is a single line, which stops again in every iteration
+        assert_eq!(
+            pauses(vec![Continue, Continue, Continue, Continue], vec![4]),
+            [1, 4, 4, 4]
+        );
+    }
+
+    #[test]
+    fn console_session() {
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let commands = "break 5\ncontinue\nprint i\nset n = 10\nset n = x\nlist\ndelete 5\nfinish\nfinish\nset I := 7\nprint s\np\nc\n";
+        let console = Console::new(
+            PROGRAM.to_string(),
+            commands.as_bytes(),
+            Shared(output.clone()),
+        );
+        let mut interpreter = Interpreter::new(Parser::new(Scanner::new(PROGRAM.to_string())));
+        interpreter.debugger = Some(Box::new(console));
+        interpreter.interpret().unwrap();
+        assert_eq!(interpreter.global_scope["n"], Value::Number(13));
+        assert_eq!(interpreter.global_scope["i"], Value::Number(7));
+
+        let transcript = String::from_utf8(output.borrow().clone()).unwrap();
+        let expected = "stopped at 1:1
+->    1 | var n : int := 0;
+(debug) breakpoint at line 5
+(debug) stopped at 5:9
+->    5 |         n := n + i;
+(debug) i = 1
+(debug) n = 10
+(debug) error: x is not a valid int
+(debug)       2 | var i : int;
+      3 | for i in 0..2 do
+      4 |     if i > 0 do
+->    5 |         n := n + i;
+      6 |     end if;
+      7 | end for;
+      8 | var s : string := \"x\";
+(debug) deleted breakpoint at line 5
+(debug) stopped at 4:5
+->    4 |     if i > 0 do
+(debug) stopped at 8:1
+->    8 | var s : string := \"x\";
+(debug) I = 7
+(debug) error: unknown variable s
+(debug) i = 7
+n = 13
+(debug) ";
+        assert_eq!(transcript, expected);
+
+        let mut scope = HashMap::new();
+        scope.insert(String::from("s"), Value::String(String::new()));
+        scope.insert(String::from("c"), Value::Char('a'));
+        assert!(set(&mut scope, "s = \"a\\tb\"").is_ok());
+        assert!(set(&mut scope, "c = 'x'").is_ok());
+        assert!(set(&mut scope, "c = \"x\"").is_err());
+        assert_eq!(scope["s"], Value::String(String::from("a\tb")));
+        assert_eq!(scope["c"], Value::Char('x'));
+    }
+
+    #[test]
+    fn counts_strings_the_debugger_sets() {
+        let program = "var s : string := \"hello\";\nprint s;\ns := \"\";";
+        let commands = "next\nset s = \"hello world\"\ncontinue\n";
+        let console = Console::new(
+            program.to_string(),
+            commands.as_bytes(),
+            Shared(Rc::new(RefCell::new(Vec::new()))),
+        );
+        let mut interpreter = Interpreter::new(Parser::new(Scanner::new(program.to_string())));
+        interpreter.debugger = Some(Box::new(console));
+        interpreter.interpret().unwrap();
+        assert_eq!(interpreter.global_scope["s"], Value::String(String::new()));
+    }
+}
@@ -0,0 +1,76 @@
+use crate::checker::SemanticError;
+use crate::interpreter::{Interrupted, LimitError, RuntimeError};
+use crate::parser::SyntaxError;
+use crate::scanner::LexError;
+use crate::tokens::Span;
+use std::fmt::{self, Display, Formatter};
+
+/// Anything that stops a program: a lexical or syntax error while parsing, a failed
+/// static check, a runtime fault, an exceeded resource limit or an interruption by the
+/// host.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    Lexical(LexError),
+    Syntax(SyntaxError),
+    Semantic(SemanticError),
+    Runtime(RuntimeError),
+    Limit(LimitError),
+    Interrupted(Interrupted),
+}
+
+impl Error {
+    pub fn span(&self) -> Span {
+        match self {
+            Error::Lexical(e) => e.span,
+            Error::Syntax(e) => e.span,
+            Error::Semantic(e) => e.span,
+            Error::Runtime(e) => e.span,
+            Error::Limit(e) => e.span,
+            Error::Interrupted(e) => e.span,
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            Error::Lexical(e) => &e.message,
+            Error::Syntax(e) => &e.message,
+            Error::Semantic(e) => &e.message,
+            Error::Runtime(e) => &e.message,
+            Error::Limit(e) => &e.message,
+            Error::Interrupted(e) => &e.message,
+        }
+    }
+}
+
+impl From<LexError> for Error {
+    fn from(error: LexError) -> Self {
+        Error::Lexical(error)
+    }
+}
+
+impl From<SemanticError> for Error {
+    fn from(error: SemanticError) -> Self {
+        Error::Semantic(error)
+    }
+}
+
+impl From<RuntimeError> for Error {
+    fn from(error: RuntimeError) -> Self {
+        Error::Runtime(error)
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Error::Lexical(e) => e.fmt(f),
+            Error::Syntax(e) => e.fmt(f),
+            Error::Semantic(e) => e.fmt(f),
+            Error::Runtime(e) => e.fmt(f),
+            Error::Limit(e) => e.fmt(f),
+            Error::Interrupted(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,290 @@
+use crate::checker::SemanticError;
+use crate::error::Error;
+use crate::interpreter::{as_real, compare, integer_op, real_op};
+use crate::nodes::{Char, DataType, Node, Num, Real, Str};
+use crate::optimizer::Types;
+use crate::tokens::{Span, Token, TokenType, Value};
+use std::mem;
+
+/// Constant folding and algebraic simplification over a checked tree:
+///
+/// - arithmetic and concatenation with constant operands become a literal, so
+///   `10 * 2 + 10` is `30`
+/// - `x - 0`, `x * 1`, `1 * x` and `x / 1` become `x`, and so do `x + 0` and `0 + x`
+///   when `x` is an int, since `-0.0 + 0` is `0.0`
+/// - an `if` whose condition is constant is replaced by the branch that runs
+///
+/// Folding follows the interpreter's arithmetic, so a constant division by zero or
+/// integer overflow is reported here, as a semantic error, instead of when the
+/// program runs. With `big_integers` overflowing constants become big literals.
+pub fn fold(tree: Node, big_integers: bool) -> Result<Node, Error> {
+    let folder = Folder {
+        big_integers,
+        types: Types::new(&tree, big_integers),
+    };
+    match tree {
+        Node::Program(mut program) => {
+            program.children = folder.statements(mem::take(&mut program.children))?;
+            Ok(Node::Program(program))
+        }
+        tree => folder.expr(tree),
+    }
+}
+
+struct Folder {
+    big_integers: bool,
+    types: Types,
+}
+
+impl Folder {
+    fn statements(&self, statements: Vec<Node>) -> Result<Vec<Node>, Error> {
+        let mut folded = Vec::with_capacity(statements.len());
+        for statement in statements {
+            self.statement(statement, &mut folded)?;
+        }
+        Ok(folded)
+    }
+
+    // appends the folded statement to `folded`, a constant `if` leaves its branch there
+    fn statement(&self, statement: Node, folded: &mut Vec<Node>) -> Result<(), Error> {
+        let statement = match statement {
+            Node::Assign(mut n) => {
+                n.right = self.expr(take(&mut n.right))?;
+                Node::Assign(n)
+            }
+            Node::DeclAssign(mut n) => {
+                n.right = self.expr(take(&mut n.right))?;
+                Node::DeclAssign(n)
+            }
+            Node::ForLoop(mut n) => {
+                n.start = self.expr(take(&mut n.start))?;
+                n.end = self.expr(take(&mut n.end))?;
+                n.statements = self.statements(mem::take(&mut n.statements))?;
+                Node::ForLoop(n)
+            }
+            Node::IfStatement(mut n) => {
+                n.bool_expr = self.expr(take(&mut n.bool_expr))?;
+                let statements = self.statements(mem::take(&mut n.statements))?;
+                let else_statements = self.statements(mem::take(&mut n.else_statements))?;
+                match self.condition(&n.bool_expr) {
+                    Some(true) => folded.extend(statements),
+                    Some(false) => folded.extend(else_statements),
+                    None => {
+                        n.statements = statements;
+                        n.else_statements = else_statements;
+                        folded.push(Node::IfStatement(n));
+                    }
+                }
+                return Ok(());
+            }
+            statement => statement,
+        };
+        folded.push(statement);
+        Ok(())
+    }
+
+    fn expr(&self, node: Node) -> Result<Node, Error> {
+        let span = node.span().unwrap_or_default();
+        match node {
+            Node::BinOp(mut n) => {
+                let left = self.expr(take(&mut n.left))?;
+                let right = self.expr(take(&mut n.right))?;
+                if let (Some(a), Some(b)) = (self.constant(&left), self.constant(&right)) {
+                    return self.binary(&n.op, a, b, span);
+                }
+                match (&n.op.type_, integer(&left), integer(&right)) {
+                    (TokenType::Plus, _, Some(0)) if self.is_int(&left) => Ok(left),
+                    (TokenType::Minus, _, Some(0))
+                    | (TokenType::Mul, _, Some(1))
+                    | (TokenType::Div, _, Some(1)) => Ok(left),
+                    (TokenType::Plus, Some(0), _) if self.is_int(&right) => Ok(right),
+                    (TokenType::Mul, Some(1), _) => Ok(right),
+                    _ => {
+                        n.left = left;
+                        n.right = right;
+                        Ok(Node::BinOp(n))
+                    }
+                }
+            }
+            Node::UnaryOp(mut n) => {
+                let expr = self.expr(take(&mut n.expr))?;
+                match (&n.op.type_, self.constant(&expr)) {
+                    (TokenType::Plus, _) => Ok(expr),
+                    (TokenType::Minus, Some(Value::Real(r))) => Ok(literal(Value::Real(-r), span)),
+                    (TokenType::Minus, Some(value)) => {
+                        self.binary(&n.op, Value::Number(0), value, span)
+                    }
+                    _ => {
+                        n.expr = expr;
+                        Ok(Node::UnaryOp(n))
+                    }
+                }
+            }
+            Node::BoolExpr(mut n) => {
+                n.left = self.expr(take(&mut n.left))?;
+                n.right = self.expr(take(&mut n.right))?;
+                Ok(Node::BoolExpr(n))
+            }
+            Node::Index(mut n) => {
+                n.target = self.expr(take(&mut n.target))?;
+                n.index = self.expr(take(&mut n.index))?;
+                Ok(Node::Index(n))
+            }
+            Node::Call(mut n) => {
+                let args = mem::take(&mut n.args);
+                n.args = args
+                    .into_iter()
+                    .map(|arg| self.expr(arg))
+                    .collect::<Result<_, _>>()?;
+                Ok(Node::Call(n))
+            }
+            node => Ok(node),
+        }
+    }
+
+    // the literal `left op right` evaluates to, as the interpreter would compute it
+    fn binary(&self, op: &Token, left: Value, right: Value, span: Span) -> Result<Node, Error> {
+        let value = match (&left, &right) {
+            (Value::String(_), _) | (Value::Char(_), _) => {
+                Value::String(left.to_string() + &right.to_string())
+            }
+            (Value::Real(_), _) | (_, Value::Real(_)) => {
+                let (x, y) = (as_real(&left).unwrap(), as_real(&right).unwrap());
+                Value::Real(real_op(&op.type_, x, y))
+            }
+            _ => integer_op(op, &left, &right, self.big_integers).map_err(|message| {
+                Error::Semantic(SemanticError {
+                    message,
+                    span: op.span,
+                })
+            })?,
+        };
+        Ok(literal(value, span))
+    }
+
+    // the value of a constant condition, as far as the interpreter would evaluate it
+    fn condition(&self, node: &Node) -> Option<bool> {
+        let n = match node {
+            Node::BoolExpr(n) => n,
+            _ => return None,
+        };
+        match n.op.type_ {
+            TokenType::Not => self.condition(&n.right).map(|b| !b),
+            TokenType::And => match self.condition(&n.left)? {
+                true => self.condition(&n.right),
+                false => Some(false),
+            },
+            TokenType::Or => match self.condition(&n.left)? {
+                true => Some(true),
+                false => self.condition(&n.right),
+            },
+            _ => {
+                let left = self.constant(&n.left)?;
+                let right = self.constant(&n.right)?;
+                Some(compare(&n.op.type_, &left, &right))
+            }
+        }
+    }
+
+    fn is_int(&self, node: &Node) -> bool {
+        self.types.type_of(node) == DataType::Int
+    }
+
+    // literals too large for 64 bits are a runtime error without big integers
+    fn constant(&self, node: &Node) -> Option<Value> {
+        match node {
+            Node::Num(n) => match &n.value {
+                Value::BigNumber(_) if !self.big_integers => None,
+                value => Some(value.clone()),
+            },
+            Node::Real(n) => Some(n.value.clone()),
+            Node::Str(n) => Some(n.value.clone()),
+            Node::Char(n) => Some(n.value.clone()),
+            _ => None,
+        }
+    }
+}
+
+fn integer(node: &Node) -> Option<i64> {
+    match node {
+        Node::Num(Num {
+            value: Value::Number(n),
+            ..
+        }) => Some(*n),
+        _ => None,
+    }
+}
+
+fn literal(value: Value, span: Span) -> Node {
+    let type_ = match value {
+        Value::Number(_) | Value::BigNumber(_) => TokenType::Integer,
+        Value::Real(_) => TokenType::RealLiteral,
+        Value::String(_) => TokenType::StringLiteral,
+        Value::Char(_) => TokenType::CharLiteral,
+        _ => unreachable!(),
+    };
+    let token = Token { type_, value, span };
+    match token.type_ {
+        TokenType::Integer => Node::Num(Num::new(token)),
+        TokenType::RealLiteral => Node::Real(Real::new(token)),
+        TokenType::StringLiteral => Node::Str(Str::new(token)),
+        _ => Node::Char(Char::new(token)),
+    }
+}
+
+fn take(node: &mut Node) -> Node {
+    mem::replace(node, Node::NoOp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checker::Checker;
+    use crate::formatter;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    fn folded(text: &str) -> Result<String, Error> {
+        let tree = Parser::new(Scanner::new(text.to_string())).parse()?;
+        Checker::new().check(&tree)?;
+        Ok(formatter::print(&fold(tree, false)?))
+    }
+
+    #[test]
+    fn folds_constants_and_identities() {
+        let text = "var a : int := 10 * 2 + 10;
+var b : int := (a + 0) * 1 - -(3 % 2);
+var s : string := \"x\" + 'y' + \"\\n\";
+var r : real := 1 / 4.0 + a;
+var q : real := 0 + r + 0;
+if 1 < 2 & !(\"a\" = \"b\") do
+    print a;
+else
+    print b;
+end if;
+if a < 2 | 3 <= 2 do
+    if 0 = 1 do
+        print s;
+    end if;
+end if;";
+        let expected = "var a : int := 30;
+var b : int := a - -1;
+var s : string := \"xy\\n\";
+var r : real := 0.25 + a;
+var q : real := 0 + r + 0;
+print a;
+if a < 2 | 3 <= 2 do
+end if;
+";
+        assert_eq!(folded(text).unwrap(), expected);
+
+        match folded("var z : int := 0;\nz := z * (7 / (2 - 2));") {
+            Err(Error::Semantic(e)) => {
+                assert_eq!(e.message, "division by zero");
+                assert_eq!((e.span.line, e.span.column), (2, 13));
+            }
+            result => panic!("expected division by zero, got {:?}", result),
+        }
+        assert!(folded("var n : int := 9223372036854775807 + 1;").is_err());
+    }
+}
@@ -0,0 +1,404 @@
+use crate::error::Error;
+use crate::nodes::{ForLoop, IfStatement, Node};
+use crate::parser::Parser;
+use crate::scanner::Scanner;
+use crate::tokens::{Span, Token, TokenType, Value};
+
+const INDENT: &str = "    ";
+
+// binding strength of operators, higher binds tighter
+const OR: u8 = 1;
+const AND: u8 = 2;
+const COMPARISON: u8 = 3;
+const SUM: u8 = 4;
+const PRODUCT: u8 = 5;
+const UNARY: u8 = 6;
+const POSTFIX: u8 = 7;
+const PRIMARY: u8 = 8;
+
+/// Formats a program in the canonical layout: one statement per line ending in `;`,
+/// blocks indented by four spaces and single spaces around `:`, `:=` and binary
+/// operators. Comments stay next to the statements they were written with, at
+/// most one blank line is kept between statements, and formatting formatted
+/// code gives the same text back.
+pub fn format(source: &str) -> Result<String, Error> {
+    let tree = Parser::new(Scanner::new(source.to_string())).parse()?;
+    // the parser already rejected lexical errors, so the second scan succeeds
+    let comments = Scanner::with_trivia(source.to_string())
+        .filter_map(Result::ok)
+        .filter(|token| token.type_ == TokenType::Comment)
+        .collect();
+    let mut printer = Printer {
+        source,
+        comments,
+        next_comment: 0,
+        output: String::new(),
+        indent: 0,
+        last_end: None,
+    };
+    if let Node::Program(program) = &tree {
+        printer.block(&program.children, source.len());
+    }
+    Ok(printer.output)
+}
+
+/// Prints a tree in the canonical layout without its source text, e.g. one that an
+/// optimization rewrote. Literals are spelled from their values and there are no
+/// comments or blank lines.
+pub fn print(tree: &Node) -> String {
+    let mut printer = Printer::without_source();
+    if let Node::Program(program) = tree {
+        printer.block(&program.children, 0);
+    }
+    printer.output
+}
+
+/// Prints a single expression the way `print` would.
+pub fn print_expr(expr: &Node) -> String {
+    Printer::without_source().expr(expr)
+}
+
+struct Printer<'a> {
+    // empty when printing a tree without its source
+    source: &'a str,
+    comments: Vec<Token>,
+    next_comment: usize,
+    output: String,
+    indent: usize,
+    // end offset of the last statement or comment printed in the current block
+    last_end: Option<usize>,
+}
+
+impl<'a> Printer<'a> {
+    fn without_source() -> Self {
+        Printer {
+            source: "",
+            comments: Vec::new(),
+            next_comment: 0,
+            output: String::new(),
+            indent: 0,
+            last_end: None,
+        }
+    }
+
+    // `close` is where the block ends in the source, comments before it belong to the block
+    fn block(&mut self, statements: &[Node], close: usize) {
+        self.last_end = None;
+        for statement in statements {
+            let span = match statement.span() {
+                Some(span) => span,
+                None => continue,
+            };
+            self.leading_comments(span.start);
+            self.blank_line_before(span.start);
+            self.statement(statement, span);
+            self.last_end = Some(span.end);
+            self.trailing_comments(span.end, close);
+        }
+        self.leading_comments(close);
+    }
+
+    fn statement(&mut self, node: &Node, span: Span) {
+        match node {
+            Node::VarDecl(n) => {
+                let line = format!("var {} : {};", n.var_node.value, n.type_node.value);
+                self.line(&line);
+            }
+            Node::DeclAssign(n) => {
+                let line = format!(
+                    "var {} : {} := {};",
+                    n.left.value,
+                    n.type_node.value,
+                    self.expr(&n.right)
+                );
+                self.line(&line);
+            }
+            Node::Assign(n) => {
+                let line = format!("{} := {};", n.left.value, self.expr(&n.right));
+                self.line(&line);
+            }
+            Node::PrintVar(n) => self.line(&format!("print {};", n.var_node.value)),
+            Node::PrintStr(n) => self.line(&format!("print {};", self.lexeme(&n.token))),
+            Node::Read(n) => self.line(&format!("read {};", n.var_node.value)),
+            Node::ForLoop(n) => self.for_loop(n, span),
+            Node::IfStatement(n) => self.if_statement(n, span),
+            _ => unreachable!(),
+        }
+    }
+
+    fn for_loop(&mut self, for_loop: &ForLoop, span: Span) {
+        let header = format!(
+            "for {} in {}..{} do",
+            for_loop.var_node.value,
+            self.expr(&for_loop.start),
+            self.expr(&for_loop.end)
+        );
+        self.line(&header);
+        self.indented_block(&for_loop.statements, span.end);
+        self.line("end for;");
+    }
+
+    fn if_statement(&mut self, if_statement: &IfStatement, span: Span) {
+        let header = format!("if {} do", self.expr(&if_statement.bool_expr));
+        self.line(&header);
+        match if_statement.else_span {
+            Some(else_span) => {
+                self.indented_block(&if_statement.statements, else_span.start);
+                self.line("else");
+                self.indented_block(&if_statement.else_statements, span.end);
+            }
+            None => self.indented_block(&if_statement.statements, span.end),
+        }
+        self.line("end if;");
+    }
+
+    fn indented_block(&mut self, statements: &[Node], close: usize) {
+        self.indent += 1;
+        self.block(statements, close);
+        self.indent -= 1;
+    }
+
+    // comments before `position` go on their own lines
+    fn leading_comments(&mut self, position: usize) {
+        while let Some(comment) = self.comments.get(self.next_comment) {
+            if comment.span.start >= position {
+                break;
+            }
+            let span = comment.span;
+            self.blank_line_before(span.start);
+            let text = self.comment_text(span);
+            self.line(&text);
+            self.last_end = Some(span.end);
+            self.next_comment += 1;
+        }
+    }
+
+    // comments inside the statement that ends at `end`, or after it on the same
+    // line, are appended to the statement's line. One after `close` on that line
+    // follows the enclosing statement instead, e.g. a comment after `end for;`.
+    fn trailing_comments(&mut self, end: usize, close: usize) {
+        let mut cursor = end;
+        while let Some(comment) = self.comments.get(self.next_comment) {
+            let span = comment.span;
+            if span.start >= end
+                && (span.start >= close || self.source[cursor..span.start].contains('\n'))
+            {
+                break;
+            }
+            let text = self.comment_text(span);
+            self.output.pop();
+            self.output.push(' ');
+            self.output.push_str(&text);
+            self.output.push('\n');
+            self.last_end = Some(span.end);
+            self.next_comment += 1;
+            cursor = span.end;
+            if text.starts_with("//") {
+                break;
+            }
+        }
+    }
+
+    // one empty line survives where the source had at least one
+    fn blank_line_before(&mut self, position: usize) {
+        if let Some(between) = self.last_end.and_then(|end| self.source.get(end..position)) {
+            if between.matches('\n').count() > 1 {
+                self.output.push('\n');
+            }
+        }
+    }
+
+    fn comment_text(&self, span: Span) -> String {
+        self.source[span.start..span.end].trim_end().to_string()
+    }
+
+    fn line(&mut self, text: &str) {
+        for _ in 0..self.indent {
+            self.output.push_str(INDENT);
+        }
+        self.output.push_str(text);
+        self.output.push('\n');
+    }
+
+    // literals keep their source spelling, e.g. escapes and exponents
+    fn lexeme(&self, token: &Token) -> String {
+        if !self.source.is_empty() {
+            return self.source[token.span.start..token.span.end].to_string();
+        }
+        match &token.value {
+            Value::String(s) => format!("\"{}\"", escape(s, '"')),
+            Value::Char(c) => format!("'{}'", escape(&c.to_string(), '\'')),
+            value => value.to_string(),
+        }
+    }
+
+    fn expr(&self, node: &Node) -> String {
+        match node {
+            Node::Num(n) => self.lexeme(&n.token),
+            Node::Real(n) => self.lexeme(&n.token),
+            Node::Str(n) => self.lexeme(&n.token),
+            Node::Char(n) => self.lexeme(&n.token),
+            Node::Var(n) => n.value.to_string(),
+            Node::Call(n) => {
+                let args: Vec<String> = n.args.iter().map(|arg| self.expr(arg)).collect();
+                format!("{}({})", n.value, args.join(", "))
+            }
+            Node::Index(n) => format!(
+                "{}[{}]",
+                self.operand(&n.target, POSTFIX),
+                self.expr(&n.index)
+            ),
+            Node::UnaryOp(n) => self.unary(&n.op, &n.expr),
+            Node::BoolExpr(n) if n.op.type_ == TokenType::Not => self.unary(&n.op, &n.right),
+            Node::BoolExpr(n) => self.binary(&n.left, &n.op, &n.right),
+            Node::BinOp(n) => self.binary(&n.left, &n.op, &n.right),
+            _ => unreachable!(),
+        }
+    }
+
+    fn unary(&self, op: &Token, operand: &Node) -> String {
+        let operand = self.operand(operand, UNARY);
+        // keep `- -x` from reading as a single operator
+        if operand.starts_with(['-', '+']) {
+            format!("{} {}", op.value, operand)
+        } else {
+            format!("{}{}", op.value, operand)
+        }
+    }
+
+    // operators are left associative, and comparisons do not chain at all
+    fn binary(&self, left: &Node, op: &Token, right: &Node) -> String {
+        let strength = binding(op);
+        let left_min = if strength == COMPARISON {
+            strength + 1
+        } else {
+            strength
+        };
+        format!(
+            "{} {} {}",
+            self.operand(left, left_min),
+            op.value,
+            self.operand(right, strength + 1)
+        )
+    }
+
+    fn operand(&self, node: &Node, min_binding: u8) -> String {
+        let text = self.expr(node);
+        if precedence(node) < min_binding {
+            format!("({})", text)
+        } else {
+            text
+        }
+    }
+}
+
+// the escapes the scanner understands, `quote` is the delimiter of the literal
+pub(crate) fn escape(text: &str, quote: char) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\\' => escaped.push_str("\\\\"),
+            c if c == quote => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c if c.is_control() => escaped.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn binding(op: &Token) -> u8 {
+    match op.type_ {
+        TokenType::Or => OR,
+        TokenType::And => AND,
+        TokenType::Plus | TokenType::Minus => SUM,
+        TokenType::Mul | TokenType::Div | TokenType::Mod => PRODUCT,
+        TokenType::Not => UNARY,
+        _ => COMPARISON,
+    }
+}
+
+fn precedence(node: &Node) -> u8 {
+    match node {
+        Node::BoolExpr(n) => binding(&n.op),
+        Node::BinOp(n) => binding(&n.op),
+        Node::UnaryOp(_) => UNARY,
+        Node::Index(_) => POSTFIX,
+        _ => PRIMARY,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_canonically() {
+        let source = "// Sum
+var   nTimes:int:=(2 * 3)+ -  -4 ;var x : int;
+x:=nTimes%(3-1) ;  /* inline */
+
+
+for x in 0..nTimes-1 do print x;if (x < 2) & !(x = 1 | x = 0) do
+print \"a;\\n\"; // note
+else
+  // nothing to do
+end if   end for;
+var s : string := upper(\"ab\")[1+ 0] + 'c'
+// done
+";
+        let expected = "// Sum
+var nTimes : int := 2 * 3 + - -4;
+var x : int;
+x := nTimes % (3 - 1); /* inline */
+
+for x in 0..nTimes - 1 do
+    print x;
+    if x < 2 & !(x = 1 | x = 0) do
+        print \"a;\\n\"; // note
+    else
+        // nothing to do
+    end if;
+end for;
+var s : string := upper(\"ab\")[1 + 0] + 'c';
+// done
+";
+        assert_eq!(format(source).unwrap(), expected);
+        assert_eq!(format(expected).unwrap(), expected);
+    }
+
+    #[test]
+    fn keeps_needed_parentheses() {
+        let source = "var b : bool := (1 < 2) = (3 > 4) & (true_ | b2);
+x := a - (b - c) - (d * e) / (f % g) * -(h + i);
+y := (\"ab\" + s)[0];";
+        let expected = "var b : bool := (1 < 2) = (3 > 4) & (true_ | b2);
+x := a - (b - c) - d * e / (f % g) * -(h + i);
+y := (\"ab\" + s)[0];
+";
+        assert_eq!(format(source).unwrap(), expected);
+        assert_eq!(format(expected).unwrap(), expected);
+    }
+
+    #[test]
+    fn keeps_comments_after_a_block_outside_it() {
+        let source = "for i in 1..2 do
+    print x; end for; // after loop
+if b do print x; else print y; end if; /* after if */";
+        let expected = "for i in 1..2 do
+    print x;
+end for; // after loop
+if b do
+    print x;
+else
+    print y;
+end if; /* after if */
+";
+        assert_eq!(format(source).unwrap(), expected);
+        assert_eq!(format(expected).unwrap(), expected);
+    }
+}
@@ -0,0 +1,741 @@
+use std::fmt::{self, Display, Formatter};
+
+/// A small deterministic random number generator (SplitMix64), so a seed always
+/// produces the same program.
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0..n`, `n` must not be 0.
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    pub fn range(&mut self, low: i64, high: i64) -> i64 {
+        low + self.below((high - low + 1) as usize) as i64
+    }
+
+    /// True with a probability of `percent` in a hundred.
+    pub fn chance(&mut self, percent: usize) -> bool {
+        self.below(100) < percent
+    }
+
+    pub fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len())]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Type {
+    Int,
+    Str,
+    Bool,
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Type::Int => "int",
+            Type::Str => "string",
+            Type::Bool => "bool",
+        })
+    }
+}
+
+/// A well-typed expression. Binary operations are printed fully parenthesized.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Int(i64),
+    Str(String),
+    Bool(bool),
+    Var(String, Type),
+    Binary(Type, Box<Expr>, &'static str, Box<Expr>),
+    Not(Box<Expr>),
+    ToString(Box<Expr>),
+}
+
+impl Expr {
+    pub fn type_(&self) -> Type {
+        match self {
+            Expr::Int(_) => Type::Int,
+            Expr::Str(_) | Expr::ToString(_) => Type::Str,
+            Expr::Bool(_) | Expr::Not(_) => Type::Bool,
+            Expr::Var(_, type_) | Expr::Binary(type_, ..) => *type_,
+        }
+    }
+
+    fn literal(type_: Type) -> Expr {
+        match type_ {
+            Type::Int => Expr::Int(0),
+            Type::Str => Expr::Str(String::new()),
+            Type::Bool => Expr::Bool(true),
+        }
+    }
+
+    fn uses(&self, name: &str) -> bool {
+        match self {
+            Expr::Var(var, _) => var == name,
+            Expr::Binary(_, left, _, right) => left.uses(name) || right.uses(name),
+            Expr::Not(expr) | Expr::ToString(expr) => expr.uses(name),
+            _ => false,
+        }
+    }
+
+    // simpler expressions of the same type: a plain literal, the operands, then
+    // the expression with one operand simplified
+    fn shrink(&self) -> Vec<Expr> {
+        let literal = Expr::literal(self.type_());
+        let mut candidates = Vec::new();
+        if *self != literal {
+            candidates.push(literal);
+        }
+        match self {
+            Expr::Int(n) if *n != 0 => candidates.push(Expr::Int(n / 2)),
+            Expr::Str(s) if !s.is_empty() => {
+                candidates.push(Expr::Str(s.chars().skip(1).collect()));
+            }
+            Expr::Binary(type_, left, op, right) => {
+                for operand in [left, right] {
+                    if operand.type_() == *type_ {
+                        candidates.push((**operand).clone());
+                    }
+                }
+                for left in left.shrink() {
+                    candidates.push(Expr::Binary(*type_, Box::new(left), op, right.clone()));
+                }
+                // divisors stay non-zero literals
+                if !matches!(*op, "/" | "%") {
+                    for right in right.shrink() {
+                        candidates.push(Expr::Binary(*type_, left.clone(), op, Box::new(right)));
+                    }
+                }
+            }
+            Expr::Not(expr) => {
+                candidates.push((**expr).clone());
+                candidates.extend(expr.shrink().into_iter().map(|e| Expr::Not(Box::new(e))));
+            }
+            Expr::ToString(expr) => {
+                candidates.extend(
+                    expr.shrink()
+                        .into_iter()
+                        .map(|e| Expr::ToString(Box::new(e))),
+                );
+            }
+            _ => {}
+        }
+        candidates
+    }
+}
+
+impl Display for Expr {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Expr::Int(n) if *n < 0 => write!(f, "(-{})", n.unsigned_abs()),
+            Expr::Int(n) => write!(f, "{}", n),
+            Expr::Str(s) => write!(f, "\"{}\"", escape(s)),
+            Expr::Bool(true) => f.write_str("(0 = 0)"),
+            Expr::Bool(false) => f.write_str("(0 = 1)"),
+            Expr::Var(name, _) => f.write_str(name),
+            Expr::Binary(_, left, op, right) => write!(f, "({} {} {})", left, op, right),
+            Expr::Not(expr) => write!(f, "!{}", expr),
+            Expr::ToString(expr) => write!(f, "to_string({})", expr),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stmt {
+    Assign(String, Expr),
+    PrintVar(String),
+    PrintStr(String),
+    For(String, i64, i64, Vec<Stmt>),
+    If(Expr, Vec<Stmt>, Vec<Stmt>),
+}
+
+impl Stmt {
+    fn uses(&self, name: &str) -> bool {
+        match self {
+            Stmt::Assign(var, expr) => var == name || expr.uses(name),
+            Stmt::PrintVar(var) => var == name,
+            Stmt::PrintStr(_) => false,
+            Stmt::For(var, _, _, body) => var == name || body.iter().any(|s| s.uses(name)),
+            Stmt::If(condition, then, otherwise) => {
+                condition.uses(name) || then.iter().chain(otherwise).any(|s| s.uses(name))
+            }
+        }
+    }
+
+    // what can take the statement's place: its bodies on their own, then the
+    // statement with a part simplified
+    fn shrink(&self) -> Vec<Vec<Stmt>> {
+        let mut candidates = Vec::new();
+        match self {
+            Stmt::Assign(var, expr) => {
+                for expr in expr.shrink() {
+                    candidates.push(vec![Stmt::Assign(var.clone(), expr)]);
+                }
+            }
+            Stmt::PrintStr(s) if !s.is_empty() => {
+                candidates.push(vec![Stmt::PrintStr(String::new())]);
+            }
+            Stmt::For(var, start, end, body) => {
+                candidates.push(body.clone());
+                if end > start {
+                    candidates.push(vec![Stmt::For(var.clone(), *start, *start, body.clone())]);
+                }
+                for body in shrink_block(body) {
+                    candidates.push(vec![Stmt::For(var.clone(), *start, *end, body)]);
+                }
+            }
+            Stmt::If(condition, then, otherwise) => {
+                candidates.push(then.clone());
+                candidates.push(otherwise.clone());
+                for condition in condition.shrink() {
+                    let shrunk = Stmt::If(condition, then.clone(), otherwise.clone());
+                    candidates.push(vec![shrunk]);
+                }
+                for then in shrink_block(then) {
+                    candidates.push(vec![Stmt::If(condition.clone(), then, otherwise.clone())]);
+                }
+                for otherwise in shrink_block(otherwise) {
+                    candidates.push(vec![Stmt::If(condition.clone(), then.clone(), otherwise)]);
+                }
+            }
+            _ => {}
+        }
+        candidates
+    }
+
+    fn write(&self, f: &mut Formatter, indent: usize) -> fmt::Result {
+        let pad = "    ".repeat(indent);
+        match self {
+            Stmt::Assign(var, expr) => writeln!(f, "{}{} := {};", pad, var, expr),
+            Stmt::PrintVar(var) => writeln!(f, "{}print {};", pad, var),
+            Stmt::PrintStr(s) => writeln!(f, "{}print \"{}\";", pad, escape(s)),
+            Stmt::For(var, start, end, body) => {
+                let (start, end) = (Expr::Int(*start), Expr::Int(*end));
+                writeln!(f, "{}for {} in {}..{} do", pad, var, start, end)?;
+                write_block(f, body, indent + 1)?;
+                writeln!(f, "{}end for;", pad)
+            }
+            Stmt::If(condition, then, otherwise) => {
+                writeln!(f, "{}if {} do", pad, condition)?;
+                write_block(f, then, indent + 1)?;
+                if !otherwise.is_empty() {
+                    writeln!(f, "{}else", pad)?;
+                    write_block(f, otherwise, indent + 1)?;
+                }
+                writeln!(f, "{}end if;", pad)
+            }
+        }
+    }
+}
+
+/// A generated program: declarations first, each initialized from literals and
+/// earlier variables, then statements. Loop control variables are declared as
+/// well but never assigned outside their loops.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    pub declarations: Vec<(String, Type, Option<Expr>)>,
+    pub statements: Vec<Stmt>,
+}
+
+impl Program {
+    /// Shrinks a program for which `fails` holds to a smaller one for which it still
+    /// holds, by removing statements and declarations and simplifying expressions
+    /// until no single step keeps the failure.
+    pub fn shrink(&self, mut fails: impl FnMut(&Program) -> bool) -> Program {
+        let mut program = self.clone();
+        'shrinking: loop {
+            for candidate in program.candidates() {
+                if fails(&candidate) {
+                    program = candidate;
+                    continue 'shrinking;
+                }
+            }
+            return program;
+        }
+    }
+
+    fn candidates(&self) -> Vec<Program> {
+        let mut candidates = Vec::new();
+        for statements in shrink_block(&self.statements) {
+            candidates.push(Program {
+                declarations: self.declarations.clone(),
+                statements,
+            });
+        }
+        for (i, (name, type_, init)) in self.declarations.iter().enumerate() {
+            let used = self.statements.iter().any(|s| s.uses(name))
+                || self.declarations[i + 1..]
+                    .iter()
+                    .any(|(_, _, init)| init.as_ref().is_some_and(|e| e.uses(name)));
+            if !used {
+                let mut declarations = self.declarations.clone();
+                declarations.remove(i);
+                candidates.push(Program {
+                    declarations,
+                    statements: self.statements.clone(),
+                });
+            }
+            let inits = match init {
+                Some(init) => init.shrink().into_iter().map(Some).chain([None]).collect(),
+                None => Vec::new(),
+            };
+            for init in inits {
+                let mut declarations = self.declarations.clone();
+                declarations[i] = (name.clone(), *type_, init);
+                candidates.push(Program {
+                    declarations,
+                    statements: self.statements.clone(),
+                });
+            }
+        }
+        candidates
+    }
+}
+
+impl Display for Program {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        for (name, type_, init) in &self.declarations {
+            match init {
+                Some(init) => writeln!(f, "var {} : {} := {};", name, type_, init)?,
+                None => writeln!(f, "var {} : {};", name, type_)?,
+            }
+        }
+        write_block(f, &self.statements, 0)
+    }
+}
+
+fn write_block(f: &mut Formatter, block: &[Stmt], indent: usize) -> fmt::Result {
+    for statement in block {
+        statement.write(f, indent)?;
+    }
+    Ok(())
+}
+
+// the block without one statement, or with one statement replaced by a simpler one
+fn shrink_block(block: &[Stmt]) -> Vec<Vec<Stmt>> {
+    let mut candidates = Vec::new();
+    for i in 0..block.len() {
+        let mut shorter = block.to_vec();
+        shorter.remove(i);
+        candidates.push(shorter);
+    }
+    for (i, statement) in block.iter().enumerate() {
+        for replacement in statement.shrink() {
+            let mut shrunk = block[..i].to_vec();
+            shrunk.extend(replacement);
+            shrunk.extend_from_slice(&block[i + 1..]);
+            candidates.push(shrunk);
+        }
+    }
+    candidates
+}
+
+fn escape(s: &str) -> String {
+    let mut escaped = String::new();
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// string literals include characters the scanner has to escape or decode
+const PIECES: &[&str] = &["a", "b", "xyz", " ", "\"", "\\", "\n", "\t", "ä", "✓"];
+const ARITHMETIC: &[&str] = &["+", "-", "*", "/", "%"];
+const COMPARISONS: &[&str] = &["=", "<>", "<", "<=", ">", ">="];
+const MAX_DEPTH: usize = 3;
+
+/// Generates random well-typed programs. Loop ranges have at most a few iterations
+/// and nest at most `MAX_DEPTH` deep, divisors are non-zero literals, and a string
+/// expression refers to at most one string variable, so strings grow linearly.
+/// Integer overflow is still possible and is a runtime error like any other.
+pub struct Generator {
+    rng: Rng,
+    // declared variables by name and type, loop control variables excluded
+    variables: Vec<(String, Type)>,
+    // loop control variables of the enclosing loops, innermost last
+    loops: Vec<String>,
+}
+
+impl Generator {
+    pub fn new(seed: u64) -> Self {
+        Generator {
+            rng: Rng::new(seed),
+            variables: Vec::new(),
+            loops: Vec::new(),
+        }
+    }
+
+    pub fn program(&mut self) -> Program {
+        self.variables.clear();
+        let mut declarations = Vec::new();
+        for i in 0..self.rng.range(2, 7) {
+            let type_ = *self.rng.pick(&[Type::Int, Type::Str, Type::Bool]);
+            let init = if self.rng.chance(75) {
+                Some(self.expr(type_, 2))
+            } else {
+                None
+            };
+            let name = format!("v{}", i);
+            declarations.push((name.clone(), type_, init));
+            self.variables.push((name, type_));
+        }
+        for depth in 0..MAX_DEPTH {
+            declarations.push((format!("i{}", depth), Type::Int, None));
+        }
+        let statements = self.block(1, 8);
+        Program {
+            declarations,
+            statements,
+        }
+    }
+
+    fn block(&mut self, min: i64, max: i64) -> Vec<Stmt> {
+        (0..self.rng.range(min, max))
+            .map(|_| self.statement())
+            .collect()
+    }
+
+    fn statement(&mut self) -> Stmt {
+        let nested = self.loops.len() < MAX_DEPTH;
+        match self.rng.below(10) {
+            0..=3 => {
+                let (name, type_) = self.rng.pick(&self.variables).clone();
+                Stmt::Assign(name, self.expr(type_, 3))
+            }
+            4 | 5 => {
+                let mut printable: Vec<String> = self.loops.clone();
+                printable.extend(self.variables.iter().map(|(name, _)| name.clone()));
+                Stmt::PrintVar(self.rng.pick(&printable).clone())
+            }
+            6 => Stmt::PrintStr(self.string()),
+            7 | 8 if nested => {
+                let var = format!("i{}", self.loops.len());
+                let start = self.rng.range(-2, 3);
+                let end = start + self.rng.range(-1, 3);
+                self.loops.push(var.clone());
+                let body = self.block(0, 4);
+                self.loops.pop();
+                Stmt::For(var, start, end, body)
+            }
+            _ if nested => {
+                let condition = self.expr(Type::Bool, 2);
+                // the bodies count as one more level, which keeps the program finite
+                self.loops.push(format!("i{}", self.loops.len()));
+                let then = self.block(0, 3);
+                let otherwise = if self.rng.chance(50) {
+                    self.block(1, 3)
+                } else {
+                    Vec::new()
+                };
+                self.loops.pop();
+                Stmt::If(condition, then, otherwise)
+            }
+            _ => Stmt::PrintStr(self.string()),
+        }
+    }
+
+    fn expr(&mut self, type_: Type, depth: usize) -> Expr {
+        self.expr_with(type_, depth, true)
+    }
+
+    // `variables` allows a string variable, see `Generator`
+    fn expr_with(&mut self, type_: Type, depth: usize, variables: bool) -> Expr {
+        if depth == 0 || self.rng.chance(30) {
+            return self.leaf(type_, variables);
+        }
+        match type_ {
+            Type::Int => {
+                let op = *self.rng.pick(ARITHMETIC);
+                let left = self.expr(Type::Int, depth - 1);
+                let right = if op == "/" || op == "%" {
+                    let divisor = self.rng.range(1, 9);
+                    Expr::Int(if self.rng.chance(20) {
+                        -divisor
+                    } else {
+                        divisor
+                    })
+                } else {
+                    self.expr(Type::Int, depth - 1)
+                };
+                Expr::Binary(Type::Int, Box::new(left), op, Box::new(right))
+            }
+            Type::Str if self.rng.chance(20) => {
+                Expr::ToString(Box::new(self.expr(Type::Int, depth - 1)))
+            }
+            Type::Str => {
+                let left = self.expr_with(Type::Str, depth - 1, variables);
+                let right = self.expr_with(Type::Str, depth - 1, false);
+                Expr::Binary(Type::Str, Box::new(left), "+", Box::new(right))
+            }
+            Type::Bool => match self.rng.below(4) {
+                0 => Expr::Not(Box::new(self.expr(Type::Bool, depth - 1))),
+                1 => {
+                    let op = *self.rng.pick(&["&", "|"]);
+                    let left = self.expr(Type::Bool, depth - 1);
+                    let right = self.expr(Type::Bool, depth - 1);
+                    Expr::Binary(Type::Bool, Box::new(left), op, Box::new(right))
+                }
+                _ => {
+                    let operands = *self
+                        .rng
+                        .pick(&[Type::Int, Type::Int, Type::Str, Type::Bool]);
+                    let op = *self.rng.pick(COMPARISONS);
+                    let left = self.expr(operands, depth - 1);
+                    let right = self.expr(operands, depth - 1);
+                    Expr::Binary(Type::Bool, Box::new(left), op, Box::new(right))
+                }
+            },
+        }
+    }
+
+    fn leaf(&mut self, type_: Type, variables: bool) -> Expr {
+        let mut candidates: Vec<String> = self
+            .variables
+            .iter()
+            .filter(|(_, t)| *t == type_)
+            .map(|(name, _)| name.clone())
+            .collect();
+        if type_ == Type::Int {
+            candidates.extend(self.loops.iter().cloned());
+        }
+        if (type_ != Type::Str || variables) && !candidates.is_empty() && self.rng.chance(60) {
+            return Expr::Var(self.rng.pick(&candidates).clone(), type_);
+        }
+        match type_ {
+            Type::Int => Expr::Int(self.rng.range(-20, 100)),
+            Type::Str => Expr::Str(self.string()),
+            Type::Bool => Expr::Bool(self.rng.chance(50)),
+        }
+    }
+
+    fn string(&mut self) -> String {
+        (0..self.rng.below(4))
+            .map(|_| *self.rng.pick(PIECES))
+            .collect()
+    }
+}
+
+// what random edits insert: tokens, their fragments and characters the scanner must reject
+const NOISE: &[&str] = &[
+    "var",
+    "for",
+    "in",
+    "do",
+    "end",
+    "if",
+    "else",
+    "print",
+    "read",
+    "int",
+    "string",
+    "bool",
+    "char",
+    "real",
+    ":=",
+    ":",
+    ";",
+    "..",
+    "(",
+    ")",
+    "[",
+    "]",
+    ",",
+    "+",
+    "-",
+    "*",
+    "/",
+    "%",
+    "=",
+    "<>",
+    "<",
+    ">",
+    "&",
+    "|",
+    "!",
+    "\"",
+    "'",
+    "\\",
+    "/*",
+    "*/",
+    "//",
+    "\n",
+    "0",
+    "9",
+    ".5",
+    "1e",
+    "99999999999999999999999",
+    "x",
+    "abs",
+    "substring",
+    "ä",
+    "\u{0}",
+    "@",
+];
+
+/// A random corruption of `source`: a few characters removed, text repeated or
+/// noise inserted. For fuzzing everything that has to cope with invalid programs.
+pub fn mutate(source: &str, rng: &mut Rng) -> String {
+    let mut chars: Vec<char> = source.chars().collect();
+    for _ in 0..rng.range(1, 4) {
+        let at = rng.below(chars.len() + 1);
+        let len = rng.below(chars.len() - at + 1).min(8);
+        match rng.below(3) {
+            0 => {
+                chars.drain(at..at + len);
+            }
+            1 => {
+                let copy: Vec<char> = chars[at..at + len].to_vec();
+                let to = rng.below(chars.len() + 1);
+                chars.splice(to..to, copy);
+            }
+            _ => {
+                let noise = rng.pick(NOISE).chars();
+                chars.splice(at..at, noise);
+            }
+        }
+    }
+    chars.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checker::Checker;
+    use crate::error::Error;
+    use crate::formatter;
+    use crate::interpreter::{Interpreter, Io, Limits};
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+    use std::cell::RefCell;
+    use std::panic;
+    use std::rc::Rc;
+
+    struct Output(Rc<RefCell<String>>);
+
+    impl Io for Output {
+        fn print(&mut self, line: &str) {
+            let mut output = self.0.borrow_mut();
+            output.push_str(line);
+            output.push('\n');
+        }
+
+        fn read_line(&mut self) -> Option<String> {
+            None
+        }
+    }
+
+    // what a program prints and how it ends
+    fn run(source: &str, big_integers: bool, optimize: bool) -> (String, Result<(), Error>) {
+        let output = Rc::new(RefCell::new(String::new()));
+        let mut interpreter = Interpreter::new(Parser::new(Scanner::new(source.to_string())));
+        interpreter.io = Box::new(Output(output.clone()));
+        interpreter.big_integers = big_integers;
+        interpreter.fold_constants = optimize;
+        interpreter.optimize_loops = optimize;
+        interpreter.eliminate_dead_code = optimize;
+        interpreter.limits = Limits {
+            fuel: Some(100_000),
+            output_bytes: Some(1 << 20),
+            string_bytes: Some(1 << 20),
+        };
+        let result = interpreter.interpret().map(|_| ());
+        let output = output.borrow().clone();
+        (output, result)
+    }
+
+    #[test]
+    fn generated_programs_agree() {
+        for seed in 0..300 {
+            let program = Generator::new(seed).program();
+            let source = program.to_string();
+            assert_eq!(Generator::new(seed).program(), program);
+            let (output, result) = run(&source, false, false);
+            if let Err(e @ (Error::Lexical(_) | Error::Syntax(_) | Error::Semantic(_))) = &result {
+                panic!("seed {} is not well-typed: {}\n{}", seed, e, source);
+            }
+            // formatting, big integers and the optimisations must not change what a
+            // program that runs to the end does
+            let formatted = formatter::format(&source).unwrap();
+            assert_eq!(
+                run(&formatted, false, false),
+                (output.clone(), result.clone()),
+                "seed {}",
+                seed
+            );
+            if result.is_ok() {
+                assert_eq!(
+                    run(&source, true, false),
+                    (output.clone(), result.clone()),
+                    "seed {}",
+                    seed
+                );
+                assert_eq!(
+                    run(&source, false, true),
+                    (output.clone(), result.clone()),
+                    "seed {}",
+                    seed
+                );
+                assert_eq!(run(&source, true, true), (output, result), "seed {}", seed);
+            }
+        }
+    }
+
+    #[test]
+    fn shrinks_to_a_minimal_failure() {
+        let program = (0..)
+            .map(|seed| Generator::new(seed).program())
+            .find(|p| p.to_string().contains(" % ") && p.statements.len() > 3)
+            .unwrap();
+        let shrunk = program.shrink(|p| p.to_string().contains(" % "));
+        assert!(shrunk.to_string().contains(" % "));
+        assert!(shrunk.to_string().len() < program.to_string().len());
+        // a declaration, or a statement and at most the declaration it assigns
+        assert!(shrunk.to_string().lines().count() <= 2, "{}", shrunk);
+    }
+
+    #[test]
+    fn corrupted_programs_do_not_panic() {
+        let mut rng = Rng::new(7);
+        for seed in 0..300 {
+            let source = Generator::new(seed).program().to_string();
+            for _ in 0..10 {
+                let mutated = mutate(&source, &mut rng);
+                let checked = panic::catch_unwind(|| {
+                    let tree = Parser::new(Scanner::new(mutated.clone())).parse()?;
+                    Checker::new().check(&tree)?;
+                    Ok::<(), Error>(())
+                });
+                match checked {
+                    // programs that still pass the checks must run without panicking too
+                    Ok(Ok(())) => {
+                        let ran = panic::catch_unwind(|| run(&mutated, false, false));
+                        assert!(ran.is_ok(), "running panicked on:\n{}", mutated);
+                    }
+                    Ok(Err(_)) => {}
+                    Err(_) => panic!("checking panicked on:\n{}", mutated),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,260 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+
+/// What running a program produced, or what it is expected to produce.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outcome {
+    pub stdout: String,
+    pub stderr: String,
+    pub status: i32,
+}
+
+impl Outcome {
+    /// The expectation recorded next to `program`: `.out` holds stdout and `.err` holds
+    /// stderr followed by an `exit status: N` line when the status is not 0. A missing
+    /// file expects nothing on that stream.
+    pub fn expected(program: &Path) -> io::Result<Outcome> {
+        let stdout = read_optional(&program.with_extension("out"))?;
+        let recorded = read_optional(&program.with_extension("err"))?;
+        let body = recorded.strip_suffix('\n').unwrap_or(&recorded);
+        let (rest, last) = match body.rfind('\n') {
+            Some(i) => (&recorded[..=i], &body[i + 1..]),
+            None => ("", body),
+        };
+        let (stderr, status) = match last.strip_prefix("exit status: ") {
+            Some(code) => {
+                let status = code.parse().map_err(|_| invalid(program, "exit status"))?;
+                (rest.to_string(), status)
+            }
+            None => (recorded.clone(), 0),
+        };
+        Ok(Outcome {
+            stdout,
+            stderr,
+            status,
+        })
+    }
+
+    /// Runs `command program` with the sibling `.in` file, if any, as stdin.
+    pub fn of(command: &Path, program: &Path) -> io::Result<Outcome> {
+        let input = read_optional(&program.with_extension("in"))?;
+        let mut child = Command::new(command)
+            .arg(program)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        // written from another thread, so a program that prints before it has read
+        // everything cannot fill the output pipe and block both sides
+        let mut stdin = child.stdin.take().unwrap();
+        let writer = thread::spawn(move || {
+            // a program that stops reading early closes the pipe, which is not a failure
+            let _ = stdin.write_all(input.as_bytes());
+        });
+        let output = child.wait_with_output()?;
+        writer.join().unwrap();
+        Ok(Outcome {
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+            // killed by a signal
+            status: output.status.code().unwrap_or(-1),
+        })
+    }
+
+    fn save(&self, program: &Path) -> io::Result<()> {
+        let mut stderr = self.stderr.clone();
+        if self.status != 0 {
+            stderr.push_str(&format!("exit status: {}\n", self.status));
+        }
+        write_optional(&program.with_extension("out"), &self.stdout)?;
+        write_optional(&program.with_extension("err"), &stderr)
+    }
+}
+
+/// The `.mpl` files among `paths`, searching directories recursively, in sorted order.
+pub fn discover(paths: &[PathBuf]) -> io::Result<Vec<PathBuf>> {
+    let mut programs = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            let mut entries: Vec<PathBuf> = fs::read_dir(path)?
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<io::Result<_>>()?;
+            entries.retain(|entry| entry.is_dir() || is_program(entry));
+            programs.extend(discover(&entries)?);
+        } else {
+            programs.push(path.clone());
+        }
+    }
+    programs.sort();
+    Ok(programs)
+}
+
+/// Runs every program found in `paths` with `command` and compares stdout, stderr
+/// and exit status with the expectation files, reporting to `report`. With `bless`
+/// the expectations are rewritten from what the programs produced instead. True if
+/// every program matched.
+pub fn run(
+    command: &Path,
+    paths: &[PathBuf],
+    bless: bool,
+    report: &mut dyn Write,
+) -> io::Result<bool> {
+    let (mut passed, mut failed) = (0, 0);
+    for program in discover(paths)? {
+        let actual = Outcome::of(command, &program)?;
+        if bless {
+            actual.save(&program)?;
+            writeln!(report, "blessed {}", program.display())?;
+            passed += 1;
+            continue;
+        }
+        let expected = Outcome::expected(&program)?;
+        if actual == expected {
+            writeln!(report, "ok      {}", program.display())?;
+            passed += 1;
+            continue;
+        }
+        writeln!(report, "FAILED  {}", program.display())?;
+        failed += 1;
+        if actual.stdout != expected.stdout {
+            writeln!(report, "  stdout:")?;
+            report.write_all(diff(&expected.stdout, &actual.stdout).as_bytes())?;
+        }
+        if actual.stderr != expected.stderr {
+            writeln!(report, "  stderr:")?;
+            report.write_all(diff(&expected.stderr, &actual.stderr).as_bytes())?;
+        }
+        if actual.status != expected.status {
+            writeln!(
+                report,
+                "  exit status: expected {}, got {}",
+                expected.status, actual.status
+            )?;
+        }
+    }
+    writeln!(report, "\n{} passed, {} failed", passed, failed)?;
+    Ok(failed == 0)
+}
+
+/// A line diff of two texts: common lines indented, lines only in `expected` marked
+/// `-` and lines only in `actual` marked `+`.
+pub fn diff(expected: &str, actual: &str) -> String {
+    let (a, b): (Vec<&str>, Vec<&str>) = (expected.lines().collect(), actual.lines().collect());
+    // longest common subsequence of the suffixes a[i..] and b[j..]
+    let mut common = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            common[i][j] = if a[i] == b[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+    let mut diff = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            diff.push_str(&format!("     {}\n", a[i]));
+            i += 1;
+            j += 1;
+        } else if j == b.len() || (i < a.len() && common[i + 1][j] >= common[i][j + 1]) {
+            diff.push_str(&format!("   - {}\n", a[i]));
+            i += 1;
+        } else {
+            diff.push_str(&format!("   + {}\n", b[j]));
+            j += 1;
+        }
+    }
+    // texts that differ only in the final line break have no differing line
+    if diff.lines().all(|line| line.starts_with("     ")) {
+        diff.push_str("   (differ in the line break at the end)\n");
+    }
+    diff
+}
+
+fn is_program(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension == "mpl")
+}
+
+fn read_optional(path: &Path) -> io::Result<String> {
+    match fs::read_to_string(path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(String::new()),
+        result => result,
+    }
+}
+
+// an empty expectation is stored as no file at all
+fn write_optional(path: &Path, text: &str) -> io::Result<()> {
+    if !text.is_empty() {
+        return fs::write(path, text);
+    }
+    match fs::remove_file(path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+fn invalid(program: &Path, what: &str) -> io::Error {
+    let message = format!(
+        "invalid {} in {}",
+        what,
+        program.with_extension("err").display()
+    );
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blesses_and_compares() {
+        let dir = std::env::temp_dir().join(format!("mini-pl-golden-{}", std::process::id()));
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        let program = dir.join("nested").join("echo.mpl");
+        fs::write(&program, "print \"hi\";\n").unwrap();
+        fs::write(dir.join("notes.txt"), "").unwrap();
+        // `cat` stands in for the interpreter: it prints the program it is given
+        let cat = Path::new("cat");
+        let paths = [dir.clone()];
+
+        let mut report = Vec::new();
+        assert!(!run(cat, &paths, false, &mut report).unwrap());
+        assert!(String::from_utf8(report).unwrap().starts_with("FAILED"));
+        assert!(run(cat, &paths, true, &mut Vec::new()).unwrap());
+        assert_eq!(
+            fs::read_to_string(dir.join("nested/echo.out")).unwrap(),
+            "print \"hi\";\n"
+        );
+        assert!(!dir.join("nested/echo.err").exists());
+        assert!(run(cat, &paths, false, &mut Vec::new()).unwrap());
+
+        fs::write(program.with_extension("err"), "oops\nexit status: 2\n").unwrap();
+        let expected = Outcome::expected(&program).unwrap();
+        assert_eq!((expected.stderr.as_str(), expected.status), ("oops\n", 2));
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            diff("a\nb\nc\n", "a\nc\nd\n"),
+            "     a\n   - b\n     c\n   + d\n"
+        );
+    }
+
+    #[test]
+    fn feeds_input_while_reading_output() {
+        let dir = std::env::temp_dir().join(format!("mini-pl-golden-io-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        // run by `sh`, the program echoes more input than a pipe holds
+        let program = dir.join("echo.mpl");
+        fs::write(&program, "cat\n").unwrap();
+        let input = "line\n".repeat(100_000);
+        fs::write(program.with_extension("in"), &input).unwrap();
+        let outcome = Outcome::of(Path::new("sh"), &program).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!((outcome.stdout, outcome.status), (input, 0));
+    }
+}
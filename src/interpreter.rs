@@ -1,34 +1,215 @@
+use crate::bigint::BigInt;
+use crate::builtins::{self, BUILTINS};
+use crate::checker::Checker;
+use crate::debugger::{DebugAction, Debugger};
+use crate::error::Error;
+use crate::fold;
+use crate::loops;
 use crate::nodes::{
-    Assign, BinOp, BoolExpr, DeclAssign, ForLoop, IfStatement, Node, Num, PrintStr, PrintVar,
-    Program, Read, Str, Type, UnaryOp, Var, VarDecl,
+    Assign, BinOp, BoolExpr, Call, Char, DeclAssign, ForLoop, IfStatement, Index, Node, Num,
+    PrintStr, PrintVar, Program, Read, Real, Str, Type, UnaryOp, Var, VarDecl,
 };
+use crate::optimizer::{self, Remark};
 use crate::parser::Parser;
-use crate::tokens::{TokenType, Value};
+use crate::profiler::Profiler;
+use crate::tokens::{Span, Token, TokenType, Value};
+use crate::trace::Tracer;
+use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
 use std::io::stdin;
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+use std::sync::Arc;
+use std::time::Instant;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuntimeError {
+    pub message: String,
+    pub span: Span,
+}
+
+impl RuntimeError {
+    fn new(message: String, span: Span) -> Self {
+        RuntimeError { message, span }
+    }
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Runtime error at {}: {}", self.span, self.message)
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+/// Which of the `Limits` a program ran into.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Limit {
+    Fuel,
+    Output,
+    StringMemory,
+}
+
+impl Display for Limit {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Limit::Fuel => "fuel",
+            Limit::Output => "output",
+            Limit::StringMemory => "string memory",
+        })
+    }
+}
+
+/// A program stopped because it exceeded one of the interpreter's `Limits`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LimitError {
+    pub limit: Limit,
+    pub message: String,
+    pub span: Span,
+}
+
+impl Display for LimitError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Limit error at {}: {}", self.span, self.message)
+    }
+}
+
+impl std::error::Error for LimitError {}
+
+/// Bounds on what a program may use, `None` means unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Limits {
+    /// Executed statements plus loop iterations.
+    pub fuel: Option<u64>,
+    /// Bytes written by `print`, line breaks included.
+    pub output_bytes: Option<usize>,
+    /// Bytes held by all string variables together, plus the string being built
+    /// by a concatenation.
+    pub string_bytes: Option<usize>,
+}
+
+/// Why a host stopped a running program.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interruption {
+    Cancelled,
+    Deadline,
+}
+
+/// A program stopped from the outside, by its cancellation flag or its deadline. The
+/// interpreter's variables keep the values they had at that point.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Interrupted {
+    pub reason: Interruption,
+    pub message: String,
+    pub span: Span,
+}
+
+impl Display for Interrupted {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Interrupted at {}: {}", self.span, self.message)
+    }
+}
+
+impl std::error::Error for Interrupted {}
+
+type RunResult<T> = Result<T, Error>;
+
+/// Where `print` writes and `read` reads, so frontends can redirect a program's I/O.
+pub trait Io {
+    fn print(&mut self, line: &str);
+
+    /// The next line of input without its line break, `None` at the end of input.
+    fn read_line(&mut self) -> Option<String>;
+}
+
+/// The process's standard output and input.
+pub struct StdIo;
+
+impl Io for StdIo {
+    fn print(&mut self, line: &str) {
+        println!("{}", line);
+    }
+
+    fn read_line(&mut self) -> Option<String> {
+        let mut input = String::new();
+        match stdin().read_line(&mut input) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => {
+                if let Some('\n') = input.chars().next_back() {
+                    input.pop();
+                }
+                Some(input)
+            }
+        }
+    }
+}
 
 trait NodeVisitor {
-    fn visit_read(&mut self, read: &Read);
-    fn visit_print_var(&mut self, print_var: &PrintVar);
-    fn visit_print_str(&mut self, print_str: &PrintStr);
-    fn visit_num(&self, num: &Num) -> i32;
+    fn visit_read(&mut self, read: &Read) -> RunResult<()>;
+    fn visit_print_var(&mut self, print_var: &PrintVar) -> RunResult<()>;
+    fn visit_print_str(&mut self, print_str: &PrintStr) -> RunResult<()>;
+    fn visit_num(&self, num: &Num) -> RunResult<Value>;
+    fn visit_real(&self, real: &Real) -> f64;
     fn visit_str(&self, str_node: &Str) -> String;
-    fn visit_bin_op(&mut self, bin_op: &BinOp) -> Value;
-    fn visit_bool_expr(&mut self, bool_expr: &BoolExpr) -> bool;
-    fn visit_unary_op(&mut self, unary_op: &UnaryOp) -> i32;
-    fn visit_assign(&mut self, assign: &Assign);
+    fn visit_char(&self, char_node: &Char) -> char;
+    fn visit_index(&mut self, index: &Index) -> RunResult<char>;
+    fn visit_bin_op(&mut self, bin_op: &BinOp) -> RunResult<Value>;
+    fn visit_bool_expr(&mut self, bool_expr: &BoolExpr) -> RunResult<bool>;
+    fn visit_unary_op(&mut self, unary_op: &UnaryOp) -> RunResult<Value>;
+    fn visit_assign(&mut self, assign: &Assign) -> RunResult<()>;
     fn visit_var(&self, var: &Var) -> Value;
-    fn visit_program(&mut self, program: &Program);
-    fn visit_var_decl(&mut self, var_decl: &VarDecl);
-    fn visit_decl_assign(&mut self, decl_assign: &DeclAssign);
+    fn visit_program(&mut self, program: &Program) -> RunResult<()>;
+    fn visit_var_decl(&mut self, var_decl: &VarDecl) -> RunResult<()>;
+    fn visit_decl_assign(&mut self, decl_assign: &DeclAssign) -> RunResult<()>;
     fn visit_type(&self, type_: &Type);
-    fn visit_for_loop(&mut self, for_loop: &ForLoop);
-    fn visit_if_statement(&mut self, if_statement: &IfStatement);
+    fn visit_for_loop(&mut self, for_loop: &ForLoop) -> RunResult<()>;
+    fn visit_if_statement(&mut self, if_statement: &IfStatement) -> RunResult<()>;
+    fn visit_call(&mut self, call: &Call) -> RunResult<Value>;
+}
+
+// when the debugger wants the next pause, depths count enclosing statement blocks
+#[derive(Clone, Copy)]
+enum Stepping {
+    Run,
+    Into,
+    Over(usize),
+    Out(usize),
 }
 
 pub struct Interpreter {
     parser: Parser,
     pub global_scope: HashMap<String, Value>,
+    /// Integers that overflow 64 bits continue as arbitrary precision values
+    /// instead of stopping with an overflow error.
+    pub big_integers: bool,
+    /// Folds constant expressions before running, see `fold::fold`.
+    pub fold_constants: bool,
+    /// Removes stores and declarations the program never reads before running, see
+    /// `optimizer::eliminate_dead_code`. What was removed is left in `remarks`.
+    pub eliminate_dead_code: bool,
+    /// Hoists invariant expressions out of loops and reduces multiplications by the
+    /// control variable before running, see `loops::optimize_loops`.
+    pub optimize_loops: bool,
+    /// What the optimisations did, in source order.
+    pub remarks: Vec<Remark>,
+    pub io: Box<dyn Io>,
+    pub tracer: Option<Tracer>,
+    pub profiler: Option<Profiler>,
+    /// Consulted before every statement, execution starts paused at the first one.
+    pub debugger: Option<Box<dyn Debugger>>,
+    pub limits: Limits,
+    /// Checked before every statement and loop iteration, setting it from another
+    /// thread stops the program.
+    pub cancel: Option<Arc<AtomicBool>>,
+    /// The program stops once this instant has passed.
+    pub deadline: Option<Instant>,
+    // what the program has used so far, measured against `limits`
+    fuel_used: u64,
+    output_used: usize,
+    string_bytes: usize,
+    stepping: Stepping,
+    depth: usize,
+    last_line: usize,
 }
 
 impl Interpreter {
@@ -36,205 +217,384 @@ impl Interpreter {
         Interpreter {
             parser,
             global_scope: HashMap::new(),
+            big_integers: false,
+            fold_constants: false,
+            eliminate_dead_code: false,
+            optimize_loops: false,
+            remarks: Vec::new(),
+            io: Box::new(StdIo),
+            tracer: None,
+            profiler: None,
+            debugger: None,
+            limits: Limits::default(),
+            cancel: None,
+            deadline: None,
+            fuel_used: 0,
+            output_used: 0,
+            string_bytes: 0,
+            stepping: Stepping::Into,
+            depth: 0,
+            last_line: 0,
         }
     }
 
-    pub fn interpret(&mut self) -> Value {
-        let tree = self.parser.parse();
+    pub fn interpret(&mut self) -> Result<Value, Error> {
+        let mut tree = self.parser.parse()?;
+        Checker::new().check(&tree)?;
+        if self.fold_constants {
+            tree = fold::fold(tree, self.big_integers)?;
+        }
+        if self.optimize_loops {
+            let (optimized, remarks) = loops::optimize_loops(tree, self.big_integers);
+            self.remarks.extend(remarks);
+            tree = optimized;
+        }
+        if self.eliminate_dead_code {
+            let (optimized, remarks) = optimizer::eliminate_dead_code(tree, self.big_integers);
+            self.remarks.extend(remarks);
+            tree = optimized;
+        }
+        self.remarks.sort_by_key(|remark| remark.span.start);
         self.visit(&tree)
     }
 
-    pub fn visit(&mut self, node: &Node) -> Value {
+    pub fn visit(&mut self, node: &Node) -> RunResult<Value> {
+        if let Some(tracer) = &mut self.tracer {
+            tracer.statement(node);
+        }
         match node {
             Node::BinOp(n) => self.visit_bin_op(n),
-            Node::UnaryOp(n) => Value::Number(self.visit_unary_op(n)),
-            Node::Num(n) => Value::Number(self.visit_num(n)),
-            Node::Str(n) => Value::String(self.visit_str(n)),
-            Node::NoOp => Value::None,
-            Node::BoolExpr(n) => Value::Boolean(self.visit_bool_expr(n)),
+            Node::UnaryOp(n) => self.visit_unary_op(n),
+            Node::Num(n) => self.visit_num(n),
+            Node::Real(n) => Ok(Value::Real(self.visit_real(n))),
+            Node::Str(n) => Ok(Value::String(self.visit_str(n))),
+            Node::Char(n) => Ok(Value::Char(self.visit_char(n))),
+            Node::Index(n) => Ok(Value::Char(self.visit_index(n)?)),
+            Node::NoOp => Ok(Value::None),
+            Node::BoolExpr(n) => Ok(Value::Boolean(self.visit_bool_expr(n)?)),
             Node::ForLoop(n) => {
-                self.visit_for_loop(n);
-                Value::None
+                self.visit_for_loop(n)?;
+                Ok(Value::None)
             }
             Node::IfStatement(n) => {
-                self.visit_if_statement(n);
-                Value::None
+                self.visit_if_statement(n)?;
+                Ok(Value::None)
             }
             Node::Assign(n) => {
-                self.visit_assign(n);
-                Value::None
+                self.visit_assign(n)?;
+                Ok(Value::None)
             }
-            Node::Var(n) => self.visit_var(n),
+            Node::Var(n) => Ok(self.visit_var(n)),
             Node::Program(n) => {
-                self.visit_program(n);
-                Value::None
+                self.visit_program(n)?;
+                Ok(Value::None)
             }
             Node::VarDecl(n) => {
-                self.visit_var_decl(n);
-                Value::None
+                self.visit_var_decl(n)?;
+                Ok(Value::None)
             }
             Node::DeclAssign(n) => {
-                self.visit_decl_assign(n);
-                Value::None
+                self.visit_decl_assign(n)?;
+                Ok(Value::None)
             }
             Node::PrintStr(n) => {
-                self.visit_print_str(n);
-                Value::None
+                self.visit_print_str(n)?;
+                Ok(Value::None)
             }
             Node::PrintVar(n) => {
-                self.visit_print_var(n);
-                Value::None
+                self.visit_print_var(n)?;
+                Ok(Value::None)
             }
             Node::Read(n) => {
-                self.visit_read(n);
-                Value::None
+                self.visit_read(n)?;
+                Ok(Value::None)
             }
+            Node::Call(n) => self.visit_call(n),
+        }
+    }
+
+    fn statements(&mut self, statements: &[Node]) -> RunResult<()> {
+        self.depth += 1;
+        for statement in statements {
+            self.pause(statement)?;
+            if let Some(span) = statement.span() {
+                self.step(span)?;
+            }
+            if let Some(profiler) = &mut self.profiler {
+                profiler.enter(statement);
+            }
+            let result = self.visit(statement);
+            if let Some(profiler) = &mut self.profiler {
+                profiler.exit();
+            }
+            result?;
+        }
+        self.depth -= 1;
+        Ok(())
+    }
+
+    // hands control to the debugger when stepping or a breakpoint stops before `statement`
+    fn pause(&mut self, statement: &Node) -> RunResult<()> {
+        let (debugger, span) = match (&mut self.debugger, statement.source_span()) {
+            (Some(debugger), Some(span)) => (debugger, span),
+            _ => return Ok(()),
+        };
+        let stepped = match self.stepping {
+            Stepping::Run => false,
+            Stepping::Into => true,
+            Stepping::Over(depth) => self.depth <= depth,
+            Stepping::Out(depth) => self.depth < depth,
+        };
+        // a breakpoint stops once when execution arrives at its line, and again in
+        // every loop iteration
+        let breakpoint = span.line != self.last_line && debugger.is_breakpoint(span.line);
+        self.last_line = span.line;
+        if !stepped && !breakpoint {
+            return Ok(());
+        }
+        self.stepping = match debugger.on_statement(span, &mut self.global_scope) {
+            DebugAction::Continue => Stepping::Run,
+            DebugAction::StepIn => Stepping::Into,
+            DebugAction::StepOver => Stepping::Over(self.depth),
+            DebugAction::StepOut => Stepping::Out(self.depth),
+            DebugAction::Stop => {
+                return Err(RuntimeError::new(
+                    String::from("execution stopped by the debugger"),
+                    span,
+                )
+                .into())
+            }
+        };
+        // the debugger may have changed strings, counting again keeps the total exact
+        self.string_bytes = self.global_scope.values().map(string_len).sum();
+        Ok(())
+    }
+
+    fn set_var(&mut self, var: &Var, value: Value) -> RunResult<()> {
+        let name = var_name(var);
+        let old = self.global_scope.get(&name);
+        let string_bytes = self.string_bytes - old.map_or(0, string_len) + string_len(&value);
+        self.check_strings(string_bytes, var.token.span)?;
+        if let Some(tracer) = &mut self.tracer {
+            tracer.write(&var.value.to_string(), old, &value, var.token.span);
+        }
+        self.string_bytes = string_bytes;
+        self.global_scope.insert(name, value);
+        Ok(())
+    }
+
+    // every statement and loop iteration costs fuel and gives the host a chance to stop it
+    fn step(&mut self, span: Span) -> RunResult<()> {
+        let reason = if self
+            .cancel
+            .as_ref()
+            .is_some_and(|cancel| cancel.load(AtomicOrdering::Relaxed))
+        {
+            Some((Interruption::Cancelled, "cancelled"))
+        } else if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            Some((Interruption::Deadline, "deadline passed"))
+        } else {
+            None
+        };
+        if let Some((reason, message)) = reason {
+            return Err(Error::Interrupted(Interrupted {
+                reason,
+                message: String::from(message),
+                span,
+            }));
+        }
+        self.fuel_used += 1;
+        match self.limits.fuel {
+            Some(max) if self.fuel_used > max => Err(limit_error(Limit::Fuel, max, "steps", span)),
+            _ => Ok(()),
+        }
+    }
+
+    // `print` writes the line and its line break, or nothing when it would exceed the limit
+    fn print(&mut self, line: &str, span: Span) -> RunResult<()> {
+        self.output_used += line.len() + 1;
+        match self.limits.output_bytes {
+            Some(max) if self.output_used > max => {
+                Err(limit_error(Limit::Output, max as u64, "bytes", span))
+            }
+            _ => {
+                self.io.print(line);
+                Ok(())
+            }
+        }
+    }
+
+    fn check_strings(&self, bytes: usize, span: Span) -> RunResult<()> {
+        match self.limits.string_bytes {
+            Some(max) if bytes > max => {
+                Err(limit_error(Limit::StringMemory, max as u64, "bytes", span))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn integer_op(&self, op: &Token, left: &Value, right: &Value) -> RunResult<Value> {
+        integer_op(op, left, right, self.big_integers)
+            .map_err(|e| RuntimeError::new(e, op.span).into())
+    }
+
+    // range bounds, indices and builtin arguments stay within 64 bits
+    fn small_integer(&self, value: &Value, what: &str, span: Span) -> RunResult<i64> {
+        match value {
+            Value::Number(n) => Ok(*n),
+            Value::BigNumber(n) => {
+                Err(RuntimeError::new(format!("{} {} is too large", what, n), span).into())
+            }
+            _ => panic!("Type error"),
         }
     }
 }
 
 impl NodeVisitor for Interpreter {
-    fn visit_for_loop(&mut self, for_loop: &ForLoop) {
+    fn visit_for_loop(&mut self, for_loop: &ForLoop) -> RunResult<()> {
         match self.visit_var(&for_loop.var_node) {
-            Value::Number(_) => {}
+            Value::Number(_) | Value::BigNumber(_) => {}
             Value::String(_) => panic!("loop variable must be declared as integer"),
             Value::Boolean(_) => panic!("loop variable must be declared as integer"),
             _ => panic!("variable used before declaration"),
         };
-        let var_name = match &for_loop.var_node.value {
-            Value::String(s) => s.to_string(),
-            _ => panic!("Error"),
-        };
-        let start = match self.visit(&for_loop.start) {
-            Value::Number(n) => n,
-            _ => panic!("Error"),
-        };
-        let end = match self.visit(&for_loop.end) {
-            Value::Number(n) => n,
-            _ => panic!("Error"),
-        };
-        for i in start..end {
-            self.global_scope
-                .insert(var_name.to_lowercase(), Value::Number(i));
-
-            for statement in &for_loop.statements {
-                self.visit(statement);
+        let span = for_loop.var_node.token.span;
+        let start = self.visit(&for_loop.start)?;
+        let start = self.small_integer(&start, "range start", span)?;
+        let end = self.visit(&for_loop.end)?;
+        let end = self.small_integer(&end, "range end", span)?;
+        for i in start..=end {
+            self.step(span)?;
+            // the iteration event stands for the write of the control variable
+            if let Some(tracer) = &mut self.tracer {
+                tracer.iteration(&for_loop.var_node.value.to_string(), i, span);
             }
+            let name = var_name(&for_loop.var_node);
+            self.global_scope.insert(name, Value::Number(i));
+            // each iteration arrives at the body's breakpoints again
+            self.last_line = 0;
+            self.statements(&for_loop.statements)?;
         }
+        Ok(())
     }
 
-    fn visit_bool_expr(&mut self, bool_expr: &BoolExpr) -> bool {
+    fn visit_bool_expr(&mut self, bool_expr: &BoolExpr) -> RunResult<bool> {
         match &bool_expr.op.type_ {
-            TokenType::And => {
-                let left_bool = match self.visit(&bool_expr.left) {
-                    Value::Boolean(b) => b,
-                    _ => panic!("Error"),
-                };
-                let right_bool = match self.visit(&bool_expr.right) {
+            TokenType::And | TokenType::Or => {
+                let left_bool = match self.visit(&bool_expr.left)? {
                     Value::Boolean(b) => b,
-                    _ => panic!("Error"),
+                    _ => panic!("Type error"),
                 };
-                return left_bool && right_bool;
-            }
-            TokenType::Semi => {
-                match self.visit(&bool_expr.left) {
-                    Value::Boolean(b) => return b,
-                    _ => panic!("Error"),
+                // short-circuit: the right operand is only evaluated when it decides the result
+                if (bool_expr.op.type_ == TokenType::And) != left_bool {
+                    return Ok(left_bool);
+                }
+                match self.visit(&bool_expr.right)? {
+                    Value::Boolean(b) => return Ok(b),
+                    _ => panic!("Type error"),
                 };
             }
             TokenType::Not => {
-                match self.visit(&bool_expr.right) {
-                    Value::Boolean(b) => return !b,
+                match self.visit(&bool_expr.right)? {
+                    Value::Boolean(b) => return Ok(!b),
                     _ => panic!("Type error"),
                 };
             }
             _ => {}
         }
-        let left = match self.visit(&bool_expr.left) {
-            Value::Number(n) => n,
-            _ => panic!("Type error"),
-        };
-        let right = match self.visit(&bool_expr.right) {
-            Value::Number(n) => n,
-            _ => panic!("Type error"),
-        };
-        match &bool_expr.op.type_ {
-            TokenType::Equal => left == right,
-            TokenType::LessThan => left < right,
-            _ => unimplemented!(),
-        }
+        let left = self.visit(&bool_expr.left)?;
+        let right = self.visit(&bool_expr.right)?;
+        Ok(compare(&bool_expr.op.type_, &left, &right))
     }
 
-    fn visit_if_statement(&mut self, if_statement: &IfStatement) {
-        let boolean = match self.visit(&if_statement.bool_expr) {
+    fn visit_if_statement(&mut self, if_statement: &IfStatement) -> RunResult<()> {
+        let boolean = match self.visit(&if_statement.bool_expr)? {
             Value::Boolean(b) => b,
             _ => panic!("Error: If statement condition must be a boolean value"),
         };
         if boolean {
-            for statement in &if_statement.statements {
-                self.visit(statement);
-            }
+            self.statements(&if_statement.statements)
         } else {
-            for statement in &if_statement.else_statements {
-                self.visit(statement);
-            }
+            self.statements(&if_statement.else_statements)
         }
     }
 
-    fn visit_print_var(&mut self, print_var: &PrintVar) {
+    fn visit_print_var(&mut self, print_var: &PrintVar) -> RunResult<()> {
         let var_value = match self.visit_var(&print_var.var_node) {
-            Value::Number(v) => v.to_string(),
-            Value::String(v) => v,
-            Value::Boolean(v) => v.to_string(),
-            _ => panic!("variable used before declaration"),
+            Value::None => panic!("variable used before declaration"),
+            v => v.to_string(),
         };
-        println!("{}", var_value);
+        self.print(&var_value, print_var.span)
     }
 
-    fn visit_print_str(&mut self, print_str: &PrintStr) {
+    fn visit_print_str(&mut self, print_str: &PrintStr) -> RunResult<()> {
         let string_literal = match &print_str.value {
             Value::String(s) => s.to_string(),
             _ => panic!("Error"),
         };
-        println!("{}", string_literal);
+        self.print(&string_literal, print_str.span)
     }
 
-    fn visit_read(&mut self, read: &Read) {
-        let var_name = match &read.var_node.value {
-            Value::String(s) => s.to_string(),
-            _ => panic!("Error"),
-        };
-        let mut input = String::new();
-        stdin().read_line(&mut input).unwrap();
-        if let Some('\n') = input.chars().next_back() {
-            input.pop();
+    fn visit_read(&mut self, read: &Read) -> RunResult<()> {
+        let input = self.io.read_line().unwrap_or_default();
+        let span = read.var_node.token.span;
+        let not_numeric = RuntimeError::new(
+            format!(
+                "cannot read non-numeric value {:?} into numeric variable",
+                input
+            ),
+            span,
+        );
+        let value = match self.visit_var(&read.var_node) {
+            Value::String(_) => Value::String(input),
+            Value::Number(_) | Value::BigNumber(_) => match input.trim().parse::<i64>() {
+                Ok(n) => Value::Number(n),
+                Err(_) => match BigInt::parse(input.trim()) {
+                    Some(n) if self.big_integers => Value::BigNumber(n),
+                    Some(n) => {
+                        return Err(RuntimeError::new(
+                            format!("integer {} does not fit in 64 bits", n),
+                            span,
+                        )
+                        .into())
+                    }
+                    None => return Err(not_numeric.into()),
+                },
+            },
+            Value::Real(_) => match input.trim().parse::<f64>() {
+                Ok(r) => Value::Real(r),
+                Err(_) => return Err(not_numeric.into()),
+            },
+            _ => panic!(
+                "variable {} used before declaration",
+                var_name(&read.var_node)
+            ),
         };
+        self.set_var(&read.var_node, value)
+    }
 
-        let var_value = self
-            .global_scope
-            .get(&var_name.to_lowercase())
-            .unwrap()
-            .clone();
-        match var_value {
-            Value::String(_) => {
-                self.global_scope
-                    .insert(var_name.to_lowercase(), Value::String(input));
-            }
-            Value::Number(_) => {
-                if input.parse::<i32>().is_ok() {
-                    self.global_scope
-                        .insert(var_name.to_lowercase(), Value::String(input));
-                } else {
-                    panic!("Error: cannot read non-numeric value into numeric variable");
-                }
-            }
-            _ => panic!("variable {} used before declaration", var_name),
-        };
+    fn visit_num(&self, num: &Num) -> RunResult<Value> {
+        match &num.value {
+            Value::Number(n) => Ok(Value::Number(*n)),
+            Value::BigNumber(n) if self.big_integers => Ok(Value::BigNumber(n.clone())),
+            Value::BigNumber(n) => Err(RuntimeError::new(
+                format!(
+                    "integer literal {} does not fit in 64 bits, run with --big-int",
+                    n
+                ),
+                num.token.span,
+            )
+            .into()),
+            _ => unimplemented!(),
+        }
     }
 
-    fn visit_num(&self, num: &Num) -> i32 {
-        match num.value {
-            Value::Number(n) => n,
+    fn visit_real(&self, real: &Real) -> f64 {
+        match real.value {
+            Value::Real(r) => r,
             _ => unimplemented!(),
         }
     }
@@ -246,113 +606,256 @@ impl NodeVisitor for Interpreter {
         }
     }
 
-    fn visit_bin_op(&mut self, bin_op: &BinOp) -> Value {
-        let left = self.visit(&bin_op.left);
-        let right = self.visit(&bin_op.right);
-        match (left, right) {
-            (Value::Number(n), Value::Number(m)) => match bin_op.op.type_ {
-                TokenType::Plus => Value::Number(n + m),
-                TokenType::Minus => Value::Number(n - m),
-                TokenType::Mul => Value::Number(n * m),
-                TokenType::Div => Value::Number(n / m),
-                _ => unimplemented!(),
-            },
-            (Value::String(s), Value::String(t)) => match bin_op.op.type_ {
+    fn visit_char(&self, char_node: &Char) -> char {
+        match char_node.value {
+            Value::Char(c) => c,
+            _ => unimplemented!(),
+        }
+    }
+
+    fn visit_index(&mut self, index: &Index) -> RunResult<char> {
+        let target = match self.visit(&index.target)? {
+            Value::String(s) => s,
+            _ => panic!("Type error"),
+        };
+        let i = self.visit(&index.index)?;
+        let i = self.small_integer(&i, "index", index.token.span)?;
+        builtins::char_at(&target, i).map_err(|e| RuntimeError::new(e, index.token.span).into())
+    }
+
+    fn visit_bin_op(&mut self, bin_op: &BinOp) -> RunResult<Value> {
+        let left = self.visit(&bin_op.left)?;
+        let right = self.visit(&bin_op.right)?;
+        match (&left, &right) {
+            (Value::String(_), _) | (Value::Char(_), _) => match bin_op.op.type_ {
                 TokenType::Plus => {
-                    let mut result = s.clone();
-                    result.push_str(&t);
-                    Value::String(result)
+                    let (left, right) = (left.to_string(), right.to_string());
+                    let bytes = self.string_bytes + left.len() + right.len();
+                    self.check_strings(bytes, bin_op.op.span)?;
+                    Ok(Value::String(left + &right))
                 }
                 _ => unimplemented!(),
             },
+            (Value::Real(_), _) | (_, Value::Real(_)) => {
+                let (x, y) = (as_real(&left).unwrap(), as_real(&right).unwrap());
+                Ok(Value::Real(real_op(&bin_op.op.type_, x, y)))
+            }
+            (Value::Number(_), _) | (Value::BigNumber(_), _) => {
+                self.integer_op(&bin_op.op, &left, &right)
+            }
             _ => panic!("Type mismatch"),
         }
     }
 
-    fn visit_program(&mut self, program: &Program) {
-        for child in &program.children {
-            self.visit(child);
-        }
+    fn visit_program(&mut self, program: &Program) -> RunResult<()> {
+        self.statements(&program.children)
     }
 
-    fn visit_unary_op(&mut self, unary_op: &UnaryOp) -> i32 {
-        let expr = match self.visit(&unary_op.expr) {
-            Value::Number(n) => n,
+    fn visit_unary_op(&mut self, unary_op: &UnaryOp) -> RunResult<Value> {
+        let value = self.visit(&unary_op.expr)?;
+        match (&value, &unary_op.op.type_) {
+            (_, TokenType::Plus) => Ok(value),
+            (Value::Real(r), TokenType::Minus) => Ok(Value::Real(-r)),
+            // negation is 0 - n, which overflows only for the smallest int
+            (_, TokenType::Minus) => self.integer_op(&unary_op.op, &Value::Number(0), &value),
             _ => panic!("Error"),
-        };
-        match unary_op.op.type_ {
-            TokenType::Plus => (0) + expr,
-            TokenType::Minus => (0) - expr,
-            _ => unimplemented!(),
         }
     }
 
-    fn visit_assign(&mut self, assign: &Assign) {
+    fn visit_assign(&mut self, assign: &Assign) -> RunResult<()> {
         let left = self.visit_var(&assign.left);
-        let right = self.visit(&assign.right);
-        match (left, &right) {
-            (Value::Number(_), Value::Number(_)) => {}
-            (Value::String(_), Value::String(_)) => {}
-            (Value::Boolean(_), Value::Boolean(_)) => {}
+        let right = self.visit(&assign.right)?;
+        let right = match (left, right) {
+            (Value::Real(_), right) => to_real(right),
+            (Value::Number(_), right @ Value::Number(_))
+            | (Value::Number(_), right @ Value::BigNumber(_))
+            | (Value::BigNumber(_), right @ Value::Number(_))
+            | (Value::BigNumber(_), right @ Value::BigNumber(_)) => right,
+            (Value::String(_), right @ Value::String(_)) => right,
+            (Value::Boolean(_), right @ Value::Boolean(_)) => right,
+            (Value::Char(_), right @ Value::Char(_)) => right,
             _ => panic!("Type mismatch"),
         };
-        let var_name = match &assign.left.value {
-            Value::String(s) => s.to_string(),
-            _ => {
-                panic!("Error");
-            }
-        };
-        self.global_scope
-            .insert(var_name.to_lowercase(), right.clone());
+        self.set_var(&assign.left, right)
     }
 
     fn visit_var(&self, var: &Var) -> Value {
-        let var_name = match &var.value {
-            Value::String(s) => s.to_string(),
-            _ => panic!("Error"),
+        self.global_scope.get(&var_name(var)).unwrap().clone()
+    }
+
+    fn visit_decl_assign(&mut self, decl_assign: &DeclAssign) -> RunResult<()> {
+        let value = self.visit(&decl_assign.right)?;
+        let value = match &decl_assign.type_node.token.type_ {
+            TokenType::Real => to_real(value),
+            _ => value,
         };
-        self.global_scope
-            .get(&var_name.to_lowercase())
-            .unwrap()
-            .clone()
+        self.set_var(&decl_assign.left, value)
     }
 
-    fn visit_decl_assign(&mut self, decl_assign: &DeclAssign) {
-        let var_name = match &decl_assign.left.value {
-            Value::String(s) => s.to_string(),
-            _ => {
-                panic!("Error");
-            }
+    fn visit_var_decl(&mut self, var_decl: &VarDecl) -> RunResult<()> {
+        let value = match &var_decl.type_node.token.type_ {
+            TokenType::Str => Value::String("".to_string()),
+            TokenType::Integer => Value::Number(0),
+            TokenType::Bool => Value::Boolean(true),
+            TokenType::Char => Value::Char('\0'),
+            TokenType::Real => Value::Real(0.0),
+            _ => unimplemented!(),
         };
-        let value = self.visit(&decl_assign.right);
-        self.global_scope.insert(var_name.to_lowercase(), value);
+        self.set_var(&var_decl.var_node, value)
     }
 
-    fn visit_var_decl(&mut self, var_decl: &VarDecl) {
-        let var_name = match &var_decl.var_node.value {
-            Value::String(s) => s.to_string(),
-            _ => {
-                panic!("Error");
-            }
+    fn visit_type(&self, _: &Type) {}
+
+    fn visit_call(&mut self, call: &Call) -> RunResult<Value> {
+        let name = match &call.value {
+            Value::String(s) => s.to_lowercase(),
+            _ => panic!("Error"),
         };
-        match &var_decl.type_node.token.type_ {
-            TokenType::Str => {
-                self.global_scope
-                    .insert(var_name.to_lowercase(), Value::String("".to_string()));
-            }
-            TokenType::Integer => {
-                self.global_scope
-                    .insert(var_name.to_lowercase(), Value::Number(0));
-            }
-            TokenType::Bool => {
-                self.global_scope
-                    .insert(var_name.to_lowercase(), Value::Boolean(true));
+        let mut args = Vec::new();
+        for arg in &call.args {
+            let value = self.visit(arg)?;
+            // builtins work on 64-bit integers only
+            if let Value::BigNumber(_) = value {
+                let what = format!("argument of {}", name);
+                self.small_integer(&value, &what, call.token.span)?;
             }
+            args.push(value);
+        }
+        let builtin = &BUILTINS[&name[..]];
+        (builtin.function)(&args).map_err(|e| RuntimeError::new(e, call.token.span).into())
+    }
+}
+
+fn limit_error(limit: Limit, max: u64, unit: &str, span: Span) -> Error {
+    let message = format!("{} limit of {} {} exceeded", limit, max, unit);
+    Error::Limit(LimitError {
+        limit,
+        message,
+        span,
+    })
+}
+
+fn string_len(value: &Value) -> usize {
+    match value {
+        Value::String(s) => s.len(),
+        _ => 0,
+    }
+}
+
+// `op` is one of the six comparisons; NaN compares false except with <>
+pub(crate) fn compare(op: &TokenType, left: &Value, right: &Value) -> bool {
+    let ordering = match (left, right) {
+        (Value::Number(n), Value::Number(m)) => Some(n.cmp(m)),
+        (Value::String(s), Value::String(t)) => Some(s.cmp(t)),
+        (Value::Boolean(a), Value::Boolean(b)) => Some(a.cmp(b)),
+        (Value::Char(a), Value::Char(b)) => Some(a.cmp(b)),
+        (Value::Real(_), _) | (_, Value::Real(_)) => match (as_real(left), as_real(right)) {
+            (Some(x), Some(y)) => x.partial_cmp(&y),
+            _ => panic!("Type error"),
+        },
+        (a, b) => Some(to_big(a).cmp(&to_big(b))),
+    };
+    match op {
+        TokenType::Equal => ordering == Some(Ordering::Equal),
+        TokenType::NotEqual => ordering != Some(Ordering::Equal),
+        TokenType::LessThan => ordering == Some(Ordering::Less),
+        TokenType::LessEqual => ordering.is_some_and(|o| o != Ordering::Greater),
+        TokenType::GreaterThan => ordering == Some(Ordering::Greater),
+        TokenType::GreaterEqual => ordering.is_some_and(|o| o != Ordering::Less),
+        _ => unimplemented!(),
+    }
+}
+
+// checked integer arithmetic, falling back to big integers on overflow when enabled
+pub(crate) fn integer_op(
+    op: &Token,
+    left: &Value,
+    right: &Value,
+    big_integers: bool,
+) -> Result<Value, String> {
+    if let (Value::Number(n), Value::Number(m)) = (left, right) {
+        let result = match op.type_ {
+            TokenType::Plus => n.checked_add(*m),
+            TokenType::Minus => n.checked_sub(*m),
+            TokenType::Mul => n.checked_mul(*m),
+            TokenType::Div => n.checked_div(*m),
+            // only overflows for the smallest integer mod -1, which is still 0
+            TokenType::Mod if *m == -1 => Some(0),
+            TokenType::Mod => n.checked_rem(*m),
             _ => unimplemented!(),
+        };
+        if let Some(value) = result {
+            return Ok(Value::Number(value));
         }
     }
+    let (a, b) = (to_big(left), to_big(right));
+    if let (TokenType::Div, true) | (TokenType::Mod, true) = (&op.type_, b.is_zero()) {
+        return Err(String::from("division by zero"));
+    }
+    if !big_integers {
+        return Err(format!(
+            "integer overflow in {} {} {}",
+            left, op.value, right
+        ));
+    }
+    let result = match op.type_ {
+        TokenType::Plus => a.add(&b),
+        TokenType::Minus => a.sub(&b),
+        TokenType::Mul => a.mul(&b),
+        TokenType::Div => a.div_rem(&b).unwrap().0,
+        TokenType::Mod => a.div_rem(&b).unwrap().1,
+        _ => unimplemented!(),
+    };
+    Ok(normalize(result))
+}
 
-    fn visit_type(&self, _: &Type) {}
+pub(crate) fn real_op(op: &TokenType, x: f64, y: f64) -> f64 {
+    match op {
+        TokenType::Plus => x + y,
+        TokenType::Minus => x - y,
+        TokenType::Mul => x * y,
+        TokenType::Div => x / y,
+        _ => unimplemented!(),
+    }
+}
+
+fn var_name(var: &Var) -> String {
+    match &var.value {
+        Value::String(s) => s.to_lowercase(),
+        _ => panic!("Error"),
+    }
+}
+
+pub(crate) fn as_real(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => Some(*n as f64),
+        Value::BigNumber(n) => Some(n.to_f64()),
+        Value::Real(r) => Some(*r),
+        _ => None,
+    }
+}
+
+fn to_real(value: Value) -> Value {
+    match as_real(&value) {
+        Some(r) => Value::Real(r),
+        None => value,
+    }
+}
+
+fn to_big(value: &Value) -> BigInt {
+    match value {
+        Value::Number(n) => BigInt::from_i64(*n),
+        Value::BigNumber(n) => n.clone(),
+        _ => panic!("Type error"),
+    }
+}
+
+// big results that fit 64 bits go back to the fast representation
+fn normalize(value: BigInt) -> Value {
+    match value.to_i64() {
+        Some(n) => Value::Number(n),
+        None => Value::BigNumber(value),
+    }
 }
 
 #[cfg(test)]
@@ -363,6 +866,16 @@ mod tests {
     use crate::tokens::Value;
     use std::collections::HashMap;
 
+    struct Discard;
+
+    impl Io for Discard {
+        fn print(&mut self, _: &str) {}
+
+        fn read_line(&mut self) -> Option<String> {
+            None
+        }
+    }
+
     #[test]
     fn variables_and_arithmetic() {
         let text = "
@@ -373,7 +886,7 @@ mod tests {
         let scanner = Scanner::new(text.to_string());
         let parser = Parser::new(scanner);
         let mut interpreter = Interpreter::new(parser);
-        interpreter.interpret();
+        interpreter.interpret().unwrap();
 
         let mut expected: HashMap<String, Value> = HashMap::new();
         expected.insert(String::from("a"), Value::Number(2));
@@ -382,4 +895,240 @@ mod tests {
 
         assert_eq!(interpreter.global_scope, expected);
     }
+
+    #[test]
+    fn comparison_and_logic() {
+        let text = "
+        var z : int := 0;
+        var a : bool := 3 >= 2 & \"abc\" < \"abd\" & !(1 <> 1);
+        var b : bool := 1 > 2 | 2 <= 1 | 1 != 1;
+        var c : bool := z = 1 & 1 / z = 0;
+        var d : bool := z = 0 | 1 / z = 0;";
+
+        let scanner = Scanner::new(text.to_string());
+        let parser = Parser::new(scanner);
+        let mut interpreter = Interpreter::new(parser);
+        interpreter.interpret().unwrap();
+
+        assert_eq!(interpreter.global_scope["a"], Value::Boolean(true));
+        assert_eq!(interpreter.global_scope["b"], Value::Boolean(false));
+        assert_eq!(interpreter.global_scope["c"], Value::Boolean(false));
+        assert_eq!(interpreter.global_scope["d"], Value::Boolean(true));
+    }
+
+    #[test]
+    fn modulo_and_builtins() {
+        let text = "
+        var a : int := 17 % 5;
+        var b : int := pow(2, 10) + abs(-3) - max(1, min(4, 9));
+        var c : int := sqrt(99);
+        var d : string := to_string(to_int(\" 42 \") + 1);";
+
+        let scanner = Scanner::new(text.to_string());
+        let parser = Parser::new(scanner);
+        let mut interpreter = Interpreter::new(parser);
+        interpreter.interpret().unwrap();
+
+        assert_eq!(interpreter.global_scope["a"], Value::Number(2));
+        assert_eq!(interpreter.global_scope["b"], Value::Number(1023));
+        assert_eq!(interpreter.global_scope["c"], Value::Number(9));
+        assert_eq!(
+            interpreter.global_scope["d"],
+            Value::String(String::from("43"))
+        );
+    }
+
+    #[test]
+    fn strings_and_chars() {
+        let text = "
+        var s : string := \"good morning\";
+        var n : int := length(s);
+        var c : char := s[3];
+        var t : string := upper(substring(s, 5, 6)) + '!';
+        var i : int := index_of(s, \"morn\");
+        var j : int := index_of(lower(t), \"x\");
+        var b : bool := \"abc\" < \"abd\" & 'a' < 'b';";
+
+        let scanner = Scanner::new(text.to_string());
+        let parser = Parser::new(scanner);
+        let mut interpreter = Interpreter::new(parser);
+        interpreter.interpret().unwrap();
+
+        assert_eq!(interpreter.global_scope["n"], Value::Number(12));
+        assert_eq!(interpreter.global_scope["c"], Value::Char('d'));
+        assert_eq!(
+            interpreter.global_scope["t"],
+            Value::String(String::from("MORNIN!"))
+        );
+        assert_eq!(interpreter.global_scope["i"], Value::Number(5));
+        assert_eq!(interpreter.global_scope["j"], Value::Number(-1));
+        assert_eq!(interpreter.global_scope["b"], Value::Boolean(true));
+    }
+
+    #[test]
+    fn real_numbers() {
+        let text = "
+        var r : real := 1;
+        var s : real := r / 4 + 2.5e1 - .5;
+        var t : real := 7 / 2;
+        var b : bool := 3 < 3.5 & 1e-3 = 0.001;
+        var n : int := trunc(-s) + round(2.5);
+        var i : int;
+        for i in 1..2 do
+            r := r * 1.5;
+        end for;";
+
+        let scanner = Scanner::new(text.to_string());
+        let parser = Parser::new(scanner);
+        let mut interpreter = Interpreter::new(parser);
+        interpreter.interpret().unwrap();
+
+        assert_eq!(interpreter.global_scope["r"], Value::Real(2.25));
+        assert_eq!(interpreter.global_scope["s"], Value::Real(24.75));
+        assert_eq!(interpreter.global_scope["t"], Value::Real(3.0));
+        assert_eq!(interpreter.global_scope["b"], Value::Boolean(true));
+        assert_eq!(interpreter.global_scope["n"], Value::Number(-21));
+    }
+
+    #[test]
+    fn resource_limits() {
+        let run = |text: &str, limits: Limits| {
+            let mut interpreter = Interpreter::new(Parser::new(Scanner::new(text.to_string())));
+            interpreter.limits = limits;
+            interpreter.io = Box::new(Discard);
+            let result = interpreter.interpret();
+            (result, interpreter.global_scope)
+        };
+        let endless = "var i : int; var j : int;
+        for i in 0..1000000 do for j in 0..1000000 do end for; end for;";
+        let fuel = Limits {
+            fuel: Some(100),
+            ..Limits::default()
+        };
+        match run(endless, fuel) {
+            (Err(Error::Limit(e)), scope) => {
+                assert_eq!(e.limit, Limit::Fuel);
+                assert_eq!(e.message, "fuel limit of 100 steps exceeded");
+                assert_eq!((e.span.line, e.span.column), (2, 36));
+                // 4 statements and the first outer iteration leave 95 inner ones
+                assert_eq!(scope["j"], Value::Number(94));
+            }
+            result => panic!("expected the fuel limit, got {:?}", result),
+        }
+
+        let output = Limits {
+            output_bytes: Some(10),
+            ..Limits::default()
+        };
+        let printing = "print \"hello\"; print \"hello\";";
+        match run(printing, output) {
+            (Err(Error::Limit(e)), _) => {
+                assert_eq!(e.limit, Limit::Output);
+                assert_eq!((e.span.line, e.span.column), (1, 16));
+            }
+            result => panic!("expected the output limit, got {:?}", result),
+        }
+
+        let strings = Limits {
+            string_bytes: Some(64),
+            ..Limits::default()
+        };
+        let doubling = "var s : string := \"ab\"; var i : int;
+        for i in 0..100 do s := s + s; end for;";
+        match run(doubling, strings) {
+            (Err(Error::Limit(e)), scope) => {
+                assert_eq!(
+                    e.to_string(),
+                    "Limit error at 2:35: string memory limit of 64 bytes exceeded"
+                );
+                assert_eq!(scope["s"].to_string().len(), 32);
+            }
+            result => panic!("expected the string memory limit, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn interrupts_on_cancel_and_deadline() {
+        let endless = "var n : int; var i : int; var j : int;
+        for i in 0..1000000 do for j in 0..1000000 do n := n + 1; end for; end for;";
+        let mut interpreter = Interpreter::new(Parser::new(Scanner::new(endless.to_string())));
+        let cancel = Arc::new(AtomicBool::new(false));
+        interpreter.cancel = Some(cancel.clone());
+        let canceller = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            cancel.store(true, AtomicOrdering::Relaxed);
+        });
+        match interpreter.interpret() {
+            Err(Error::Interrupted(e)) => assert_eq!(e.reason, Interruption::Cancelled),
+            result => panic!("expected cancellation, got {:?}", result),
+        }
+        canceller.join().unwrap();
+        // the loop stopped between two statements, so n counts the completed iterations
+        let n = match interpreter.global_scope["n"] {
+            Value::Number(n) => n,
+            _ => unreachable!(),
+        };
+        let (i, j) = match (
+            &interpreter.global_scope["i"],
+            &interpreter.global_scope["j"],
+        ) {
+            (Value::Number(i), Value::Number(j)) => (*i, *j),
+            _ => unreachable!(),
+        };
+        assert!(n > 0);
+        assert!(n == i * 1000000 + j || n == i * 1000000 + j + 1);
+
+        let mut interpreter = Interpreter::new(Parser::new(Scanner::new(endless.to_string())));
+        interpreter.deadline = Some(Instant::now() + std::time::Duration::from_millis(20));
+        match interpreter.interpret() {
+            Err(e @ Error::Interrupted(_)) => {
+                assert!(e.to_string().ends_with(": deadline passed"));
+            }
+            result => panic!("expected the deadline, got {:?}", result),
+        }
+    }
+
+    fn run_big(text: &str, big_integers: bool) -> Result<Value, Error> {
+        let mut interpreter = Interpreter::new(Parser::new(Scanner::new(text.to_string())));
+        interpreter.big_integers = big_integers;
+        interpreter.interpret()?;
+        Ok(interpreter.global_scope["n"].clone())
+    }
+
+    #[test]
+    fn checked_integer_arithmetic() {
+        let overflow = "
+        var n : int := 9223372036854775807;
+        n := n + 1;";
+        match run_big(overflow, false) {
+            Err(Error::Runtime(e)) => {
+                assert_eq!(e.message, "integer overflow in 9223372036854775807 + 1");
+                assert_eq!((e.span.line, e.span.column), (3, 16));
+            }
+            result => panic!("expected overflow, got {:?}", result),
+        }
+        match run_big("var z : int := 0;\nvar n : int := 7 % z;", true) {
+            Err(Error::Runtime(e)) => {
+                assert_eq!(e.message, "division by zero");
+                assert_eq!((e.span.line, e.span.column), (2, 18));
+            }
+            result => panic!("expected division by zero, got {:?}", result),
+        }
+        assert!(run_big("var n : int := 99999999999999999999;", false).is_err());
+        let smallest = "var m : int := -1;
+        var n : int := -9223372036854775807 - 1;
+        n := n % m;";
+        assert_eq!(run_big(smallest, false).unwrap(), Value::Number(0));
+
+        let factorial = "
+        var n : int := 1;
+        var i : int;
+        for i in 1..25 do
+            n := n * i;
+        end for;
+        n := n - 15511210043330985984000000 + 1;";
+        assert_eq!(run_big(factorial, true), Ok(Value::Number(1)));
+        let big = run_big("var n : int := 10 * 9223372036854775807 / 3 - 1;", true).unwrap();
+        assert_eq!(big.to_string(), "30744573456182586022");
+    }
 }
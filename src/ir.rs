@@ -0,0 +1,1112 @@
+use crate::builtins::BUILTINS;
+use crate::checker::SemanticError;
+use crate::dataflow::{self, defined};
+use crate::error::Error;
+use crate::formatter::escape;
+use crate::nodes::{DataType, ForLoop, IfStatement, Node};
+use crate::optimizer::Types;
+use crate::tokens::{Span, TokenType, Value};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::{self, Display, Formatter};
+
+/// An SSA value, defined exactly once by an instruction or a phi.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ValueId(pub usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BlockId(pub usize);
+
+impl Display for ValueId {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "%{}", self.0)
+    }
+}
+
+impl Display for BlockId {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "b{}", self.0)
+    }
+}
+
+/// An instruction's input: a value or a constant.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operand {
+    Value(ValueId),
+    Int(i64),
+    Real(f64),
+    Bool(bool),
+    Char(char),
+    Str(String),
+}
+
+impl Display for Operand {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Operand::Value(value) => write!(f, "{}", value),
+            Operand::Int(n) => write!(f, "{}", n),
+            Operand::Real(r) => write!(f, "{:?}", r),
+            Operand::Bool(b) => write!(f, "{}", b),
+            Operand::Char(c) => write!(f, "'{}'", escape(&c.to_string(), '\'')),
+            Operand::Str(s) => write!(f, "\"{}\"", escape(s, '"')),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOp {
+    /// Checked on integers: overflow stops the program.
+    Add,
+    Sub,
+    Mul,
+    /// Checked on integers: division by zero and overflow stop the program.
+    Div,
+    Mod,
+    /// Of strings and characters, giving a string.
+    Concat,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl BinaryOp {
+    pub fn is_comparison(self) -> bool {
+        matches!(
+            self,
+            BinaryOp::Eq | BinaryOp::Ne | BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge
+        )
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            BinaryOp::Add => "add",
+            BinaryOp::Sub => "sub",
+            BinaryOp::Mul => "mul",
+            BinaryOp::Div => "div",
+            BinaryOp::Mod => "mod",
+            BinaryOp::Concat => "concat",
+            BinaryOp::Eq => "eq",
+            BinaryOp::Ne => "ne",
+            BinaryOp::Lt => "lt",
+            BinaryOp::Le => "le",
+            BinaryOp::Gt => "gt",
+            BinaryOp::Ge => "ge",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryOp {
+    /// Checked on integers, `-` of the smallest one overflows.
+    Neg,
+    Not,
+    /// The implicit conversion where a real is expected and an int given.
+    IntToReal,
+}
+
+impl UnaryOp {
+    fn name(self) -> &'static str {
+        match self {
+            UnaryOp::Neg => "neg",
+            UnaryOp::Not => "not",
+            UnaryOp::IntToReal => "itor",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Kind {
+    Binary(BinaryOp, Operand, Operand),
+    Unary(UnaryOp, Operand),
+    /// The character of a string at an index, which must be in range.
+    Index(Operand, Operand),
+    /// A builtin function, see `builtins::BUILTINS`.
+    Call(String, Vec<Operand>),
+    /// A line of input as the result type: int, real or string.
+    Read,
+    Print(Operand),
+}
+
+/// One instruction. `span` locates the source it came from, for the runtime errors
+/// of instructions that can fail.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instruction {
+    pub result: Option<ValueId>,
+    pub kind: Kind,
+    pub span: Span,
+}
+
+impl Instruction {
+    /// Whether executing the instruction can stop the program with an error.
+    pub fn may_fail(&self, function: &Function) -> bool {
+        match &self.kind {
+            Kind::Binary(op, left, _) => {
+                function.type_of(left) == DataType::Int
+                    && matches!(
+                        op,
+                        BinaryOp::Add
+                            | BinaryOp::Sub
+                            | BinaryOp::Mul
+                            | BinaryOp::Div
+                            | BinaryOp::Mod
+                    )
+            }
+            Kind::Unary(op, operand) => {
+                *op == UnaryOp::Neg && function.type_of(operand) == DataType::Int
+            }
+            Kind::Index(..) | Kind::Call(..) | Kind::Read => true,
+            Kind::Print(_) => false,
+        }
+    }
+}
+
+/// Where control goes at the end of a block.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Terminator {
+    Jump(BlockId),
+    Branch(Operand, BlockId, BlockId),
+    Return,
+}
+
+impl Terminator {
+    pub fn successors(&self) -> Vec<BlockId> {
+        match self {
+            Terminator::Jump(target) => vec![*target],
+            Terminator::Branch(_, then, otherwise) => vec![*then, *otherwise],
+            Terminator::Return => vec![],
+        }
+    }
+}
+
+/// Chooses a value by the block control came from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Phi {
+    pub result: ValueId,
+    pub incoming: Vec<(BlockId, Operand)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub phis: Vec<Phi>,
+    pub instructions: Vec<Instruction>,
+    pub terminator: Terminator,
+}
+
+/// A program in SSA form. Block 0 is the entry, and `values` holds the type of each
+/// value by its number.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub blocks: Vec<Block>,
+    pub values: Vec<DataType>,
+}
+
+impl Function {
+    pub fn type_of(&self, operand: &Operand) -> DataType {
+        match operand {
+            Operand::Value(value) => self.values[value.0],
+            Operand::Int(_) => DataType::Int,
+            Operand::Real(_) => DataType::Real,
+            Operand::Bool(_) => DataType::Bool,
+            Operand::Char(_) => DataType::Char,
+            Operand::Str(_) => DataType::Str,
+        }
+    }
+
+    pub fn predecessors(&self) -> Vec<Vec<BlockId>> {
+        let mut predecessors = vec![Vec::new(); self.blocks.len()];
+        for (i, block) in self.blocks.iter().enumerate() {
+            for successor in block.terminator.successors() {
+                if let Some(list) = predecessors.get_mut(successor.0) {
+                    list.push(BlockId(i));
+                }
+            }
+        }
+        predecessors
+    }
+}
+
+impl Display for Function {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        for (i, block) in self.blocks.iter().enumerate() {
+            writeln!(f, "{}:", BlockId(i))?;
+            for phi in &block.phis {
+                let incoming: Vec<String> = phi
+                    .incoming
+                    .iter()
+                    .map(|(block, operand)| format!("[{}: {}]", block, operand))
+                    .collect();
+                let type_ = self.values[phi.result.0];
+                writeln!(
+                    f,
+                    "    {}: {} = phi {}",
+                    phi.result,
+                    type_,
+                    incoming.join(", ")
+                )?;
+            }
+            for instruction in &block.instructions {
+                write!(f, "    ")?;
+                if let Some(result) = instruction.result {
+                    write!(f, "{}: {} = ", result, self.values[result.0])?;
+                }
+                match &instruction.kind {
+                    Kind::Binary(op, left, right) => {
+                        write!(f, "{} {}, {}", op.name(), left, right)?
+                    }
+                    Kind::Unary(op, operand) => write!(f, "{} {}", op.name(), operand)?,
+                    Kind::Index(string, index) => write!(f, "index {}, {}", string, index)?,
+                    Kind::Call(name, args) => {
+                        let args: Vec<String> = args.iter().map(Operand::to_string).collect();
+                        write!(f, "call {}({})", name, args.join(", "))?;
+                    }
+                    Kind::Read => write!(f, "read")?,
+                    Kind::Print(operand) => write!(f, "print {}", operand)?,
+                }
+                if instruction.may_fail(self) {
+                    write!(f, " ; {}", instruction.span)?;
+                }
+                writeln!(f)?;
+            }
+            match &block.terminator {
+                Terminator::Jump(target) => writeln!(f, "    jump {}", target)?,
+                Terminator::Branch(condition, then, otherwise) => {
+                    writeln!(f, "    branch {}, {}, {}", condition, then, otherwise)?
+                }
+                Terminator::Return => writeln!(f, "    return")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Lowers a checked program to SSA form. Variables become values: an assignment
+/// names the value of its expression, and where paths meet a phi chooses between the
+/// values each path left. Integers are 64 bits, so a literal that does not fit is
+/// rejected here rather than when it runs.
+pub fn lower(program: &Node) -> Result<Function, Error> {
+    let mut lowering = Lowering {
+        types: Types::new(program, false),
+        function: Function {
+            blocks: Vec::new(),
+            values: Vec::new(),
+        },
+        current: BlockId(0),
+        variables: HashMap::new(),
+    };
+    lowering.new_block();
+    if let Node::Program(program) = program {
+        lowering.statements(&program.children)?;
+    }
+    Ok(in_reverse_postorder(lowering.function))
+}
+
+// numbers the blocks in the order a reader follows them: each block after its
+// predecessors, except along loop back edges
+fn in_reverse_postorder(mut function: Function) -> Function {
+    let mut order = Vec::new();
+    let mut visited = vec![false; function.blocks.len()];
+    // blocks with the successors still to visit
+    let mut stack = vec![(BlockId(0), function.blocks[0].terminator.successors())];
+    visited[0] = true;
+    while let Some((block, successors)) = stack.last_mut() {
+        match successors.pop() {
+            Some(next) if !visited[next.0] => {
+                visited[next.0] = true;
+                let successors = function.blocks[next.0].terminator.successors();
+                stack.push((next, successors));
+            }
+            Some(_) => {}
+            None => {
+                order.push(*block);
+                stack.pop();
+            }
+        }
+    }
+    order.reverse();
+    let mut number = vec![BlockId(0); function.blocks.len()];
+    for (i, block) in order.iter().enumerate() {
+        number[block.0] = BlockId(i);
+    }
+    let mut blocks: Vec<Option<Block>> = function.blocks.drain(..).map(Some).collect();
+    for block in order {
+        let mut block = blocks[block.0].take().unwrap();
+        for phi in &mut block.phis {
+            for (from, _) in &mut phi.incoming {
+                *from = number[from.0];
+            }
+        }
+        block.terminator = match block.terminator {
+            Terminator::Jump(target) => Terminator::Jump(number[target.0]),
+            Terminator::Branch(condition, then, otherwise) => {
+                Terminator::Branch(condition, number[then.0], number[otherwise.0])
+            }
+            Terminator::Return => Terminator::Return,
+        };
+        function.blocks.push(block);
+    }
+    function
+}
+
+struct Lowering {
+    types: Types,
+    function: Function,
+    // the block instructions are added to
+    current: BlockId,
+    // the value each variable holds at this point
+    variables: HashMap<String, Operand>,
+}
+
+impl Lowering {
+    fn statements(&mut self, statements: &[Node]) -> Result<(), Error> {
+        for statement in statements {
+            self.statement(statement)?;
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self, statement: &Node) -> Result<(), Error> {
+        let span = statement.span().unwrap_or_default();
+        match statement {
+            Node::VarDecl(n) => {
+                let name = dataflow::name(&n.var_node);
+                let value = default(n.type_node.data_type());
+                self.variables.insert(name, value);
+            }
+            Node::DeclAssign(n) => {
+                let name = dataflow::name(&n.left);
+                let value = self.expr(&n.right)?;
+                let value = self.convert(value, n.type_node.data_type(), span);
+                self.variables.insert(name, value);
+            }
+            Node::Assign(n) => {
+                let name = dataflow::name(&n.left);
+                let value = self.expr(&n.right)?;
+                let value = self.convert(value, self.types.variable(&name), span);
+                self.variables.insert(name, value);
+            }
+            Node::Read(n) => {
+                let name = dataflow::name(&n.var_node);
+                let type_ = self.types.variable(&name);
+                let value = self.emit(Some(type_), Kind::Read, n.var_node.token.span);
+                self.variables.insert(name, value);
+            }
+            Node::PrintVar(n) => {
+                let value = self.variable(&dataflow::name(&n.var_node));
+                self.emit(None, Kind::Print(value), span);
+            }
+            Node::PrintStr(n) => {
+                let text = Operand::Str(n.value.to_string());
+                self.emit(None, Kind::Print(text), span);
+            }
+            Node::IfStatement(n) => self.if_statement(n)?,
+            Node::ForLoop(n) => self.for_loop(n)?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn if_statement(&mut self, if_statement: &IfStatement) -> Result<(), Error> {
+        let condition = self.expr(&if_statement.bool_expr)?;
+        let before = self.variables.clone();
+        let start = self.current;
+        let then = self.new_block();
+        let join = self.new_block();
+        // without an `else` a false condition goes straight to the join
+        let no_else = if_statement
+            .else_statements
+            .iter()
+            .all(|s| matches!(s, Node::NoOp));
+        let otherwise = match no_else {
+            true => join,
+            false => self.new_block(),
+        };
+        self.terminate(Terminator::Branch(condition, then, otherwise));
+
+        self.current = then;
+        self.statements(&if_statement.statements)?;
+        self.terminate(Terminator::Jump(join));
+        let then_end = (self.current, self.variables.clone());
+
+        self.variables = before;
+        let else_end = match otherwise == join {
+            true => (start, self.variables.clone()),
+            false => {
+                self.current = otherwise;
+                self.statements(&if_statement.else_statements)?;
+                self.terminate(Terminator::Jump(join));
+                (self.current, self.variables.clone())
+            }
+        };
+
+        self.current = join;
+        self.merge(&[then_end, else_end]);
+        Ok(())
+    }
+
+    // the control variable takes start..=end, and the loop is left after the iteration
+    // where it equals end, so it never steps past end
+    fn for_loop(&mut self, for_loop: &ForLoop) -> Result<(), Error> {
+        let span = for_loop.var_node.token.span;
+        let start = self.expr(&for_loop.start)?;
+        let end = self.expr(&for_loop.end)?;
+        let control = dataflow::name(&for_loop.var_node);
+        let mut changed = defined(&for_loop.statements);
+        changed.insert(control.clone());
+
+        let before = (self.current, self.variables.clone());
+        let header = self.new_block();
+        let step = self.new_block();
+        let exit = self.new_block();
+        let runs = self.binary(BinaryOp::Le, start.clone(), end.clone(), span);
+        self.terminate(Terminator::Branch(runs, header, exit));
+
+        // the values at the top of an iteration, completed once the body is lowered
+        self.current = header;
+        let mut phis = Vec::new();
+        for name in &changed {
+            let entering = match name == &control {
+                true => start.clone(),
+                false => self.variable(name),
+            };
+            let type_ = self.types.variable(name);
+            let value = self.new_value(type_);
+            self.function.blocks[header.0].phis.push(Phi {
+                result: value,
+                incoming: vec![(before.0, entering)],
+            });
+            phis.push((name.clone(), value));
+            self.variables.insert(name.clone(), Operand::Value(value));
+        }
+        self.statements(&for_loop.statements)?;
+        let counter = Operand::Value(phis.iter().find(|(name, _)| name == &control).unwrap().1);
+        let more = self.binary(BinaryOp::Lt, counter.clone(), end, span);
+        self.terminate(Terminator::Branch(more, step, exit));
+        let body_end = (self.current, self.variables.clone());
+
+        self.current = step;
+        let next = self.binary(BinaryOp::Add, counter, Operand::Int(1), span);
+        self.variables.insert(control.clone(), next);
+        self.terminate(Terminator::Jump(header));
+        for (i, (name, _)) in phis.iter().enumerate() {
+            let value = self.variable(name);
+            self.function.blocks[header.0].phis[i]
+                .incoming
+                .push((step, value));
+        }
+
+        self.current = exit;
+        self.merge(&[before, body_end]);
+        Ok(())
+    }
+
+    // phis for the variables whose values differ between the paths into the current block
+    fn merge(&mut self, paths: &[(BlockId, HashMap<String, Operand>)]) {
+        let mut names: BTreeSet<&String> = BTreeSet::new();
+        for (_, variables) in paths {
+            names.extend(variables.keys());
+        }
+        let mut merged = HashMap::new();
+        for name in names {
+            let type_ = self.types.variable(name);
+            let incoming: Vec<(BlockId, Operand)> = paths
+                .iter()
+                .map(|(block, variables)| {
+                    let value = variables.get(name).cloned();
+                    (*block, value.unwrap_or_else(|| default(type_)))
+                })
+                .collect();
+            if incoming.iter().all(|(_, value)| *value == incoming[0].1) {
+                merged.insert(name.clone(), incoming[0].1.clone());
+                continue;
+            }
+            let result = self.new_value(type_);
+            let current = self.current.0;
+            self.function.blocks[current]
+                .phis
+                .push(Phi { result, incoming });
+            merged.insert(name.clone(), Operand::Value(result));
+        }
+        self.variables = merged;
+    }
+
+    fn expr(&mut self, node: &Node) -> Result<Operand, Error> {
+        let span = node.span().unwrap_or_default();
+        let operand = match node {
+            Node::Num(n) => match &n.value {
+                Value::Number(n) => Operand::Int(*n),
+                value => {
+                    let message = format!("integer literal {} does not fit in 64 bits", value);
+                    return Err(Error::Semantic(SemanticError::new(message, span)));
+                }
+            },
+            Node::Real(n) => match n.value {
+                Value::Real(r) => Operand::Real(r),
+                _ => unreachable!(),
+            },
+            Node::Str(n) => Operand::Str(n.value.to_string()),
+            Node::Char(n) => match n.value {
+                Value::Char(c) => Operand::Char(c),
+                _ => unreachable!(),
+            },
+            Node::Var(var) => self.variable(&dataflow::name(var)),
+            Node::BinOp(n) => {
+                let left = self.expr(&n.left)?;
+                let right = self.expr(&n.right)?;
+                let op = match n.op.type_ {
+                    TokenType::Plus => BinaryOp::Add,
+                    TokenType::Minus => BinaryOp::Sub,
+                    TokenType::Mul => BinaryOp::Mul,
+                    TokenType::Div => BinaryOp::Div,
+                    TokenType::Mod => BinaryOp::Mod,
+                    _ => unreachable!(),
+                };
+                let types = (self.function.type_of(&left), self.function.type_of(&right));
+                match types {
+                    (DataType::Str, _) | (DataType::Char, _) => {
+                        self.binary(BinaryOp::Concat, left, right, n.op.span)
+                    }
+                    (DataType::Real, _) | (_, DataType::Real) => {
+                        let left = self.convert(left, DataType::Real, span);
+                        let right = self.convert(right, DataType::Real, span);
+                        self.binary(op, left, right, n.op.span)
+                    }
+                    _ => self.binary(op, left, right, n.op.span),
+                }
+            }
+            Node::UnaryOp(n) => {
+                let operand = self.expr(&n.expr)?;
+                match n.op.type_ {
+                    TokenType::Minus => {
+                        let type_ = self.function.type_of(&operand);
+                        self.emit(Some(type_), Kind::Unary(UnaryOp::Neg, operand), n.op.span)
+                    }
+                    _ => operand,
+                }
+            }
+            Node::BoolExpr(n) => match n.op.type_ {
+                TokenType::And | TokenType::Or => self.short_circuit(n.op.type_.clone(), n)?,
+                TokenType::Not => {
+                    let operand = self.expr(&n.right)?;
+                    self.emit(
+                        Some(DataType::Bool),
+                        Kind::Unary(UnaryOp::Not, operand),
+                        span,
+                    )
+                }
+                _ => {
+                    let left = self.expr(&n.left)?;
+                    let right = self.expr(&n.right)?;
+                    let op = match n.op.type_ {
+                        TokenType::Equal => BinaryOp::Eq,
+                        TokenType::NotEqual => BinaryOp::Ne,
+                        TokenType::LessThan => BinaryOp::Lt,
+                        TokenType::LessEqual => BinaryOp::Le,
+                        TokenType::GreaterThan => BinaryOp::Gt,
+                        TokenType::GreaterEqual => BinaryOp::Ge,
+                        _ => unreachable!(),
+                    };
+                    let types = (self.function.type_of(&left), self.function.type_of(&right));
+                    let (left, right) = match types {
+                        (DataType::Real, DataType::Int) | (DataType::Int, DataType::Real) => (
+                            self.convert(left, DataType::Real, span),
+                            self.convert(right, DataType::Real, span),
+                        ),
+                        _ => (left, right),
+                    };
+                    self.binary(op, left, right, n.op.span)
+                }
+            },
+            Node::Index(n) => {
+                let target = self.expr(&n.target)?;
+                let index = self.expr(&n.index)?;
+                self.emit(
+                    Some(DataType::Char),
+                    Kind::Index(target, index),
+                    n.token.span,
+                )
+            }
+            Node::Call(n) => {
+                let name = n.value.to_string().to_lowercase();
+                let builtin = &BUILTINS[name.as_str()];
+                let mut args = Vec::with_capacity(n.args.len());
+                for (arg, param) in n.args.iter().zip(builtin.params) {
+                    let value = self.expr(arg)?;
+                    args.push(self.convert(value, *param, span));
+                }
+                self.emit(Some(builtin.returns), Kind::Call(name, args), n.token.span)
+            }
+            _ => unreachable!(),
+        };
+        Ok(operand)
+    }
+
+    // `a & b` runs `b` only when `a` is true, `a | b` only when it is false
+    fn short_circuit(
+        &mut self,
+        op: TokenType,
+        n: &crate::nodes::BoolExpr,
+    ) -> Result<Operand, Error> {
+        let left = self.expr(&n.left)?;
+        let left_end = self.current;
+        let right_block = self.new_block();
+        let join = self.new_block();
+        let decided = op == TokenType::Or;
+        let terminator = match decided {
+            true => Terminator::Branch(left, join, right_block),
+            false => Terminator::Branch(left, right_block, join),
+        };
+        self.terminate(terminator);
+        self.current = right_block;
+        let right = self.expr(&n.right)?;
+        let right_end = self.current;
+        self.terminate(Terminator::Jump(join));
+        self.current = join;
+        let result = self.new_value(DataType::Bool);
+        self.function.blocks[join.0].phis.push(Phi {
+            result,
+            incoming: vec![(left_end, Operand::Bool(decided)), (right_end, right)],
+        });
+        Ok(Operand::Value(result))
+    }
+
+    fn binary(&mut self, op: BinaryOp, left: Operand, right: Operand, span: Span) -> Operand {
+        let type_ = match op {
+            _ if op.is_comparison() => DataType::Bool,
+            BinaryOp::Concat => DataType::Str,
+            _ => self.function.type_of(&left),
+        };
+        self.emit(Some(type_), Kind::Binary(op, left, right), span)
+    }
+
+    // ints become reals where reals are expected, constants without an instruction
+    fn convert(&mut self, operand: Operand, to: DataType, span: Span) -> Operand {
+        match (to, operand) {
+            (DataType::Real, Operand::Int(n)) => Operand::Real(n as f64),
+            (DataType::Real, operand) if self.function.type_of(&operand) == DataType::Int => {
+                let kind = Kind::Unary(UnaryOp::IntToReal, operand);
+                self.emit(Some(DataType::Real), kind, span)
+            }
+            (_, operand) => operand,
+        }
+    }
+
+    // a variable declared on a path not taken still reads as its default
+    fn variable(&self, name: &str) -> Operand {
+        match self.variables.get(name) {
+            Some(value) => value.clone(),
+            None => default(self.types.variable(name)),
+        }
+    }
+
+    // the instruction's result, or a placeholder for instructions without one
+    fn emit(&mut self, type_: Option<DataType>, kind: Kind, span: Span) -> Operand {
+        let result = type_.map(|type_| self.new_value(type_));
+        let current = self.current.0;
+        self.function.blocks[current]
+            .instructions
+            .push(Instruction { result, kind, span });
+        match result {
+            Some(result) => Operand::Value(result),
+            None => Operand::Bool(false),
+        }
+    }
+
+    fn new_value(&mut self, type_: DataType) -> ValueId {
+        self.function.values.push(type_);
+        ValueId(self.function.values.len() - 1)
+    }
+
+    fn new_block(&mut self) -> BlockId {
+        self.function.blocks.push(Block {
+            phis: Vec::new(),
+            instructions: Vec::new(),
+            terminator: Terminator::Return,
+        });
+        BlockId(self.function.blocks.len() - 1)
+    }
+
+    fn terminate(&mut self, terminator: Terminator) {
+        self.function.blocks[self.current.0].terminator = terminator;
+    }
+}
+
+// what a declaration without a value starts with, as in the interpreter
+fn default(type_: DataType) -> Operand {
+    match type_ {
+        DataType::Int => Operand::Int(0),
+        DataType::Real => Operand::Real(0.0),
+        DataType::Bool => Operand::Bool(true),
+        DataType::Char => Operand::Char('\0'),
+        DataType::Str => Operand::Str(String::new()),
+    }
+}
+
+/// An inconsistency in a `Function`, which lowering or a pass got wrong.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifyError {
+    pub block: BlockId,
+    pub message: String,
+}
+
+impl Display for VerifyError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "IR error in {}: {}", self.block, self.message)
+    }
+}
+
+/// Checks that `function` is well formed:
+///
+/// - branch targets exist and branch conditions are bools
+/// - every value is defined once, with the type `values` gives it
+/// - operands have the types their instruction expects
+/// - each phi has exactly one incoming value per predecessor of its block
+/// - every use is dominated by its definition; for a phi, the end of the
+///   predecessor it comes from is
+pub fn verify(function: &Function) -> Result<(), VerifyError> {
+    let verifier = Verifier::new(function)?;
+    for (i, block) in function.blocks.iter().enumerate() {
+        verifier.block(BlockId(i), block)?;
+    }
+    Ok(())
+}
+
+struct Verifier<'a> {
+    function: &'a Function,
+    predecessors: Vec<Vec<BlockId>>,
+    // where each value is defined: its block and position, phis before instructions
+    definitions: HashMap<ValueId, (BlockId, usize)>,
+    dominators: Vec<BTreeSet<BlockId>>,
+}
+
+impl<'a> Verifier<'a> {
+    fn new(function: &'a Function) -> Result<Self, VerifyError> {
+        if function.blocks.is_empty() {
+            return Err(error(BlockId(0), String::from("there is no entry block")));
+        }
+        let mut definitions = HashMap::new();
+        for (i, block) in function.blocks.iter().enumerate() {
+            let id = BlockId(i);
+            for target in block.terminator.successors() {
+                if target.0 >= function.blocks.len() {
+                    return Err(error(id, format!("jumps to missing block {}", target)));
+                }
+            }
+            let results = block
+                .phis
+                .iter()
+                .map(|phi| Some(phi.result))
+                .chain(block.instructions.iter().map(|i| i.result));
+            for (position, result) in results.enumerate() {
+                let result = match result {
+                    Some(result) => result,
+                    None => continue,
+                };
+                if result.0 >= function.values.len() {
+                    return Err(error(id, format!("{} has no type", result)));
+                }
+                if definitions.insert(result, (id, position)).is_some() {
+                    return Err(error(id, format!("{} is defined more than once", result)));
+                }
+            }
+        }
+        Ok(Verifier {
+            function,
+            predecessors: function.predecessors(),
+            definitions,
+            dominators: dominators(function),
+        })
+    }
+
+    fn block(&self, id: BlockId, block: &Block) -> Result<(), VerifyError> {
+        let mut predecessors = self.predecessors[id.0].clone();
+        predecessors.sort();
+        for phi in &block.phis {
+            let mut from: Vec<BlockId> = phi.incoming.iter().map(|(block, _)| *block).collect();
+            from.sort();
+            if from != predecessors {
+                let message = format!(
+                    "{} has incoming values from {} but the block's predecessors are {}",
+                    phi.result,
+                    list(&from),
+                    list(&predecessors)
+                );
+                return Err(error(id, message));
+            }
+            let type_ = self.function.values[phi.result.0];
+            for (predecessor, operand) in &phi.incoming {
+                self.expect_type(id, operand, type_, &phi.result.to_string())?;
+                // available at the end of the predecessor
+                let position = self.length(*predecessor);
+                self.dominated(id, operand, *predecessor, position)?;
+            }
+        }
+        for (i, instruction) in block.instructions.iter().enumerate() {
+            let position = block.phis.len() + i;
+            self.instruction(id, instruction, position)?;
+        }
+        if let Terminator::Branch(condition, ..) = &block.terminator {
+            self.expect_type(id, condition, DataType::Bool, "branch")?;
+            self.dominated(id, condition, id, self.length(id))?;
+        }
+        Ok(())
+    }
+
+    fn instruction(
+        &self,
+        id: BlockId,
+        instruction: &Instruction,
+        position: usize,
+    ) -> Result<(), VerifyError> {
+        let what = match instruction.result {
+            Some(result) => result.to_string(),
+            None => String::from("print"),
+        };
+        let operands = match &instruction.kind {
+            Kind::Binary(_, left, right) | Kind::Index(left, right) => vec![left, right],
+            Kind::Unary(_, operand) | Kind::Print(operand) => vec![operand],
+            Kind::Call(_, args) => args.iter().collect(),
+            Kind::Read => vec![],
+        };
+        for operand in &operands {
+            self.dominated(id, operand, id, position)?;
+        }
+        let types: Vec<DataType> = operands.iter().map(|o| self.function.type_of(o)).collect();
+        let result = instruction
+            .result
+            .map(|result| self.function.values[result.0]);
+        let expected = match &instruction.kind {
+            Kind::Binary(op, ..) if op.is_comparison() => same(&types).then_some(DataType::Bool),
+            Kind::Binary(BinaryOp::Concat, ..) => types
+                .iter()
+                .all(|t| matches!(t, DataType::Str | DataType::Char))
+                .then_some(DataType::Str),
+            Kind::Binary(BinaryOp::Mod, ..) => {
+                (same(&types) && types[0] == DataType::Int).then_some(DataType::Int)
+            }
+            Kind::Binary(..) => (same(&types) && types[0].is_numeric()).then_some(types[0]),
+            Kind::Unary(UnaryOp::Neg, ..) => types[0].is_numeric().then_some(types[0]),
+            Kind::Unary(UnaryOp::Not, ..) => (types[0] == DataType::Bool).then_some(DataType::Bool),
+            Kind::Unary(UnaryOp::IntToReal, ..) => {
+                (types[0] == DataType::Int).then_some(DataType::Real)
+            }
+            Kind::Index(..) => (types == [DataType::Str, DataType::Int]).then_some(DataType::Char),
+            Kind::Call(name, _) => match BUILTINS.get(name.as_str()) {
+                Some(builtin) => (types == builtin.params).then_some(builtin.returns),
+                None => return Err(error(id, format!("{} calls unknown {}", what, name))),
+            },
+            Kind::Read => {
+                result.filter(|t| matches!(t, DataType::Int | DataType::Real | DataType::Str))
+            }
+            Kind::Print(_) => None,
+        };
+        let is_print = matches!(instruction.kind, Kind::Print(_));
+        if (is_print && result.is_none()) || (expected.is_some() && expected == result) {
+            return Ok(());
+        }
+        let types: Vec<String> = types.iter().map(DataType::to_string).collect();
+        let result = result.map_or(String::from("nothing"), |t| t.to_string());
+        let message = format!(
+            "{} has operands of type ({}) and result {}, which do not fit its instruction",
+            what,
+            types.join(", "),
+            result
+        );
+        Err(error(id, message))
+    }
+
+    fn expect_type(
+        &self,
+        id: BlockId,
+        operand: &Operand,
+        expected: DataType,
+        what: &str,
+    ) -> Result<(), VerifyError> {
+        let found = self.function.type_of(operand);
+        match found == expected {
+            true => Ok(()),
+            false => Err(error(
+                id,
+                format!("{} expects {} but {} is {}", what, expected, operand, found),
+            )),
+        }
+    }
+
+    // `operand` is available at `position` in `block`
+    fn dominated(
+        &self,
+        id: BlockId,
+        operand: &Operand,
+        block: BlockId,
+        position: usize,
+    ) -> Result<(), VerifyError> {
+        let value = match operand {
+            Operand::Value(value) => *value,
+            _ => return Ok(()),
+        };
+        let available = match self.definitions.get(&value) {
+            None => return Err(error(id, format!("{} is used but never defined", value))),
+            Some((defined_in, at)) if *defined_in == block => *at < position,
+            Some((defined_in, _)) => self.dominators[block.0].contains(defined_in),
+        };
+        // unreachable blocks have no dominators worth checking
+        if available || self.dominators[block.0].is_empty() {
+            return Ok(());
+        }
+        Err(error(
+            id,
+            format!("{} is used where its definition does not dominate", value),
+        ))
+    }
+
+    // the position just past a block's last instruction
+    fn length(&self, block: BlockId) -> usize {
+        let block = &self.function.blocks[block.0];
+        block.phis.len() + block.instructions.len()
+    }
+}
+
+// the blocks that dominate each block, including itself; empty for unreachable ones
+fn dominators(function: &Function) -> Vec<BTreeSet<BlockId>> {
+    let n = function.blocks.len();
+    let predecessors = function.predecessors();
+    let mut reachable = vec![false; n];
+    let mut stack = vec![BlockId(0)];
+    while let Some(block) = stack.pop() {
+        if !reachable[block.0] {
+            reachable[block.0] = true;
+            stack.extend(function.blocks[block.0].terminator.successors());
+        }
+    }
+    let all: BTreeSet<BlockId> = (0..n).filter(|&i| reachable[i]).map(BlockId).collect();
+    let mut dominators: Vec<BTreeSet<BlockId>> = (0..n)
+        .map(|i| match (i, reachable[i]) {
+            (0, _) => [BlockId(0)].iter().copied().collect(),
+            (_, true) => all.clone(),
+            (_, false) => BTreeSet::new(),
+        })
+        .collect();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for i in (1..n).filter(|&i| reachable[i]) {
+            let mut new: Option<BTreeSet<BlockId>> = None;
+            for predecessor in predecessors[i].iter().filter(|p| reachable[p.0]) {
+                let set = &dominators[predecessor.0];
+                new = Some(match new {
+                    None => set.clone(),
+                    Some(new) => new.intersection(set).copied().collect(),
+                });
+            }
+            let mut new = new.unwrap_or_default();
+            new.insert(BlockId(i));
+            if new != dominators[i] {
+                dominators[i] = new;
+                changed = true;
+            }
+        }
+    }
+    dominators
+}
+
+fn same(types: &[DataType]) -> bool {
+    types.windows(2).all(|pair| pair[0] == pair[1])
+}
+
+fn list(blocks: &[BlockId]) -> String {
+    let blocks: Vec<String> = blocks.iter().map(BlockId::to_string).collect();
+    format!("[{}]", blocks.join(", "))
+}
+
+fn error(block: BlockId, message: String) -> VerifyError {
+    VerifyError { block, message }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::Generator;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    fn lower_text(text: &str) -> Function {
+        let tree = Parser::new(Scanner::new(text.to_string())).parse().unwrap();
+        lower(&tree).unwrap()
+    }
+
+    #[test]
+    fn lowers_to_ssa() {
+        let function = lower_text(
+            "var n : int := 0;
+var i : int;
+var r : real;
+for i in 1..3 do
+    if i < 2 & n = 0 do
+        n := n + i;
+    end if;
+end for;
+r := n;
+print r;",
+        );
+        verify(&function).unwrap();
+        let expected = "b0:
+    %0: bool = le 1, 3
+    branch %0, b1, b7
+b1:
+    %1: int = phi [b0: 1], [b6: %9]
+    %2: int = phi [b0: 0], [b6: %7]
+    %3: bool = lt %1, 2
+    branch %3, b2, b3
+b2:
+    %4: bool = eq %2, 0
+    jump b3
+b3:
+    %5: bool = phi [b1: false], [b2: %4]
+    branch %5, b4, b5
+b4:
+    %6: int = add %2, %1 ; 6:16
+    jump b5
+b5:
+    %7: int = phi [b4: %6], [b3: %2]
+    %8: bool = lt %1, 3
+    branch %8, b6, b7
+b6:
+    %9: int = add %1, 1 ; 4:5
+    jump b1
+b7:
+    %10: int = phi [b0: 0], [b5: %1]
+    %11: int = phi [b0: 0], [b5: %7]
+    %12: real = itor %11
+    print %12
+    return
+";
+        assert_eq!(function.to_string(), expected);
+    }
+
+    #[test]
+    fn rejects_a_use_before_its_definition() {
+        let mut function = lower_text("var n : int; read n; print n;");
+        function.blocks[0].instructions.swap(0, 1);
+        let error = verify(&function).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "IR error in b0: %0 is used where its definition does not dominate"
+        );
+    }
+
+    #[test]
+    fn generated_programs_verify() {
+        for seed in 0..200 {
+            let text = Generator::new(seed).program().to_string();
+            let tree = Parser::new(Scanner::new(text.clone())).parse().unwrap();
+            let function = match lower(&tree) {
+                Ok(function) => function,
+                Err(_) => continue,
+            };
+            if let Err(e) = verify(&function) {
+                panic!("{}\n{}\n{}", e, text, function);
+            }
+        }
+    }
+}
@@ -0,0 +1,27 @@
+//! Interpreter and tooling for MiniPL, a small educational language loosely based on Pascal.
+
+pub mod bigint;
+pub mod builtins;
+pub mod checker;
+pub mod dap;
+pub mod dataflow;
+pub mod debugger;
+pub mod error;
+pub mod fold;
+pub mod formatter;
+pub mod generator;
+pub mod golden;
+pub mod interpreter;
+pub mod ir;
+pub mod json;
+pub mod linter;
+pub mod llvm;
+pub mod loops;
+pub mod lsp;
+pub mod nodes;
+pub mod optimizer;
+pub mod parser;
+pub mod profiler;
+pub mod scanner;
+pub mod tokens;
+pub mod trace;
@@ -0,0 +1,459 @@
+use crate::interpreter::integer_op;
+use crate::json::Json;
+use crate::nodes::{ForLoop, IfStatement, Node, Var};
+use crate::tokens::{Span, TokenType, Value};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Display, Formatter};
+
+/// Rule ids with a short description, all enabled by default.
+pub const RULES: &[(&str, &str)] = &[
+    ("unused-variable", "variable is declared but never read"),
+    (
+        "uninitialized-read",
+        "variable may be read before any value is assigned to it",
+    ),
+    (
+        "unreachable-branch",
+        "if condition is constant, so one branch never runs",
+    ),
+    ("empty-loop", "for loop has an empty body"),
+    (
+        "case-mismatch",
+        "identifier is spelled with different case than its declaration",
+    ),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    pub rule: &'static str,
+    pub message: String,
+    pub span: Span,
+}
+
+impl Warning {
+    pub fn to_json(&self) -> Json {
+        Json::object(vec![
+            ("rule", self.rule.into()),
+            ("message", self.message.clone().into()),
+            ("line", self.span.line.into()),
+            ("column", self.span.column.into()),
+            ("start", self.span.start.into()),
+            ("end", self.span.end.into()),
+        ])
+    }
+}
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}: warning[{}]: {}", self.span, self.rule, self.message)
+    }
+}
+
+/// Style and correctness warnings for programs that already pass the checker.
+pub struct Linter {
+    enabled: HashSet<&'static str>,
+}
+
+impl Default for Linter {
+    fn default() -> Self {
+        Linter::new()
+    }
+}
+
+impl Linter {
+    pub fn new() -> Self {
+        Linter {
+            enabled: RULES.iter().map(|(id, _)| *id).collect(),
+        }
+    }
+
+    /// Turns a rule on, `all` turns on every rule.
+    pub fn enable(&mut self, rule: &str) -> Result<(), String> {
+        for id in rule_ids(rule)? {
+            self.enabled.insert(id);
+        }
+        Ok(())
+    }
+
+    /// Turns a rule off, `all` turns off every rule.
+    pub fn disable(&mut self, rule: &str) -> Result<(), String> {
+        for id in rule_ids(rule)? {
+            self.enabled.remove(id);
+        }
+        Ok(())
+    }
+
+    /// Warnings of the enabled rules in source order.
+    pub fn lint(&self, tree: &Node) -> Vec<Warning> {
+        let mut walk = Walk {
+            variables: HashMap::new(),
+            assigned: HashSet::new(),
+            uninitialized_reported: HashSet::new(),
+            warnings: Vec::new(),
+        };
+        if let Node::Program(program) = tree {
+            walk.statements(&program.children);
+        }
+        let mut unused: Vec<&Variable> = walk.variables.values().filter(|v| !v.read).collect();
+        unused.sort_by_key(|v| v.span.start);
+        let unused: Vec<Warning> = unused
+            .into_iter()
+            .map(|v| Warning {
+                rule: "unused-variable",
+                message: format!("variable {} is never read", v.spelling),
+                span: v.span,
+            })
+            .collect();
+        let mut warnings = walk.warnings;
+        warnings.extend(unused);
+        warnings.retain(|w| self.enabled.contains(w.rule));
+        warnings.sort_by_key(|w| w.span.start);
+        warnings
+    }
+}
+
+fn rule_ids(rule: &str) -> Result<Vec<&'static str>, String> {
+    if rule == "all" {
+        return Ok(RULES.iter().map(|(id, _)| *id).collect());
+    }
+    match RULES.iter().find(|(id, _)| *id == rule) {
+        Some((id, _)) => Ok(vec![*id]),
+        None => Err(format!("unknown lint rule {}", rule)),
+    }
+}
+
+struct Variable {
+    spelling: String,
+    span: Span,
+    read: bool,
+}
+
+struct Walk {
+    variables: HashMap<String, Variable>,
+    // variables that certainly hold an assigned value at this point
+    assigned: HashSet<String>,
+    uninitialized_reported: HashSet<String>,
+    warnings: Vec<Warning>,
+}
+
+impl Walk {
+    fn warn(&mut self, rule: &'static str, message: String, span: Span) {
+        self.warnings.push(Warning {
+            rule,
+            message,
+            span,
+        });
+    }
+
+    fn statements(&mut self, statements: &[Node]) {
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    fn statement(&mut self, node: &Node) {
+        match node {
+            Node::VarDecl(n) => self.declare(&n.var_node, false),
+            Node::DeclAssign(n) => {
+                self.expr(&n.right);
+                self.declare(&n.left, true);
+            }
+            Node::Assign(n) => {
+                self.expr(&n.right);
+                self.write(&n.left);
+            }
+            Node::Read(n) => self.write(&n.var_node),
+            Node::PrintVar(n) => self.read(&n.var_node),
+            Node::ForLoop(n) => self.for_loop(n),
+            Node::IfStatement(n) => self.if_statement(n),
+            _ => {}
+        }
+    }
+
+    fn for_loop(&mut self, for_loop: &ForLoop) {
+        self.expr(&for_loop.start);
+        self.expr(&for_loop.end);
+        self.write(&for_loop.var_node);
+        if for_loop.statements.iter().all(|s| s.span().is_none()) {
+            self.warn(
+                "empty-loop",
+                String::from("for loop body is empty"),
+                for_loop.span,
+            );
+        }
+        // the body may not run at all, so its assignments do not count afterwards
+        let before = self.assigned.clone();
+        self.statements(&for_loop.statements);
+        self.assigned = before;
+    }
+
+    fn if_statement(&mut self, if_statement: &IfStatement) {
+        self.expr(&if_statement.bool_expr);
+        let constant = match constant(&if_statement.bool_expr) {
+            Some(Value::Boolean(b)) => Some(b),
+            _ => None,
+        };
+        let has_else = if_statement
+            .else_statements
+            .iter()
+            .any(|s| s.span().is_some());
+        match (constant, if_statement.else_span) {
+            (Some(true), Some(else_span)) if has_else => self.warn(
+                "unreachable-branch",
+                String::from("else branch is unreachable, the condition is always true"),
+                else_span,
+            ),
+            (Some(false), _) => self.warn(
+                "unreachable-branch",
+                String::from("if branch is unreachable, the condition is always false"),
+                if_statement.span,
+            ),
+            _ => {}
+        }
+
+        let before = self.assigned.clone();
+        self.statements(&if_statement.statements);
+        let then_assigned = std::mem::replace(&mut self.assigned, before);
+        self.statements(&if_statement.else_statements);
+        // only a branch that can run decides what is assigned afterwards
+        self.assigned = match constant {
+            Some(true) => then_assigned,
+            Some(false) => self.assigned.clone(),
+            None => self
+                .assigned
+                .intersection(&then_assigned)
+                .cloned()
+                .collect(),
+        };
+    }
+
+    fn expr(&mut self, node: &Node) {
+        match node {
+            Node::Var(n) => self.read(n),
+            Node::BinOp(n) => {
+                self.expr(&n.left);
+                self.expr(&n.right);
+            }
+            Node::BoolExpr(n) => {
+                self.expr(&n.left);
+                self.expr(&n.right);
+            }
+            Node::UnaryOp(n) => self.expr(&n.expr),
+            Node::Index(n) => {
+                self.expr(&n.target);
+                self.expr(&n.index);
+            }
+            Node::Call(n) => {
+                for arg in &n.args {
+                    self.expr(arg);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn declare(&mut self, var: &Var, assigned: bool) {
+        let name = var_name(var);
+        if assigned {
+            self.assigned.insert(name.clone());
+        }
+        self.variables.insert(
+            name,
+            Variable {
+                spelling: var.value.to_string(),
+                span: var.token.span,
+                read: false,
+            },
+        );
+    }
+
+    fn write(&mut self, var: &Var) {
+        self.spelling(var);
+        self.assigned.insert(var_name(var));
+    }
+
+    fn read(&mut self, var: &Var) {
+        self.spelling(var);
+        let name = var_name(var);
+        if let Some(variable) = self.variables.get_mut(&name) {
+            variable.read = true;
+        }
+        if !self.assigned.contains(&name) && self.uninitialized_reported.insert(name) {
+            self.warn(
+                "uninitialized-read",
+                format!("variable {} may be read before it is assigned", var.value),
+                var.token.span,
+            );
+        }
+    }
+
+    fn spelling(&mut self, var: &Var) {
+        let spelling = var.value.to_string();
+        let declared = match self.variables.get(&var_name(var)) {
+            Some(variable) if variable.spelling != spelling => variable.spelling.clone(),
+            _ => return,
+        };
+        self.warn(
+            "case-mismatch",
+            format!(
+                "{} differs only in case from its declaration {}",
+                spelling, declared
+            ),
+            var.token.span,
+        );
+    }
+}
+
+fn var_name(var: &Var) -> String {
+    var.value.to_string().to_lowercase()
+}
+
+// value of an expression made of literals only
+fn constant(node: &Node) -> Option<Value> {
+    match node {
+        Node::Num(n) => match n.value {
+            Value::Number(_) => Some(n.value.clone()),
+            _ => None,
+        },
+        Node::Real(n) => Some(n.value.clone()),
+        Node::Str(n) => Some(n.value.clone()),
+        Node::Char(n) => Some(n.value.clone()),
+        Node::UnaryOp(n) => match (&n.op.type_, constant(&n.expr)?) {
+            (TokenType::Plus, value) => Some(value),
+            (TokenType::Minus, value @ Value::Number(_)) => {
+                integer_op(&n.op, &Value::Number(0), &value, false).ok()
+            }
+            (TokenType::Minus, Value::Real(r)) => Some(Value::Real(-r)),
+            _ => None,
+        },
+        Node::BinOp(n) => match (constant(&n.left)?, constant(&n.right)?) {
+            (left @ Value::Number(_), right @ Value::Number(_)) => {
+                integer_op(&n.op, &left, &right, false).ok()
+            }
+            _ => None,
+        },
+        Node::BoolExpr(n) => {
+            let boolean = |node: &Node| match constant(node) {
+                Some(Value::Boolean(b)) => Some(b),
+                _ => None,
+            };
+            let result = match n.op.type_ {
+                TokenType::Not => !boolean(&n.right)?,
+                TokenType::And => boolean(&n.left)? && boolean(&n.right)?,
+                TokenType::Or => boolean(&n.left)? || boolean(&n.right)?,
+                _ => {
+                    let ordering = compare(&constant(&n.left)?, &constant(&n.right)?)?;
+                    match n.op.type_ {
+                        TokenType::Equal => ordering == Ordering::Equal,
+                        TokenType::NotEqual => ordering != Ordering::Equal,
+                        TokenType::LessThan => ordering == Ordering::Less,
+                        TokenType::LessEqual => ordering != Ordering::Greater,
+                        TokenType::GreaterThan => ordering == Ordering::Greater,
+                        TokenType::GreaterEqual => ordering != Ordering::Less,
+                        _ => return None,
+                    }
+                }
+            };
+            Some(Value::Boolean(result))
+        }
+        _ => None,
+    }
+}
+
+fn compare(left: &Value, right: &Value) -> Option<Ordering> {
+    match (left, right) {
+        (Value::Number(a), Value::Number(b)) => Some(a.cmp(b)),
+        (Value::Number(a), Value::Real(b)) => (*a as f64).partial_cmp(b),
+        (Value::Real(a), Value::Number(b)) => a.partial_cmp(&(*b as f64)),
+        (Value::Real(a), Value::Real(b)) => a.partial_cmp(b),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        (Value::Char(a), Value::Char(b)) => Some(a.cmp(b)),
+        (Value::Boolean(a), Value::Boolean(b)) => Some(a.cmp(b)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    fn lint(linter: &Linter, text: &str) -> Vec<String> {
+        let tree = Parser::new(Scanner::new(text.to_string())).parse().unwrap();
+        linter
+            .lint(&tree)
+            .iter()
+            .map(|w| format!("{} {} {}", w.span, w.rule, w.message))
+            .collect()
+    }
+
+    #[test]
+    fn reports_each_rule() {
+        let text = "var Count : int;
+var unused : string := \"x\";
+var flag : bool := 1 < 2;
+var i : int;
+print count;
+for i in 0..3 do
+end for;
+if 2 > 1 do
+    Count := 1;
+else
+    print flag;
+end if;
+var r : int;
+if flag do r := 1; else r := 2; end if;
+print r;";
+        let linter = Linter::new();
+        assert_eq!(
+            lint(&linter, text),
+            vec![
+                "2:5 unused-variable variable unused is never read",
+                "4:5 unused-variable variable i is never read",
+                "5:7 case-mismatch count differs only in case from its declaration Count",
+                "5:7 uninitialized-read variable count may be read before it is assigned",
+                "6:1 empty-loop for loop body is empty",
+                "10:1 unreachable-branch else branch is unreachable, the condition is always true",
+            ]
+        );
+
+        let mut linter = Linter::new();
+        linter.disable("all").unwrap();
+        linter.enable("empty-loop").unwrap();
+        assert_eq!(
+            lint(&linter, text),
+            vec!["6:1 empty-loop for loop body is empty"]
+        );
+        assert!(linter.disable("no-such-rule").is_err());
+
+        // constant arithmetic is the interpreter's
+        let smallest =
+            "if (-9223372036854775807 - 1) % -1 = 0 do print \"a\"; else print \"b\"; end if;";
+        assert_eq!(
+            lint(&Linter::new(), smallest),
+            vec![
+                "1:54 unreachable-branch else branch is unreachable, the condition is always true"
+            ]
+        );
+    }
+
+    #[test]
+    fn warnings_as_json() {
+        let warning = Warning {
+            rule: "empty-loop",
+            message: String::from("say \"hi\"\n"),
+            span: Span {
+                start: 3,
+                end: 5,
+                line: 1,
+                column: 4,
+            },
+        };
+        assert_eq!(
+            Json::Array(vec![warning.to_json()]).to_string(),
+            r#"[{"rule":"empty-loop","message":"say \"hi\"\n","line":1,"column":4,"start":3,"end":5}]"#
+        );
+    }
+}
@@ -0,0 +1,744 @@
+use crate::checker::{Checker, SemanticError};
+use crate::error::Error;
+use crate::ir::{
+    self, BinaryOp, BlockId, Function, Instruction, Kind, Operand, Terminator, UnaryOp, ValueId,
+};
+use crate::nodes::DataType;
+use crate::parser::Parser;
+use crate::scanner::Scanner;
+use crate::tokens::Span;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt::Write;
+use std::mem;
+
+/// The C runtime compiled programs link with: printing, reading, the builtins and
+/// runtime errors, all as the interpreter does them.
+pub const RUNTIME: &str = include_str!("runtime.c");
+
+/// The LLVM type MiniPL's `int` becomes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Int {
+    /// Overflow is checked at 32 bits, and literals and values from `read` and the
+    /// builtins must fit.
+    I32,
+    I64,
+}
+
+impl Int {
+    fn bits(self) -> u32 {
+        match self {
+            Int::I32 => 32,
+            Int::I64 => 64,
+        }
+    }
+
+    fn fits(self, n: i64) -> bool {
+        self == Int::I64 || i32::try_from(n).is_ok()
+    }
+}
+
+/// Compiles a program to textual LLVM IR, by way of `ir::lower`.
+pub fn compile(source: &str, int: Int) -> Result<String, Error> {
+    let tree = Parser::new(Scanner::new(source.to_string())).parse()?;
+    Checker::new().check(&tree)?;
+    let function = ir::lower(&tree)?;
+    // a malformed function is a bug in lowering, not in the program
+    if let Err(e) = ir::verify(&function) {
+        panic!("{}", e);
+    }
+    emit(&function, int)
+}
+
+/// Translates a verified function to a module whose `main` runs it. Strings are
+/// pointers to the runtime's `mpl.string`, `bool` is `i1` and `char` an `i32` code
+/// point. Checked arithmetic branches to a call into the runtime that reports the
+/// error and exits.
+pub fn emit(function: &Function, int: Int) -> Result<String, Error> {
+    check_literals(function, int)?;
+    let mut emitter = Emitter {
+        function,
+        int,
+        names: HashMap::new(),
+        strings: Vec::new(),
+        string_ids: HashMap::new(),
+        temporaries: 0,
+        text: String::new(),
+        label: String::new(),
+        splits: 0,
+    };
+    for block in &function.blocks {
+        for phi in &block.phis {
+            let name = format!("%v{}", phi.result.0);
+            emitter.names.insert(phi.result, name);
+        }
+    }
+    // blocks are in reverse postorder, so values are named before they are used, but
+    // phis need the labels control leaves each block from
+    let mut bodies = Vec::new();
+    let mut ends = Vec::new();
+    for (i, block) in function.blocks.iter().enumerate() {
+        emitter.label = BlockId(i).to_string();
+        emitter.splits = 0;
+        for instruction in &block.instructions {
+            emitter.instruction(instruction);
+        }
+        emitter.terminator(&block.terminator);
+        bodies.push(mem::take(&mut emitter.text));
+        ends.push(emitter.label.clone());
+    }
+    let mut main = String::new();
+    for (i, block) in function.blocks.iter().enumerate() {
+        writeln!(main, "{}:", BlockId(i)).unwrap();
+        for phi in &block.phis {
+            let type_ = emitter.type_name(function.values[phi.result.0]);
+            let mut incoming = Vec::new();
+            for (from, operand) in &phi.incoming {
+                let value = emitter.operand(operand);
+                incoming.push(format!("[ {}, %{} ]", value, ends[from.0]));
+            }
+            let name = &emitter.names[&phi.result];
+            writeln!(main, "  {} = phi {} {}", name, type_, incoming.join(", ")).unwrap();
+        }
+        main.push_str(&bodies[i]);
+    }
+
+    let mut out = String::from(
+        "; compiled from MiniPL, link with the runtime from `mini-pl compile --runtime`\n",
+    );
+    out.push_str("%mpl.string = type { i64, i8* }\n\n");
+    for (i, string) in emitter.strings.iter().enumerate() {
+        let n = string.len();
+        writeln!(
+            out,
+            "@.bytes.{} = private unnamed_addr constant [{} x i8] c\"{}\"",
+            i,
+            n,
+            escape(string)
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "@.str.{} = private unnamed_addr constant %mpl.string {{ i64 {}, \
+             i8* getelementptr inbounds ([{} x i8], [{} x i8]* @.bytes.{}, i64 0, i64 0) }}",
+            i, n, n, n, i
+        )
+        .unwrap();
+    }
+    if !emitter.strings.is_empty() {
+        out.push('\n');
+    }
+    out.push_str(&declarations(int));
+    write!(out, "\ndefine i32 @main() {{\n{}}}\n", main).unwrap();
+    Ok(out)
+}
+
+struct Emitter<'a> {
+    function: &'a Function,
+    int: Int,
+    // the LLVM value each IR value is
+    names: HashMap<ValueId, String>,
+    // string constants by number, `@.str.N`
+    strings: Vec<String>,
+    string_ids: HashMap<String, usize>,
+    temporaries: usize,
+    // the instructions of the block being emitted
+    text: String,
+    // the LLVM block instructions currently go to: an IR block is split after every
+    // check that can fail
+    label: String,
+    splits: usize,
+}
+
+impl<'a> Emitter<'a> {
+    fn instruction(&mut self, instruction: &Instruction) {
+        let span = instruction.span;
+        let result = instruction.result;
+        let type_ = instruction.result.map(|v| self.function.values[v.0]);
+        match &instruction.kind {
+            Kind::Binary(op, left, right) => {
+                let value = self.binary(*op, left, right, span);
+                self.assign(result, value);
+            }
+            Kind::Unary(op, operand) => {
+                let value = self.unary(*op, operand, span);
+                self.assign(result, value);
+            }
+            Kind::Index(string, index) => {
+                let string = self.operand(string);
+                let index = self.widen(index);
+                let value = self.call(
+                    "i32",
+                    "mpl_index",
+                    &[format!("%mpl.string* {}", string), format!("i64 {}", index)],
+                    Some(span),
+                );
+                self.assign(result, value);
+            }
+            Kind::Call(name, args) => {
+                let builtin = &crate::builtins::BUILTINS[name.as_str()];
+                let mut typed = Vec::new();
+                for (arg, param) in args.iter().zip(builtin.params) {
+                    typed.push(self.argument(arg, *param));
+                }
+                let value = self.call(
+                    runtime_type(builtin.returns),
+                    &format!("mpl_{}", name),
+                    &typed,
+                    Some(span),
+                );
+                let value = match builtin.returns {
+                    DataType::Int => self.narrow(value, span),
+                    _ => value,
+                };
+                self.assign(result, value);
+            }
+            Kind::Read => {
+                let value = match type_ {
+                    Some(DataType::Int) => {
+                        let value = self.call("i64", "mpl_read_int", &[], Some(span));
+                        self.narrow(value, span)
+                    }
+                    Some(DataType::Real) => self.call("double", "mpl_read_real", &[], Some(span)),
+                    _ => self.call("%mpl.string*", "mpl_read_string", &[], None),
+                };
+                self.assign(result, value);
+            }
+            Kind::Print(operand) => {
+                let type_ = self.function.type_of(operand);
+                let (function, argument) = match type_ {
+                    DataType::Int => ("mpl_print_int", format!("i64 {}", self.widen(operand))),
+                    DataType::Real => (
+                        "mpl_print_real",
+                        format!("double {}", self.operand(operand)),
+                    ),
+                    DataType::Bool => {
+                        let value = self.operand(operand);
+                        let t = self.temporary();
+                        self.line(format!("{} = zext i1 {} to i32", t, value));
+                        ("mpl_print_bool", format!("i32 {}", t))
+                    }
+                    DataType::Char => ("mpl_print_char", format!("i32 {}", self.operand(operand))),
+                    DataType::Str => (
+                        "mpl_print_string",
+                        format!("%mpl.string* {}", self.operand(operand)),
+                    ),
+                };
+                self.line(format!("call void @{}({})", function, argument));
+            }
+        }
+    }
+
+    fn binary(&mut self, op: BinaryOp, left: &Operand, right: &Operand, span: Span) -> String {
+        let type_ = self.function.type_of(left);
+        if op == BinaryOp::Concat {
+            let left = self.string(left);
+            let right = self.string(right);
+            let args = [
+                format!("%mpl.string* {}", left),
+                format!("%mpl.string* {}", right),
+            ];
+            return self.call("%mpl.string*", "mpl_concat", &args, None);
+        }
+        if op.is_comparison() {
+            return self.comparison(op, type_, left, right);
+        }
+        let (a, b) = (self.operand(left), self.operand(right));
+        if type_ == DataType::Real {
+            let instruction = match op {
+                BinaryOp::Add => "fadd",
+                BinaryOp::Sub => "fsub",
+                BinaryOp::Mul => "fmul",
+                BinaryOp::Div => "fdiv",
+                _ => unreachable!(),
+            };
+            let t = self.temporary();
+            self.line(format!("{} = {} double {}, {}", t, instruction, a, b));
+            return t;
+        }
+        let int = self.int_name();
+        match op {
+            BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul => {
+                let intrinsic = match op {
+                    BinaryOp::Add => "sadd",
+                    BinaryOp::Sub => "ssub",
+                    _ => "smul",
+                };
+                self.with_overflow(intrinsic, operator(op), left, right, span)
+            }
+            _ => {
+                // a zero divisor first, then the one quotient that overflows
+                let zero = self.temporary();
+                self.line(format!("{} = icmp eq {} {}, 0", zero, int, b));
+                let (line, column) = (span.line, span.column);
+                self.check(
+                    &zero,
+                    format!(
+                        "call void @mpl_division_by_zero(i32 {}, i32 {})",
+                        line, column
+                    ),
+                );
+                let minus_one = self.temporary();
+                self.line(format!("{} = icmp eq {} {}, -1", minus_one, int, b));
+                let t;
+                if op == BinaryOp::Div {
+                    let minimum = self.temporary();
+                    let overflow = self.temporary();
+                    let smallest = match self.int {
+                        Int::I32 => i64::from(i32::MIN),
+                        Int::I64 => i64::MIN,
+                    };
+                    self.line(format!("{} = icmp eq {} {}, {}", minimum, int, a, smallest));
+                    self.line(format!("{} = and i1 {}, {}", overflow, minimum, minus_one));
+                    let report = self.overflow(operator(op), left, right, span);
+                    self.check(&overflow, report);
+                    t = self.temporary();
+                    self.line(format!("{} = sdiv {} {}, {}", t, int, a, b));
+                } else {
+                    // srem traps on the smallest integer mod -1, which is 0 like any
+                    // remainder of -1
+                    let divisor = self.temporary();
+                    self.line(format!(
+                        "{} = select i1 {}, {} 1, {} {}",
+                        divisor, minus_one, int, int, b
+                    ));
+                    t = self.temporary();
+                    self.line(format!("{} = srem {} {}, {}", t, int, a, divisor));
+                }
+                t
+            }
+        }
+    }
+
+    fn comparison(
+        &mut self,
+        op: BinaryOp,
+        type_: DataType,
+        left: &Operand,
+        right: &Operand,
+    ) -> String {
+        let (signed, unsigned, float) = match op {
+            BinaryOp::Eq => ("eq", "eq", "oeq"),
+            // unordered, so NaN is not equal to anything
+            BinaryOp::Ne => ("ne", "ne", "une"),
+            BinaryOp::Lt => ("slt", "ult", "olt"),
+            BinaryOp::Le => ("sle", "ule", "ole"),
+            BinaryOp::Gt => ("sgt", "ugt", "ogt"),
+            _ => ("sge", "uge", "oge"),
+        };
+        let (a, b) = (self.operand(left), self.operand(right));
+        let t = self.temporary();
+        match type_ {
+            DataType::Int => {
+                let int = self.int_name();
+                self.line(format!("{} = icmp {} {} {}, {}", t, signed, int, a, b));
+            }
+            DataType::Real => self.line(format!("{} = fcmp {} double {}, {}", t, float, a, b)),
+            // false before true, characters by code point
+            DataType::Bool => self.line(format!("{} = icmp {} i1 {}, {}", t, unsigned, a, b)),
+            DataType::Char => self.line(format!("{} = icmp {} i32 {}, {}", t, unsigned, a, b)),
+            DataType::Str => {
+                let args = [format!("%mpl.string* {}", a), format!("%mpl.string* {}", b)];
+                let order = self.call("i32", "mpl_compare", &args, None);
+                self.line(format!("{} = icmp {} i32 {}, 0", t, signed, order));
+            }
+        }
+        t
+    }
+
+    fn unary(&mut self, op: UnaryOp, operand: &Operand, span: Span) -> String {
+        if op == UnaryOp::Neg && self.function.type_of(operand) == DataType::Int {
+            // `-n` is `0 - n`, which overflows for the smallest int only
+            return self.with_overflow("ssub", '-', &Operand::Int(0), operand, span);
+        }
+        let value = self.operand(operand);
+        let t = self.temporary();
+        let line = match op {
+            UnaryOp::Neg => format!("{} = fneg double {}", t, value),
+            UnaryOp::Not => format!("{} = xor i1 {}, true", t, value),
+            UnaryOp::IntToReal => {
+                format!("{} = sitofp {} {} to double", t, self.int_name(), value)
+            }
+        };
+        self.line(line);
+        t
+    }
+
+    fn with_overflow(
+        &mut self,
+        intrinsic: &str,
+        op: char,
+        left: &Operand,
+        right: &Operand,
+        span: Span,
+    ) -> String {
+        let int = self.int_name();
+        let (a, b) = (self.operand(left), self.operand(right));
+        let pair = self.temporary();
+        self.line(format!(
+            "{} = call {{ {}, i1 }} @llvm.{}.with.overflow.{}({} {}, {} {})",
+            pair, int, intrinsic, int, int, a, int, b
+        ));
+        let overflow = self.temporary();
+        self.line(format!(
+            "{} = extractvalue {{ {}, i1 }} {}, 1",
+            overflow, int, pair
+        ));
+        let report = self.overflow(op, left, right, span);
+        self.check(&overflow, report);
+        let t = self.temporary();
+        self.line(format!(
+            "{} = extractvalue {{ {}, i1 }} {}, 0",
+            t, int, pair
+        ));
+        t
+    }
+
+    // the call reporting `left op right` overflowed, operands widened for the runtime
+    fn overflow(&mut self, op: char, left: &Operand, right: &Operand, span: Span) -> String {
+        let (a, b) = (self.widen(left), self.widen(right));
+        format!(
+            "call void @mpl_overflow(i32 {}, i64 {}, i64 {}, i32 {}, i32 {})",
+            op as u32, a, b, span.line, span.column
+        )
+    }
+
+    // continues in a new block when `failed` is false, and makes `report` otherwise
+    fn check(&mut self, failed: &str, report: String) {
+        self.splits += 1;
+        let base = self.label.split('.').next().unwrap().to_string();
+        let fail = format!("{}.fail{}", base, self.splits);
+        let next = format!("{}.{}", base, self.splits);
+        self.line(format!(
+            "br i1 {}, label %{}, label %{}",
+            failed, fail, next
+        ));
+        self.text.push_str(&format!("{}:\n", fail));
+        self.line(report);
+        self.line(String::from("unreachable"));
+        self.text.push_str(&format!("{}:\n", next));
+        self.label = next;
+    }
+
+    // an int from the runtime, which works in 64 bits, that must fit `Int::I32`
+    fn narrow(&mut self, value: String, span: Span) -> String {
+        if self.int == Int::I64 {
+            return value;
+        }
+        let narrowed = self.temporary();
+        let back = self.temporary();
+        let lost = self.temporary();
+        self.line(format!("{} = trunc i64 {} to i32", narrowed, value));
+        self.line(format!("{} = sext i32 {} to i64", back, narrowed));
+        self.line(format!("{} = icmp ne i64 {}, {}", lost, back, value));
+        let report = format!(
+            "call void @mpl_too_large(i64 {}, i32 32, i32 {}, i32 {})",
+            value, span.line, span.column
+        );
+        self.check(&lost, report);
+        narrowed
+    }
+
+    // an int operand as the runtime's `int64_t`
+    fn widen(&mut self, operand: &Operand) -> String {
+        let value = self.operand(operand);
+        if self.int == Int::I64 || !matches!(operand, Operand::Value(_)) {
+            return value;
+        }
+        let t = self.temporary();
+        self.line(format!("{} = sext i32 {} to i64", t, value));
+        t
+    }
+
+    // a concatenation operand: chars become strings
+    fn string(&mut self, operand: &Operand) -> String {
+        let value = self.operand(operand);
+        match self.function.type_of(operand) {
+            DataType::Char => self.call(
+                "%mpl.string*",
+                "mpl_char_to_string",
+                &[format!("i32 {}", value)],
+                None,
+            ),
+            _ => value,
+        }
+    }
+
+    fn argument(&mut self, operand: &Operand, param: DataType) -> String {
+        match param {
+            DataType::Int => format!("i64 {}", self.widen(operand)),
+            _ => format!("{} {}", self.type_name(param), self.operand(operand)),
+        }
+    }
+
+    // a call into the runtime, builtins and checks get the position to report
+    fn call(
+        &mut self,
+        returns: &str,
+        function: &str,
+        args: &[String],
+        span: Option<Span>,
+    ) -> String {
+        let mut args = args.to_vec();
+        if let Some(span) = span {
+            args.push(format!("i32 {}", span.line));
+            args.push(format!("i32 {}", span.column));
+        }
+        let t = self.temporary();
+        self.line(format!(
+            "{} = call {} @{}({})",
+            t,
+            returns,
+            function,
+            args.join(", ")
+        ));
+        t
+    }
+
+    fn assign(&mut self, result: Option<ValueId>, value: String) {
+        if let Some(result) = result {
+            self.names.insert(result, value);
+        }
+    }
+
+    fn terminator(&mut self, terminator: &Terminator) {
+        let line = match terminator {
+            Terminator::Jump(target) => format!("br label %{}", target),
+            Terminator::Branch(condition, then, otherwise) => format!(
+                "br i1 {}, label %{}, label %{}",
+                self.operand(condition),
+                then,
+                otherwise
+            ),
+            Terminator::Return => String::from("ret i32 0"),
+        };
+        self.line(line);
+    }
+
+    fn operand(&mut self, operand: &Operand) -> String {
+        match operand {
+            Operand::Value(value) => self.names[value].clone(),
+            Operand::Int(n) => n.to_string(),
+            // exact, whatever the value
+            Operand::Real(r) => format!("0x{:016X}", r.to_bits()),
+            Operand::Bool(b) => b.to_string(),
+            Operand::Char(c) => (*c as u32).to_string(),
+            Operand::Str(s) => {
+                let next = self.strings.len();
+                let id = *self.string_ids.entry(s.clone()).or_insert(next);
+                if id == next {
+                    self.strings.push(s.clone());
+                }
+                format!("@.str.{}", id)
+            }
+        }
+    }
+
+    fn type_name(&self, type_: DataType) -> &'static str {
+        match type_ {
+            DataType::Int => self.int_name(),
+            DataType::Real => "double",
+            DataType::Bool => "i1",
+            DataType::Char => "i32",
+            DataType::Str => "%mpl.string*",
+        }
+    }
+
+    fn int_name(&self) -> &'static str {
+        match self.int {
+            Int::I32 => "i32",
+            Int::I64 => "i64",
+        }
+    }
+
+    fn temporary(&mut self) -> String {
+        self.temporaries += 1;
+        format!("%t{}", self.temporaries)
+    }
+
+    fn line(&mut self, line: String) {
+        self.text.push_str("  ");
+        self.text.push_str(&line);
+        self.text.push('\n');
+    }
+}
+
+fn operator(op: BinaryOp) -> char {
+    match op {
+        BinaryOp::Add => '+',
+        BinaryOp::Sub => '-',
+        BinaryOp::Mul => '*',
+        BinaryOp::Div => '/',
+        _ => '%',
+    }
+}
+
+fn runtime_type(type_: DataType) -> &'static str {
+    match type_ {
+        DataType::Int => "i64",
+        DataType::Real => "double",
+        DataType::Str => "%mpl.string*",
+        DataType::Bool | DataType::Char => "i32",
+    }
+}
+
+fn declarations(int: Int) -> String {
+    let mut out = String::from(
+        "declare void @mpl_print_int(i64)
+declare void @mpl_print_real(double)
+declare void @mpl_print_bool(i32)
+declare void @mpl_print_char(i32)
+declare void @mpl_print_string(%mpl.string*)
+declare i64 @mpl_read_int(i32, i32)
+declare double @mpl_read_real(i32, i32)
+declare %mpl.string* @mpl_read_string()
+declare %mpl.string* @mpl_concat(%mpl.string*, %mpl.string*)
+declare %mpl.string* @mpl_char_to_string(i32)
+declare i32 @mpl_compare(%mpl.string*, %mpl.string*)
+declare i32 @mpl_index(%mpl.string*, i64, i32, i32)
+declare void @mpl_overflow(i32, i64, i64, i32, i32) noreturn
+declare void @mpl_division_by_zero(i32, i32) noreturn
+declare void @mpl_too_large(i64, i32, i32, i32) noreturn
+",
+    );
+    let mut names: Vec<&&str> = crate::builtins::BUILTINS.keys().collect();
+    names.sort();
+    for name in names {
+        let builtin = &crate::builtins::BUILTINS[*name];
+        let mut params: Vec<&str> = builtin.params.iter().map(|p| runtime_type(*p)).collect();
+        params.extend(&["i32", "i32"]);
+        writeln!(
+            out,
+            "declare {} @mpl_{}({})",
+            runtime_type(builtin.returns),
+            name,
+            params.join(", ")
+        )
+        .unwrap();
+    }
+    let int = match int {
+        Int::I32 => "i32",
+        Int::I64 => "i64",
+    };
+    for intrinsic in &["sadd", "ssub", "smul"] {
+        writeln!(
+            out,
+            "declare {{ {}, i1 }} @llvm.{}.with.overflow.{}({}, {})",
+            int, intrinsic, int, int, int
+        )
+        .unwrap();
+    }
+    out
+}
+
+// printable ASCII as is, everything else as `\XX`
+fn escape(s: &str) -> String {
+    let mut escaped = String::new();
+    for byte in s.bytes() {
+        if (0x20..0x7F).contains(&byte) && byte != b'"' && byte != b'\\' {
+            escaped.push(byte as char);
+        } else {
+            write!(escaped, "\\{:02X}", byte).unwrap();
+        }
+    }
+    escaped
+}
+
+// int literals must fit `int`, reported where they are used
+fn check_literals(function: &Function, int: Int) -> Result<(), Error> {
+    for block in &function.blocks {
+        for instruction in &block.instructions {
+            let operands = match &instruction.kind {
+                Kind::Binary(_, left, right) | Kind::Index(left, right) => vec![left, right],
+                Kind::Unary(_, operand) | Kind::Print(operand) => vec![operand],
+                Kind::Call(_, args) => args.iter().collect(),
+                Kind::Read => vec![],
+            };
+            for operand in operands {
+                too_large(operand, int, instruction.span)?;
+            }
+        }
+        for phi in &block.phis {
+            for (_, operand) in &phi.incoming {
+                too_large(operand, int, first_use(function, phi.result))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn too_large(operand: &Operand, int: Int, span: Span) -> Result<(), Error> {
+    match operand {
+        Operand::Int(n) if !int.fits(*n) => {
+            let message = format!("integer literal {} does not fit in {} bits", n, int.bits());
+            Err(Error::Semantic(SemanticError::new(message, span)))
+        }
+        _ => Ok(()),
+    }
+}
+
+// where a phi's value is first used by an instruction, to report its operands at
+fn first_use(function: &Function, value: ValueId) -> Span {
+    let used = |operand: &Operand| *operand == Operand::Value(value);
+    for block in &function.blocks {
+        for instruction in &block.instructions {
+            let uses = match &instruction.kind {
+                Kind::Binary(_, left, right) | Kind::Index(left, right) => {
+                    used(left) || used(right)
+                }
+                Kind::Unary(_, operand) | Kind::Print(operand) => used(operand),
+                Kind::Call(_, args) => args.iter().any(used),
+                Kind::Read => false,
+            };
+            if uses {
+                return instruction.span;
+            }
+        }
+    }
+    Span::default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn emits_checked_arithmetic() {
+        let source = "var n : int;
+read n;
+if n > 2 do
+    n := n * 3;
+end if;
+print n;";
+        let module = compile(source, Int::I64).unwrap();
+        let main = &module[module.find("define").unwrap()..];
+        let expected = "define i32 @main() {
+b0:
+  %t1 = call i64 @mpl_read_int(i32 2, i32 6)
+  %t2 = icmp sgt i64 %t1, 2
+  br i1 %t2, label %b1, label %b2
+b1:
+  %t3 = call { i64, i1 } @llvm.smul.with.overflow.i64(i64 %t1, i64 3)
+  %t4 = extractvalue { i64, i1 } %t3, 1
+  br i1 %t4, label %b1.fail1, label %b1.1
+b1.fail1:
+  call void @mpl_overflow(i32 42, i64 %t1, i64 3, i32 4, i32 12)
+  unreachable
+b1.1:
+  %t5 = extractvalue { i64, i1 } %t3, 0
+  br label %b2
+b2:
+  %v3 = phi i64 [ %t5, %b1.1 ], [ %t1, %b0 ]
+  call void @mpl_print_int(i64 %v3)
+  ret i32 0
+}
+";
+        assert_eq!(main, expected);
+
+        let error = compile("var n : int := 3000000000; print n;", Int::I32).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Semantic error at 1:28: integer literal 3000000000 does not fit in 32 bits"
+        );
+    }
+}
@@ -0,0 +1,626 @@
+use crate::dataflow::{self, defined, uses};
+use crate::formatter::print_expr;
+use crate::nodes::{Assign, BinOp, DataType, ForLoop, Node, Num, Type, Var, VarDecl};
+use crate::optimizer::{Remark, Types};
+use crate::tokens::{Span, Token, TokenType, Value};
+use std::collections::{BTreeSet, HashMap};
+use std::convert::TryFrom;
+use std::mem;
+
+/// Loop optimisations over a checked tree:
+///
+/// - loop-invariant code motion: an expression in a `for` body that reads no variable
+///   the loop writes is computed once into a temporary before the loop
+/// - strength reduction: `i * c`, with `i` the control variable and `c` invariant, is
+///   replaced by a temporary that starts at `start * c` and grows by `c` each iteration
+///
+/// Temporaries are named `tmp1`, `tmp2`, ... skipping names the program uses, and are
+/// declared at the start of the program. Only expressions that cannot fail are hoisted,
+/// since a hoisted one runs even when the loop body would not reach it. Without
+/// `big_integers` strength reduction needs constant bounds and factor to prove that the
+/// additions stay in range. Every transformation and every one passed over is remarked.
+pub fn optimize_loops(tree: Node, big_integers: bool) -> (Node, Vec<Remark>) {
+    let types = Types::new(&tree, big_integers);
+    let mut program = match tree {
+        Node::Program(program) => program,
+        tree => return (tree, Vec::new()),
+    };
+    let mut optimizer = LoopOptimizer {
+        types,
+        temps: Vec::new(),
+        hoisted: BTreeSet::new(),
+        remarks: Vec::new(),
+    };
+    let children = mem::take(&mut program.children);
+    let mut children = optimizer.statements(children);
+    let mut declarations: Vec<Node> = optimizer
+        .temps
+        .iter()
+        .map(|(name, data_type)| declaration(name, *data_type))
+        .collect();
+    declarations.append(&mut children);
+    program.children = declarations;
+    let mut remarks = optimizer.remarks;
+    remarks.sort_by_key(|remark| remark.span.start);
+    (Node::Program(program), remarks)
+}
+
+struct LoopOptimizer {
+    types: Types,
+    temps: Vec<(String, DataType)>,
+    // temporaries holding hoisted expressions, each assigned exactly once
+    hoisted: BTreeSet<String>,
+    remarks: Vec<Remark>,
+}
+
+// the loop being optimised
+struct Loop {
+    span: Span,
+    // variables the loop writes, including its control variable
+    defined: BTreeSet<String>,
+    // statements to run before the loop
+    preheader: Vec<Node>,
+    // temporaries by the text of the expression they hold
+    temps: HashMap<String, String>,
+    missed: BTreeSet<String>,
+}
+
+impl LoopOptimizer {
+    fn statements(&mut self, statements: Vec<Node>) -> Vec<Node> {
+        let mut optimized = Vec::with_capacity(statements.len());
+        for statement in statements {
+            match statement {
+                Node::ForLoop(n) => self.for_loop(n, &mut optimized),
+                Node::IfStatement(mut n) => {
+                    n.statements = self.statements(mem::take(&mut n.statements));
+                    n.else_statements = self.statements(mem::take(&mut n.else_statements));
+                    optimized.push(Node::IfStatement(n));
+                }
+                statement => optimized.push(statement),
+            }
+        }
+        optimized
+    }
+
+    // appends the statements to run before the loop, then the loop itself
+    fn for_loop(&mut self, mut for_loop: Box<ForLoop>, optimized: &mut Vec<Node>) {
+        // inner loops first, their preheaders are then part of this body
+        let body = self.statements(mem::take(&mut for_loop.statements));
+        let mut l = Loop {
+            span: for_loop.span,
+            defined: BTreeSet::new(),
+            preheader: Vec::new(),
+            temps: HashMap::new(),
+            missed: BTreeSet::new(),
+        };
+        let var = dataflow::name(&for_loop.var_node);
+        for_loop.statements = self.move_hoisted(body, &var, &mut l);
+
+        l.defined = defined(&for_loop.statements);
+        l.defined.insert(var);
+        for statement in &mut for_loop.statements {
+            self.hoist_in(statement, &mut l);
+        }
+        self.reduce_loop(&mut for_loop, &mut l);
+
+        optimized.append(&mut l.preheader);
+        optimized.push(Node::ForLoop(for_loop));
+    }
+
+    // assignments of hoisted temporaries that are invariant in this loop too move before it,
+    // `var` is the loop's control variable
+    fn move_hoisted(&mut self, body: Vec<Node>, var: &str, l: &mut Loop) -> Vec<Node> {
+        let mut defined = defined(&body);
+        defined.insert(var.to_string());
+        let mut kept = Vec::with_capacity(body.len());
+        for statement in body {
+            let n = match statement {
+                Node::Assign(n) if self.hoisted.contains(&dataflow::name(&n.left)) => n,
+                statement => {
+                    kept.push(statement);
+                    continue;
+                }
+            };
+            let name = dataflow::name(&n.left);
+            defined.remove(&name);
+            if !uses(&n.right).is_disjoint(&defined) {
+                defined.insert(name);
+                kept.push(Node::Assign(n));
+                continue;
+            }
+            let message = format!(
+                "moved {} := {} out of the loop at {} as well",
+                name,
+                print_expr(&n.right),
+                l.span
+            );
+            self.remark("licm", message, n.span);
+            l.preheader.push(Node::Assign(n));
+        }
+        kept
+    }
+
+    fn hoist_in(&mut self, statement: &mut Node, l: &mut Loop) {
+        match statement {
+            Node::Assign(n) => n.right = self.hoist(take(&mut n.right), l),
+            Node::DeclAssign(n) => n.right = self.hoist(take(&mut n.right), l),
+            Node::IfStatement(n) => {
+                n.bool_expr = self.hoist(take(&mut n.bool_expr), l);
+                for statement in n.statements.iter_mut().chain(&mut n.else_statements) {
+                    self.hoist_in(statement, l);
+                }
+            }
+            // the range of an inner loop is evaluated once per iteration of this one
+            Node::ForLoop(n) => {
+                n.start = self.hoist(take(&mut n.start), l);
+                n.end = self.hoist(take(&mut n.end), l);
+            }
+            _ => {}
+        }
+    }
+
+    // replaces the largest invariant parts of `expr` by temporaries
+    fn hoist(&mut self, expr: Node, l: &mut Loop) -> Node {
+        let span = expr.span().unwrap_or(l.span);
+        if is_computation(&expr) && uses(&expr).is_disjoint(&l.defined) {
+            let text = print_expr(&expr);
+            if !self.types.may_fail(&expr) {
+                let message = format!("hoisted {} out of the loop at {}", text, l.span);
+                let name = match l.temps.get(&text) {
+                    Some(name) => name.clone(),
+                    None => {
+                        let name = self.temp(self.types.type_of(&expr));
+                        self.hoisted.insert(name.clone());
+                        l.temps.insert(text, name.clone());
+                        l.preheader.push(assignment(&name, expr, span));
+                        name
+                    }
+                };
+                self.remark("licm", format!("{} into {}", message, name), span);
+                return Node::Var(var(&name, span));
+            }
+            if l.missed.insert(text.clone()) {
+                let message = format!(
+                    "did not hoist {} out of the loop at {}, evaluating it may fail",
+                    text, l.span
+                );
+                self.remark("licm", message, span);
+            }
+        }
+        match expr {
+            Node::BinOp(mut n) => {
+                n.left = self.hoist(take(&mut n.left), l);
+                n.right = self.hoist(take(&mut n.right), l);
+                Node::BinOp(n)
+            }
+            Node::BoolExpr(mut n) => {
+                n.left = self.hoist(take(&mut n.left), l);
+                n.right = self.hoist(take(&mut n.right), l);
+                Node::BoolExpr(n)
+            }
+            Node::UnaryOp(mut n) => {
+                n.expr = self.hoist(take(&mut n.expr), l);
+                Node::UnaryOp(n)
+            }
+            Node::Index(mut n) => {
+                n.target = self.hoist(take(&mut n.target), l);
+                n.index = self.hoist(take(&mut n.index), l);
+                Node::Index(n)
+            }
+            Node::Call(mut n) => {
+                let args = mem::take(&mut n.args);
+                n.args = args.into_iter().map(|arg| self.hoist(arg, l)).collect();
+                Node::Call(n)
+            }
+            expr => expr,
+        }
+    }
+
+    fn reduce_loop(&mut self, for_loop: &mut ForLoop, l: &mut Loop) {
+        let mut reduction = Reduction {
+            var: dataflow::name(&for_loop.var_node),
+            // the bounds, when constant
+            bounds: match (&for_loop.start, &for_loop.end) {
+                (Node::Num(start), Node::Num(end)) => match (&start.value, &end.value) {
+                    (Value::Number(start), Value::Number(end)) => Some((*start, *end)),
+                    _ => None,
+                },
+                _ => None,
+            },
+            induction: Vec::new(),
+        };
+        for statement in &mut for_loop.statements {
+            self.reduce_in(statement, &mut reduction, l);
+        }
+        if reduction.induction.is_empty() {
+            return;
+        }
+        // the temporaries start from the range start, which is evaluated only once
+        if !matches!(for_loop.start, Node::Num(_) | Node::Var(_)) {
+            let span = for_loop.start.span().unwrap_or(l.span);
+            let name = self.temp(DataType::Int);
+            let start = take(&mut for_loop.start);
+            l.preheader.push(assignment(&name, start, span));
+            for_loop.start = Node::Var(var(&name, span));
+        }
+        for induction in reduction.induction {
+            let start = match (copy(&for_loop.start), &induction.step) {
+                (Node::Num(start), Node::Num(step)) => match (&start.value, &step.value) {
+                    (Value::Number(a), Value::Number(b)) => match a.checked_mul(*b) {
+                        Some(product) => number(product, induction.span),
+                        None => product(Node::Num(start), copy(&induction.step)),
+                    },
+                    _ => product(Node::Num(start), copy(&induction.step)),
+                },
+                (start, step) => product(start, copy(step)),
+            };
+            l.preheader
+                .push(assignment(&induction.name, start, induction.span));
+            let next = Node::BinOp(Box::new(BinOp::new(
+                Node::Var(var(&induction.name, induction.step_span)),
+                Token::new(TokenType::Plus, Value::String(String::from("+"))),
+                copy(&induction.step),
+            )));
+            for_loop
+                .statements
+                .push(assignment(&induction.name, next, induction.step_span));
+        }
+    }
+
+    fn reduce_in(&mut self, statement: &mut Node, r: &mut Reduction, l: &mut Loop) {
+        match statement {
+            Node::Assign(n) => n.right = self.reduce(take(&mut n.right), r, l),
+            Node::DeclAssign(n) => n.right = self.reduce(take(&mut n.right), r, l),
+            Node::IfStatement(n) => {
+                n.bool_expr = self.reduce(take(&mut n.bool_expr), r, l);
+                for statement in n.statements.iter_mut().chain(&mut n.else_statements) {
+                    self.reduce_in(statement, r, l);
+                }
+            }
+            Node::ForLoop(n) => {
+                n.start = self.reduce(take(&mut n.start), r, l);
+                n.end = self.reduce(take(&mut n.end), r, l);
+                for statement in &mut n.statements {
+                    self.reduce_in(statement, r, l);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn reduce(&mut self, expr: Node, r: &mut Reduction, l: &mut Loop) -> Node {
+        let (step, step_span) = match &expr {
+            Node::BinOp(n) if n.op.type_ == TokenType::Mul => {
+                match (self.is_step(&n.left, r, l), self.is_step(&n.right, r, l)) {
+                    (false, true) if is_var(&n.left, &r.var) => (copy(&n.right), n.right.span()),
+                    (true, false) if is_var(&n.right, &r.var) => (copy(&n.left), n.left.span()),
+                    _ => return self.reduce_operands(expr, r, l),
+                }
+            }
+            _ => return self.reduce_operands(expr, r, l),
+        };
+        let span = expr.span().unwrap_or(l.span);
+        let text = print_expr(&expr);
+        if !self.types.big_integers() && !r.in_range(&step) {
+            if l.missed.insert(text.clone()) {
+                let message = format!(
+                    "did not reduce {} in the loop at {}, \
+                     without big integers the additions may overflow",
+                    text, l.span
+                );
+                self.remark("strength-reduction", message, span);
+            }
+            return expr;
+        }
+        let step_text = print_expr(&step);
+        let name = match r
+            .induction
+            .iter()
+            .find(|i| print_expr(&i.step) == step_text)
+        {
+            Some(induction) => induction.name.clone(),
+            None => {
+                let name = self.temp(DataType::Int);
+                r.induction.push(Induction {
+                    name: name.clone(),
+                    step,
+                    span,
+                    step_span: step_span.unwrap_or(l.span),
+                });
+                name
+            }
+        };
+        let message = format!(
+            "replaced {} by {}, which grows by {} each iteration of the loop at {}",
+            text, name, step_text, l.span
+        );
+        self.remark("strength-reduction", message, span);
+        Node::Var(var(&name, span))
+    }
+
+    fn reduce_operands(&mut self, expr: Node, r: &mut Reduction, l: &mut Loop) -> Node {
+        match expr {
+            Node::BinOp(mut n) => {
+                n.left = self.reduce(take(&mut n.left), r, l);
+                n.right = self.reduce(take(&mut n.right), r, l);
+                Node::BinOp(n)
+            }
+            Node::BoolExpr(mut n) => {
+                n.left = self.reduce(take(&mut n.left), r, l);
+                n.right = self.reduce(take(&mut n.right), r, l);
+                Node::BoolExpr(n)
+            }
+            Node::UnaryOp(mut n) => {
+                n.expr = self.reduce(take(&mut n.expr), r, l);
+                Node::UnaryOp(n)
+            }
+            Node::Index(mut n) => {
+                n.target = self.reduce(take(&mut n.target), r, l);
+                n.index = self.reduce(take(&mut n.index), r, l);
+                Node::Index(n)
+            }
+            Node::Call(mut n) => {
+                let args = mem::take(&mut n.args);
+                n.args = args.into_iter().map(|arg| self.reduce(arg, r, l)).collect();
+                Node::Call(n)
+            }
+            expr => expr,
+        }
+    }
+
+    // an integer the loop does not change: a literal or an invariant variable
+    fn is_step(&self, node: &Node, r: &Reduction, l: &Loop) -> bool {
+        match node {
+            Node::Num(n) => matches!(n.value, Value::Number(_)),
+            Node::Var(v) => {
+                let name = dataflow::name(v);
+                name != r.var
+                    && !l.defined.contains(&name)
+                    && self.types.type_of(node) == DataType::Int
+            }
+            _ => false,
+        }
+    }
+
+    fn temp(&mut self, data_type: DataType) -> String {
+        let mut n = self.temps.len() + 1;
+        let name = loop {
+            let name = format!("tmp{}", n);
+            if !self.types.is_declared(&name) {
+                break name;
+            }
+            n += 1;
+        };
+        self.types.insert(name.clone(), data_type);
+        self.temps.push((name.clone(), data_type));
+        name
+    }
+
+    fn remark(&mut self, pass: &'static str, message: String, span: Span) {
+        self.remarks.push(Remark {
+            pass,
+            message,
+            span,
+        });
+    }
+}
+
+struct Reduction {
+    var: String,
+    bounds: Option<(i64, i64)>,
+    induction: Vec<Induction>,
+}
+
+// a temporary that equals `var * step` throughout every iteration
+struct Induction {
+    name: String,
+    step: Node,
+    // where the product was first replaced, and its right operand, as distinct spans for
+    // the initialisation before the loop and the increment at the end of the body
+    span: Span,
+    step_span: Span,
+}
+
+impl Reduction {
+    // whether `start * step` up to `(end + 1) * step` fit in 64 bits
+    fn in_range(&self, step: &Node) -> bool {
+        let step = match step {
+            Node::Num(Num {
+                value: Value::Number(step),
+                ..
+            }) => *step as i128,
+            _ => return false,
+        };
+        let fits = |n: i128| i64::try_from(n * step).is_ok();
+        matches!(self.bounds, Some((start, end)) if fits(start as i128) && fits(end as i128 + 1))
+    }
+}
+
+// worth a temporary: computes something rather than naming a value
+fn is_computation(expr: &Node) -> bool {
+    match expr {
+        Node::BinOp(_) | Node::BoolExpr(_) | Node::Call(_) | Node::Index(_) => true,
+        Node::UnaryOp(n) => !matches!(n.expr, Node::Num(_) | Node::Real(_)),
+        _ => false,
+    }
+}
+
+fn is_var(node: &Node, name: &str) -> bool {
+    matches!(node, Node::Var(v) if dataflow::name(v) == name)
+}
+
+// a copy of a literal or variable
+fn copy(node: &Node) -> Node {
+    match node {
+        Node::Num(n) => Node::Num(Num::new(n.token.clone())),
+        Node::Var(v) => Node::Var(v.clone()),
+        _ => unreachable!(),
+    }
+}
+
+fn product(left: Node, right: Node) -> Node {
+    Node::BinOp(Box::new(BinOp::new(
+        left,
+        Token::new(TokenType::Mul, Value::String(String::from("*"))),
+        right,
+    )))
+}
+
+fn number(n: i64, span: Span) -> Node {
+    let mut token = Token::new(TokenType::Integer, Value::Number(n));
+    token.span = span;
+    Node::Num(Num::new(token))
+}
+
+fn var(name: &str, span: Span) -> Var {
+    let mut token = Token::new(TokenType::ID, Value::String(name.to_string()));
+    token.span = span;
+    Var::new(token)
+}
+
+fn assignment(name: &str, expr: Node, span: Span) -> Node {
+    let op = Token::new(TokenType::Assign, Value::String(String::from(":=")));
+    let mut assign = Assign::new(var(name, span), op, expr);
+    assign.span = span;
+    Node::Assign(Box::new(assign))
+}
+
+// declared before the program with no span, see `Node::source_span`
+fn declaration(name: &str, data_type: DataType) -> Node {
+    let type_ = match data_type {
+        DataType::Int => TokenType::Integer,
+        DataType::Str => TokenType::Str,
+        DataType::Bool => TokenType::Bool,
+        DataType::Char => TokenType::Char,
+        DataType::Real => TokenType::Real,
+    };
+    let token = Token::new(type_, Value::String(data_type.to_string()));
+    let declaration = VarDecl::new(var(name, Span::default()), Type::new(token));
+    Node::VarDecl(Box::new(declaration))
+}
+
+fn take(node: &mut Node) -> Node {
+    mem::replace(node, Node::NoOp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formatter;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    fn optimized(text: &str, big_integers: bool) -> (String, Vec<String>) {
+        let tree = Parser::new(Scanner::new(text.to_string())).parse().unwrap();
+        let (tree, remarks) = optimize_loops(tree, big_integers);
+        let remarks = remarks.iter().map(Remark::to_string).collect();
+        (formatter::print(&tree), remarks)
+    }
+
+    #[test]
+    fn hoists_and_reduces() {
+        let text = "var r : real := 1.5;
+var x : real := 1.0;
+var n : int := 0;
+var tmp1 : int;
+var i : int;
+var j : int;
+for i in 1..10 do
+    for j in 0..9 do
+        n := n + j * 4 + tmp1 / 2;
+    end for;
+    x := x * (r + 2.0);
+    n := n + i * n;
+end for;";
+        let expected = "var tmp2 : int;
+var tmp3 : int;
+var tmp4 : real;
+var r : real := 1.5;
+var x : real := 1.0;
+var n : int := 0;
+var tmp1 : int;
+var i : int;
+var j : int;
+tmp2 := tmp1 / 2;
+tmp4 := r + 2.0;
+for i in 1..10 do
+    tmp3 := 0;
+    for j in 0..9 do
+        n := n + tmp3 + tmp2;
+        tmp3 := tmp3 + 4;
+    end for;
+    x := x * tmp4;
+    n := n + i * n;
+end for;
+";
+        let (tree, remarks) = optimized(text, false);
+        assert_eq!(tree, expected);
+        assert_eq!(
+            remarks,
+            [
+                "9:18: remark[strength-reduction]: replaced j * 4 by tmp3, which grows by 4 \
+                 each iteration of the loop at 8:5",
+                "9:26: remark[licm]: hoisted tmp1 / 2 out of the loop at 8:5 into tmp2",
+                "9:26: remark[licm]: moved tmp2 := tmp1 / 2 out of the loop at 7:1 as well",
+                "11:15: remark[licm]: hoisted r + 2.0 out of the loop at 7:1 into tmp4",
+            ]
+        );
+
+        // the loop changes `n`, and `i * k` is only reduced when it cannot overflow
+        let text = "var k : int := 3;
+var n : int;
+var i : int;
+for i in 1..n do
+    n := n + i * k + (k + 1);
+end for;";
+        let (_, remarks) = optimized(text, false);
+        assert_eq!(
+            remarks,
+            [
+                "5:14: remark[strength-reduction]: did not reduce i * k in the loop at 4:1, \
+                 without big integers the additions may overflow",
+                "5:23: remark[licm]: did not hoist k + 1 out of the loop at 4:1, evaluating \
+                 it may fail",
+            ]
+        );
+        let (tree, remarks) = optimized(text, true);
+        assert_eq!(remarks.len(), 2);
+        assert!(tree.contains("tmp1 := k + 1;\ntmp2 := 1 * k;\nfor i in 1..n do\n"));
+    }
+    #[test]
+    fn keeps_temporaries_of_the_outer_control_variable_in_its_loop() {
+        let text = "var n : int;
+var i : int;
+var j : int;
+for i in 1..3 do
+    for j in 1..2 do
+        n := n + 6800 * i;
+    end for;
+end for;";
+        // `6800 * i` is invariant in the inner loop only
+        let expected = "var tmp1 : int;
+var tmp2 : int;
+var n : int;
+var i : int;
+var j : int;
+tmp2 := 6800;
+for i in 1..3 do
+    tmp1 := tmp2;
+    for j in 1..2 do
+        n := n + tmp1;
+    end for;
+    tmp2 := tmp2 + 6800;
+end for;
+";
+        let (tree, remarks) = optimized(text, true);
+        assert_eq!(tree, expected);
+        assert_eq!(
+            remarks,
+            [
+                "6:18: remark[licm]: hoisted 6800 * i out of the loop at 5:5 into tmp1",
+                "6:18: remark[strength-reduction]: replaced 6800 * i by tmp2, which grows by \
+                 6800 each iteration of the loop at 4:1",
+            ]
+        );
+    }
+}
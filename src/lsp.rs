@@ -0,0 +1,923 @@
+//! A language server speaking the Language Server Protocol over stdio: JSON-RPC
+//! messages framed by a `Content-Length` header. Documents are synced in full
+//! and analysed again on every change.
+
+use crate::builtins::BUILTINS;
+use crate::checker::Checker;
+use crate::error::Error;
+use crate::json::Json;
+use crate::linter::Linter;
+use crate::nodes::{DataType, Node, Var};
+use crate::parser::Parser;
+use crate::scanner::Scanner;
+use crate::tokens::{Span, Token, TokenType, Value};
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// Semantic token legend, a token's type is its index here.
+pub const TOKEN_TYPES: &[&str] = &[
+    "keyword", "type", "variable", "function", "string", "number", "operator", "comment",
+];
+
+/// Serves one client until it sends `exit` or closes the input. Returns whether
+/// the client shut the server down properly, i.e. sent `shutdown` before `exit`.
+pub fn run<R: BufRead, W: Write>(mut input: R, mut output: W) -> io::Result<bool> {
+    let mut server = Server {
+        documents: HashMap::new(),
+        shutdown: false,
+    };
+    while let Some(body) = read_message(&mut input)? {
+        let message = match Json::parse(&body) {
+            Ok(message) => message,
+            Err(e) => {
+                write_message(&mut output, &error_response(Json::Null, PARSE_ERROR, e))?;
+                continue;
+            }
+        };
+        if message.get("method").and_then(Json::as_str) == Some("exit") {
+            return Ok(server.shutdown);
+        }
+        for reply in server.handle(&message) {
+            write_message(&mut output, &reply)?;
+        }
+    }
+    Ok(false)
+}
+
+// the debug adapter protocol uses the same framing
+pub(crate) fn read_message<R: BufRead>(input: &mut R) -> io::Result<Option<String>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse().ok();
+            }
+        }
+    }
+    let length = length.ok_or_else(|| invalid_data("missing Content-Length header"))?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    String::from_utf8(body)
+        .map(Some)
+        .map_err(|_| invalid_data("message is not UTF-8"))
+}
+
+pub(crate) fn write_message<W: Write>(output: &mut W, message: &Json) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn response(id: Json, result: Json) -> Json {
+    Json::object(vec![
+        ("jsonrpc", "2.0".into()),
+        ("id", id),
+        ("result", result),
+    ])
+}
+
+fn error_response(id: Json, code: i64, message: String) -> Json {
+    let error = Json::object(vec![
+        ("code", Json::Number(code as f64)),
+        ("message", message.into()),
+    ]);
+    Json::object(vec![
+        ("jsonrpc", "2.0".into()),
+        ("id", id),
+        ("error", error),
+    ])
+}
+
+fn notification(method: &str, params: Json) -> Json {
+    Json::object(vec![
+        ("jsonrpc", "2.0".into()),
+        ("method", method.into()),
+        ("params", params),
+    ])
+}
+
+type RequestResult = Result<Json, (i64, String)>;
+
+struct Server {
+    documents: HashMap<String, Document>,
+    shutdown: bool,
+}
+
+impl Server {
+    // replies to a request, or the notifications a client notification causes
+    fn handle(&mut self, message: &Json) -> Vec<Json> {
+        let method = match message.get("method").and_then(Json::as_str) {
+            Some(method) => method,
+            // responses to requests we never send
+            None => return Vec::new(),
+        };
+        let params = message.get("params").unwrap_or(&Json::Null);
+        match message.get("id") {
+            Some(id) => vec![match self.request(method, params) {
+                Ok(result) => response(id.clone(), result),
+                Err((code, message)) => error_response(id.clone(), code, message),
+            }],
+            None => self.notification(method, params),
+        }
+    }
+
+    fn request(&mut self, method: &str, params: &Json) -> RequestResult {
+        if self.shutdown {
+            return Err((INVALID_REQUEST, String::from("server is shut down")));
+        }
+        match method {
+            "initialize" => Ok(capabilities()),
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Json::Null)
+            }
+            "textDocument/hover" => {
+                let (document, offset) = self.locate(params)?;
+                Ok(document.hover(offset))
+            }
+            "textDocument/definition" => {
+                let (document, offset) = self.locate(params)?;
+                Ok(match document.symbol_at(offset) {
+                    Some(symbol) => document.location(symbol.declaration),
+                    None => Json::Null,
+                })
+            }
+            "textDocument/references" => {
+                let (document, offset) = self.locate(params)?;
+                let include_declaration = params
+                    .path(&["context", "includeDeclaration"])
+                    .and_then(Json::as_bool)
+                    .unwrap_or(false);
+                let symbol = match document.symbol_at(offset) {
+                    Some(symbol) => symbol,
+                    None => return Ok(Json::Null),
+                };
+                let locations = symbol
+                    .references
+                    .iter()
+                    .filter(|span| include_declaration || **span != symbol.declaration)
+                    .map(|span| document.location(*span))
+                    .collect();
+                Ok(Json::Array(locations))
+            }
+            "textDocument/rename" => {
+                let (document, offset) = self.locate(params)?;
+                let new_name = params
+                    .get("newName")
+                    .and_then(Json::as_str)
+                    .ok_or((INVALID_PARAMS, String::from("missing newName")))?;
+                document.rename(offset, new_name)
+            }
+            "textDocument/documentSymbol" => {
+                let document = self.document(params)?;
+                Ok(Json::Array(
+                    document
+                        .symbols
+                        .iter()
+                        .map(|symbol| document.document_symbol(symbol))
+                        .collect(),
+                ))
+            }
+            "textDocument/semanticTokens/full" => {
+                let document = self.document(params)?;
+                let data = document.semantic_tokens().into_iter().map(Json::from);
+                Ok(Json::object(vec![("data", Json::Array(data.collect()))]))
+            }
+            _ => Err((METHOD_NOT_FOUND, format!("unknown method {}", method))),
+        }
+    }
+
+    fn notification(&mut self, method: &str, params: &Json) -> Vec<Json> {
+        let uri = match params.path(&["textDocument", "uri"]).and_then(Json::as_str) {
+            Some(uri) => uri.to_string(),
+            None => return Vec::new(),
+        };
+        let text = match method {
+            "textDocument/didOpen" => params.path(&["textDocument", "text"]),
+            // full sync, so the last change holds the whole text
+            "textDocument/didChange" => params
+                .get("contentChanges")
+                .and_then(Json::as_array)
+                .and_then(|changes| changes.last())
+                .and_then(|change| change.get("text")),
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                return vec![publish_diagnostics(&uri, Vec::new())];
+            }
+            _ => return Vec::new(),
+        };
+        let text = match text.and_then(Json::as_str) {
+            Some(text) => text.to_string(),
+            None => return Vec::new(),
+        };
+        let document = Document::new(uri.clone(), text);
+        let diagnostics = document.diagnostics();
+        self.documents.insert(uri.clone(), document);
+        vec![publish_diagnostics(&uri, diagnostics)]
+    }
+
+    fn document(&self, params: &Json) -> Result<&Document, (i64, String)> {
+        let uri = params
+            .path(&["textDocument", "uri"])
+            .and_then(Json::as_str)
+            .ok_or((INVALID_PARAMS, String::from("missing textDocument")))?;
+        self.documents
+            .get(uri)
+            .ok_or_else(|| (INVALID_PARAMS, format!("unknown document {}", uri)))
+    }
+
+    fn locate(&self, params: &Json) -> Result<(&Document, usize), (i64, String)> {
+        let document = self.document(params)?;
+        let offset = params
+            .get("position")
+            .and_then(|position| document.offset(position))
+            .ok_or((INVALID_PARAMS, String::from("missing or invalid position")))?;
+        Ok((document, offset))
+    }
+}
+
+fn capabilities() -> Json {
+    let legend = Json::object(vec![
+        (
+            "tokenTypes",
+            Json::Array(TOKEN_TYPES.iter().map(|t| (*t).into()).collect()),
+        ),
+        ("tokenModifiers", Json::Array(Vec::new())),
+    ]);
+    Json::object(vec![
+        (
+            "capabilities",
+            Json::object(vec![
+                // full document sync
+                ("textDocumentSync", 1.into()),
+                ("hoverProvider", true.into()),
+                ("definitionProvider", true.into()),
+                ("referencesProvider", true.into()),
+                ("renameProvider", true.into()),
+                ("documentSymbolProvider", true.into()),
+                (
+                    "semanticTokensProvider",
+                    Json::object(vec![("legend", legend), ("full", true.into())]),
+                ),
+            ]),
+        ),
+        (
+            "serverInfo",
+            Json::object(vec![("name", "mini-pl-lsp".into())]),
+        ),
+    ])
+}
+
+fn publish_diagnostics(uri: &str, diagnostics: Vec<Json>) -> Json {
+    notification(
+        "textDocument/publishDiagnostics",
+        Json::object(vec![
+            ("uri", uri.into()),
+            ("diagnostics", Json::Array(diagnostics)),
+        ]),
+    )
+}
+
+/// A declared variable with every place its name appears.
+struct Symbol {
+    spelling: String,
+    data_type: DataType,
+    // the name in the declaration and the whole declaring statement
+    declaration: Span,
+    statement: Span,
+    // declaration included, in source order
+    references: Vec<Span>,
+}
+
+struct Document {
+    uri: String,
+    text: String,
+    // byte offset where each line starts
+    line_starts: Vec<usize>,
+    error: Option<Error>,
+    warnings: Vec<crate::linter::Warning>,
+    symbols: Vec<Symbol>,
+    calls: Vec<Token>,
+}
+
+impl Document {
+    fn new(uri: String, text: String) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        let mut document = Document {
+            uri,
+            text,
+            line_starts,
+            error: None,
+            warnings: Vec::new(),
+            symbols: Vec::new(),
+            calls: Vec::new(),
+        };
+        let tree = match Parser::new(Scanner::new(document.text.clone())).parse() {
+            Ok(tree) => tree,
+            Err(e) => {
+                document.error = Some(e);
+                return document;
+            }
+        };
+        let mut table = SymbolTable {
+            symbols: Vec::new(),
+            by_name: HashMap::new(),
+            calls: Vec::new(),
+        };
+        if let Node::Program(program) = &tree {
+            table.statements(&program.children);
+        }
+        for symbol in &mut table.symbols {
+            symbol.references.sort_by_key(|span| span.start);
+        }
+        document.symbols = table.symbols;
+        document.calls = table.calls;
+        // the linter expects a program that passed the checker
+        match Checker::new().check(&tree) {
+            Ok(_) => document.warnings = Linter::new().lint(&tree),
+            Err(e) => document.error = Some(e.into()),
+        }
+        document
+    }
+
+    fn diagnostics(&self) -> Vec<Json> {
+        let error = self.error.iter().map(|e| {
+            Json::object(vec![
+                ("range", self.range(e.span())),
+                ("severity", 1.into()),
+                ("source", "mini-pl".into()),
+                ("message", e.message().into()),
+            ])
+        });
+        let warnings = self.warnings.iter().map(|w| {
+            Json::object(vec![
+                ("range", self.range(w.span)),
+                ("severity", 2.into()),
+                ("code", w.rule.into()),
+                ("source", "mini-pl".into()),
+                ("message", w.message.clone().into()),
+            ])
+        });
+        error.chain(warnings).collect()
+    }
+
+    // zero-based line and UTF-16 column of a byte offset
+    fn line_column(&self, offset: usize) -> (usize, usize) {
+        let line = self.line_starts.partition_point(|start| *start <= offset) - 1;
+        let start = self.line_starts[line];
+        (line, self.text[start..offset].encode_utf16().count())
+    }
+
+    fn position(&self, offset: usize) -> Json {
+        let (line, character) = self.line_column(offset);
+        Json::object(vec![("line", line.into()), ("character", character.into())])
+    }
+
+    fn range(&self, span: Span) -> Json {
+        Json::object(vec![
+            ("start", self.position(span.start)),
+            ("end", self.position(span.end)),
+        ])
+    }
+
+    fn location(&self, span: Span) -> Json {
+        Json::object(vec![
+            ("uri", self.uri.as_str().into()),
+            ("range", self.range(span)),
+        ])
+    }
+
+    // byte offset of an LSP position, columns past the end of a line clamp to it
+    fn offset(&self, position: &Json) -> Option<usize> {
+        let line = position.get("line").and_then(Json::as_usize)?;
+        let character = position.get("character").and_then(Json::as_usize)?;
+        let start = *self.line_starts.get(line)?;
+        let mut units = 0;
+        for (i, c) in self.text[start..].char_indices() {
+            if units >= character || c == '\n' {
+                return Some(start + i);
+            }
+            units += c.len_utf16();
+        }
+        Some(self.text.len())
+    }
+
+    fn symbol_at(&self, offset: usize) -> Option<&Symbol> {
+        self.symbols.iter().find(|symbol| {
+            symbol
+                .references
+                .iter()
+                .any(|span| span.start <= offset && offset <= span.end)
+        })
+    }
+
+    fn hover(&self, offset: usize) -> Json {
+        let (span, text) = if let Some(symbol) = self.symbol_at(offset) {
+            let span = *symbol
+                .references
+                .iter()
+                .find(|span| span.start <= offset && offset <= span.end)
+                .unwrap();
+            (
+                span,
+                format!("var {} : {}", symbol.spelling, symbol.data_type),
+            )
+        } else if let Some(call) = self
+            .calls
+            .iter()
+            .find(|call| call.span.start <= offset && offset <= call.span.end)
+        {
+            let name = call.value.to_string();
+            let builtin = match BUILTINS.get(name.as_str()) {
+                Some(builtin) => builtin,
+                None => return Json::Null,
+            };
+            let params: Vec<String> = builtin.params.iter().map(|p| p.to_string()).collect();
+            let signature = format!("{}({}) : {}", name, params.join(", "), builtin.returns);
+            (call.span, signature)
+        } else {
+            return Json::Null;
+        };
+        Json::object(vec![
+            (
+                "contents",
+                Json::object(vec![
+                    ("kind", "markdown".into()),
+                    ("value", format!("```minipl\n{}\n```", text).into()),
+                ]),
+            ),
+            ("range", self.range(span)),
+        ])
+    }
+
+    fn rename(&self, offset: usize, new_name: &str) -> RequestResult {
+        let symbol = match self.symbol_at(offset) {
+            Some(symbol) => symbol,
+            None => return Ok(Json::Null),
+        };
+        let tokens: Vec<Token> = Scanner::new(new_name.to_string())
+            .collect::<Result<_, _>>()
+            .unwrap_or_default();
+        let is_identifier = match tokens.as_slice() {
+            [token] => token.type_ == TokenType::ID && token.value.to_string() == new_name,
+            _ => false,
+        };
+        if !is_identifier {
+            return Err((
+                INVALID_PARAMS,
+                format!("{} is not a valid variable name", new_name),
+            ));
+        }
+        let lowercase = new_name.to_lowercase();
+        let clash = self.symbols.iter().any(|other| {
+            other.declaration != symbol.declaration && other.spelling.to_lowercase() == lowercase
+        });
+        if clash {
+            return Err((
+                INVALID_PARAMS,
+                format!("a variable named {} already exists", new_name),
+            ));
+        }
+        let edits = symbol
+            .references
+            .iter()
+            .map(|span| {
+                Json::object(vec![
+                    ("range", self.range(*span)),
+                    ("newText", new_name.into()),
+                ])
+            })
+            .collect();
+        let changes = Json::Object(vec![(self.uri.clone(), Json::Array(edits))]);
+        Ok(Json::object(vec![("changes", changes)]))
+    }
+
+    fn document_symbol(&self, symbol: &Symbol) -> Json {
+        Json::object(vec![
+            ("name", symbol.spelling.as_str().into()),
+            ("detail", symbol.data_type.to_string().into()),
+            // SymbolKind.Variable
+            ("kind", 13.into()),
+            ("range", self.range(symbol.statement)),
+            ("selectionRange", self.range(symbol.declaration)),
+        ])
+    }
+
+    // relative encoding: line delta, start delta, length, type, modifiers; tokens
+    // spanning lines (block comments) are split since clients may not handle them
+    fn semantic_tokens(&self) -> Vec<usize> {
+        let tokens: Vec<Token> = Scanner::with_trivia(self.text.clone())
+            .filter_map(Result::ok)
+            .collect();
+        let mut data = Vec::new();
+        let (mut last_line, mut last_character) = (0, 0);
+        for (i, token) in tokens.iter().enumerate() {
+            let next = tokens[i + 1..].iter().find(|t| !t.type_.is_trivia());
+            let kind = match token_kind(token, next) {
+                Some(kind) => kind,
+                None => continue,
+            };
+            let mut start = token.span.start;
+            for piece in self.text[token.span.start..token.span.end].split('\n') {
+                let text = piece.trim_end_matches('\r');
+                if !text.is_empty() {
+                    let (line, character) = self.line_column(start);
+                    let delta = if line == last_line {
+                        character - last_character
+                    } else {
+                        character
+                    };
+                    data.extend_from_slice(&[
+                        line - last_line,
+                        delta,
+                        text.encode_utf16().count(),
+                        kind,
+                        0,
+                    ]);
+                    last_line = line;
+                    last_character = character;
+                }
+                start += piece.len() + 1;
+            }
+        }
+        data
+    }
+}
+
+fn token_kind(token: &Token, next: Option<&Token>) -> Option<usize> {
+    let kind = match token.type_ {
+        TokenType::Var
+        | TokenType::Print
+        | TokenType::Read
+        | TokenType::For
+        | TokenType::End
+        | TokenType::If
+        | TokenType::Else
+        | TokenType::Do
+        | TokenType::In => "keyword",
+        // integer literals share the token type of the `int` keyword
+        TokenType::Integer if !matches!(token.value, Value::String(_)) => "number",
+        TokenType::Integer
+        | TokenType::Str
+        | TokenType::Bool
+        | TokenType::Char
+        | TokenType::Real => "type",
+        TokenType::ID if next.map(|t| &t.type_) == Some(&TokenType::LeftParen) => "function",
+        TokenType::ID => "variable",
+        TokenType::StringLiteral | TokenType::CharLiteral => "string",
+        TokenType::RealLiteral => "number",
+        TokenType::Comment => "comment",
+        TokenType::Plus
+        | TokenType::Minus
+        | TokenType::Mul
+        | TokenType::Div
+        | TokenType::Mod
+        | TokenType::Assign
+        | TokenType::To
+        | TokenType::Equal
+        | TokenType::NotEqual
+        | TokenType::LessThan
+        | TokenType::LessEqual
+        | TokenType::GreaterThan
+        | TokenType::GreaterEqual
+        | TokenType::And
+        | TokenType::Or
+        | TokenType::Not => "operator",
+        _ => return None,
+    };
+    TOKEN_TYPES.iter().position(|t| *t == kind)
+}
+
+// links every use of a name to the declaration in effect, programs have a single scope
+struct SymbolTable {
+    symbols: Vec<Symbol>,
+    by_name: HashMap<String, usize>,
+    calls: Vec<Token>,
+}
+
+impl SymbolTable {
+    fn statements(&mut self, statements: &[Node]) {
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    fn statement(&mut self, node: &Node) {
+        match node {
+            Node::VarDecl(n) => self.declare(&n.var_node, n.type_node.data_type(), n.span),
+            Node::DeclAssign(n) => {
+                self.expr(&n.right);
+                self.declare(&n.left, n.type_node.data_type(), n.span);
+            }
+            Node::Assign(n) => {
+                self.reference(&n.left);
+                self.expr(&n.right);
+            }
+            Node::Read(n) => self.reference(&n.var_node),
+            Node::PrintVar(n) => self.reference(&n.var_node),
+            Node::ForLoop(n) => {
+                self.reference(&n.var_node);
+                self.expr(&n.start);
+                self.expr(&n.end);
+                self.statements(&n.statements);
+            }
+            Node::IfStatement(n) => {
+                self.expr(&n.bool_expr);
+                self.statements(&n.statements);
+                self.statements(&n.else_statements);
+            }
+            _ => {}
+        }
+    }
+
+    fn expr(&mut self, node: &Node) {
+        match node {
+            Node::Var(n) => self.reference(n),
+            Node::BinOp(n) => {
+                self.expr(&n.left);
+                self.expr(&n.right);
+            }
+            Node::BoolExpr(n) => {
+                self.expr(&n.left);
+                self.expr(&n.right);
+            }
+            Node::UnaryOp(n) => self.expr(&n.expr),
+            Node::Index(n) => {
+                self.expr(&n.target);
+                self.expr(&n.index);
+            }
+            Node::Call(n) => {
+                self.calls.push(n.token.clone());
+                for arg in &n.args {
+                    self.expr(arg);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn declare(&mut self, var: &Var, data_type: DataType, statement: Span) {
+        let name = var.value.to_string().to_lowercase();
+        // a second declaration is a checker error, the first one stays in effect
+        if self.by_name.contains_key(&name) {
+            return;
+        }
+        self.by_name.insert(name, self.symbols.len());
+        self.symbols.push(Symbol {
+            spelling: var.value.to_string(),
+            data_type,
+            declaration: var.token.span,
+            statement,
+            references: vec![var.token.span],
+        });
+    }
+
+    fn reference(&mut self, var: &Var) {
+        let name = var.value.to_string().to_lowercase();
+        if let Some(index) = self.by_name.get(&name) {
+            self.symbols[*index].references.push(var.token.span);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // runs a scripted session, returning the server's messages and the exit status
+    fn session(messages: &[String]) -> (Vec<Json>, bool) {
+        let mut input = Vec::new();
+        for message in messages {
+            write!(
+                input,
+                "Content-Length: {}\r\n\r\n{}",
+                message.len(),
+                message
+            )
+            .unwrap();
+        }
+        let mut output = Vec::new();
+        let clean = run(&input[..], &mut output).unwrap();
+        let mut replies = Vec::new();
+        let mut output = &output[..];
+        while let Some(body) = read_message(&mut output).unwrap() {
+            replies.push(Json::parse(&body).unwrap());
+        }
+        (replies, clean)
+    }
+
+    fn request(id: usize, method: &str, params: &str) -> String {
+        format!(
+            r#"{{"jsonrpc":"2.0","id":{},"method":"{}","params":{}}}"#,
+            id, method, params
+        )
+    }
+
+    fn at(line: usize, character: usize) -> String {
+        format!(
+            r#"{{"textDocument":{{"uri":"file:///a.mpl"}},"position":{{"line":{},"character":{}}}}}"#,
+            line, character
+        )
+    }
+
+    fn result(replies: &[Json], id: usize) -> &Json {
+        replies
+            .iter()
+            .find(|reply| reply.get("id").and_then(Json::as_usize) == Some(id))
+            .unwrap()
+    }
+
+    fn lines(locations: &Json) -> Vec<(usize, usize)> {
+        let locations = locations.as_array().unwrap();
+        locations
+            .iter()
+            .map(|location| {
+                let range = location.get("range").unwrap_or(location);
+                let start = range.get("start").unwrap();
+                let line = start.get("line").and_then(Json::as_usize).unwrap();
+                let character = start.get("character").and_then(Json::as_usize).unwrap();
+                (line, character)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn serves_a_scripted_session() {
+        let source = r#"var n : int := 3;\nvar s : string := \"😀\"; print s;\nvar i : int;\nfor i in 0..N do\n    print s;\nend for;\n"#;
+        let messages = vec![
+            request(1, "initialize", r#"{"capabilities":{}}"#),
+            String::from(r#"{"jsonrpc":"2.0","method":"initialized","params":{}}"#),
+            format!(
+                r#"{{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{{"textDocument":{{"uri":"file:///a.mpl","languageId":"minipl","version":1,"text":"{}"}}}}}}"#,
+                source
+            ),
+            request(2, "textDocument/hover", &at(1, 30)),
+            request(3, "textDocument/definition", &at(3, 12)),
+            request(
+                4,
+                "textDocument/references",
+                &at(4, 10).replace("}}", r#"},"context":{"includeDeclaration":true}}"#),
+            ),
+            request(
+                5,
+                "textDocument/rename",
+                &at(0, 4).replace("}}", r#"},"newName":"count"}"#),
+            ),
+            request(
+                6,
+                "textDocument/rename",
+                &at(0, 4).replace("}}", r#"},"newName":"for"}"#),
+            ),
+            request(
+                7,
+                "textDocument/rename",
+                &at(0, 4).replace("}}", r#"},"newName":"S"}"#),
+            ),
+            request(
+                8,
+                "textDocument/documentSymbol",
+                r#"{"textDocument":{"uri":"file:///a.mpl"}}"#,
+            ),
+            request(
+                9,
+                "textDocument/semanticTokens/full",
+                r#"{"textDocument":{"uri":"file:///a.mpl"}}"#,
+            ),
+            request(10, "textDocument/formatting", "{}"),
+            String::from(
+                r#"{"jsonrpc":"2.0","method":"textDocument/didChange","params":{"textDocument":{"uri":"file:///a.mpl","version":2},"contentChanges":[{"text":"var x : int :="}]}}"#,
+            ),
+            request(11, "shutdown", "null"),
+            String::from(r#"{"jsonrpc":"2.0","method":"exit"}"#),
+        ];
+        let (replies, clean) = session(&messages);
+        assert!(clean);
+
+        let capabilities = result(&replies, 1)
+            .path(&["result", "capabilities"])
+            .unwrap();
+        assert_eq!(capabilities.get("hoverProvider"), Some(&Json::Bool(true)));
+
+        let diagnostics: Vec<&Json> = replies
+            .iter()
+            .filter(|reply| reply.get("method").is_some())
+            .map(|reply| reply.path(&["params", "diagnostics"]).unwrap())
+            .collect();
+        assert_eq!(diagnostics.len(), 2);
+        let codes: Vec<&str> = diagnostics[0]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|d| d.get("code").and_then(Json::as_str).unwrap())
+            .collect();
+        assert_eq!(codes, ["unused-variable", "case-mismatch"]);
+        assert_eq!(lines(diagnostics[0]), [(2, 4), (3, 12)]);
+        let error = &diagnostics[1].as_array().unwrap()[0];
+        assert_eq!(error.get("severity").and_then(Json::as_usize), Some(1));
+        assert_eq!(
+            error.get("message").and_then(Json::as_str),
+            Some("expected an expression, found end of input")
+        );
+
+        // columns count UTF-16 code units, the emoji takes two
+        let hover = result(&replies, 2).get("result").unwrap();
+        assert_eq!(
+            hover.path(&["contents", "value"]).and_then(Json::as_str),
+            Some("```minipl\nvar s : string\n```")
+        );
+        assert_eq!(
+            hover
+                .path(&["range", "start", "character"])
+                .and_then(Json::as_usize),
+            Some(30)
+        );
+
+        let definition = result(&replies, 3).get("result").unwrap();
+        assert_eq!(lines(&Json::Array(vec![definition.clone()])), [(0, 4)]);
+
+        let references = result(&replies, 4).get("result").unwrap();
+        assert_eq!(lines(references), [(1, 4), (1, 30), (4, 10)]);
+
+        let edits = result(&replies, 5)
+            .path(&["result", "changes", "file:///a.mpl"])
+            .unwrap();
+        assert_eq!(lines(edits), [(0, 4), (3, 12)]);
+        for id in &[6, 7] {
+            let code = result(&replies, *id).path(&["error", "code"]);
+            assert_eq!(code.and_then(Json::as_f64), Some(INVALID_PARAMS as f64));
+        }
+
+        let symbols = result(&replies, 8)
+            .get("result")
+            .unwrap()
+            .as_array()
+            .unwrap();
+        let symbols: Vec<(&str, &str)> = symbols
+            .iter()
+            .map(|s| {
+                let name = s.get("name").and_then(Json::as_str).unwrap();
+                (name, s.get("detail").and_then(Json::as_str).unwrap())
+            })
+            .collect();
+        assert_eq!(symbols, [("n", "int"), ("s", "string"), ("i", "int")]);
+
+        // var n : int := 3;
+        let tokens = result(&replies, 9).path(&["result", "data"]).unwrap();
+        let tokens: Vec<usize> = tokens
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|n| n.as_usize().unwrap())
+            .collect();
+        assert_eq!(
+            &tokens[..25],
+            [0, 0, 3, 0, 0, 0, 4, 1, 2, 0, 0, 4, 3, 1, 0, 0, 4, 2, 6, 0, 0, 3, 1, 5, 0]
+        );
+
+        let code = result(&replies, 10).path(&["error", "code"]);
+        assert_eq!(code.and_then(Json::as_f64), Some(METHOD_NOT_FOUND as f64));
+        assert_eq!(result(&replies, 11).get("result"), Some(&Json::Null));
+    }
+
+    #[test]
+    fn splits_multiline_tokens() {
+        let document = Document::new(
+            String::from("file:///b.mpl"),
+            String::from("/* a\n  bc */ x := abs(1);"),
+        );
+        assert_eq!(
+            document.semantic_tokens(),
+            [
+                0, 0, 4, 7, 0, 1, 0, 7, 7, 0, 0, 8, 1, 2, 0, 0, 2, 2, 6, 0, 0, 3, 3, 3, 0, 0, 4, 1,
+                5, 0
+            ]
+        );
+        let hover = document.hover(19);
+        assert_eq!(
+            hover.path(&["contents", "value"]).and_then(Json::as_str),
+            Some("```minipl\nabs(int) : int\n```")
+        );
+        // leaving without shutdown is an error
+        let (_, clean) = session(&[String::from(r#"{"jsonrpc":"2.0","method":"exit"}"#)]);
+        assert!(!clean);
+    }
+}
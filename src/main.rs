@@ -1,31 +1,367 @@
-mod interpreter;
-mod nodes;
-mod parser;
-mod scanner;
-mod tokens;
-
-use crate::interpreter::Interpreter;
-use crate::parser::Parser;
-use crate::scanner::Scanner;
+use mini_pl::checker::Checker;
+use mini_pl::debugger::Console;
+use mini_pl::error::Error;
+use mini_pl::fold;
+use mini_pl::formatter;
+use mini_pl::generator::Generator;
+use mini_pl::golden;
+use mini_pl::interpreter::{Interpreter, Limits};
+use mini_pl::ir;
+use mini_pl::json::Json;
+use mini_pl::linter::{self, Linter};
+use mini_pl::llvm::{self, Int};
+use mini_pl::loops;
+use mini_pl::nodes::Node;
+use mini_pl::optimizer;
+use mini_pl::parser::Parser;
+use mini_pl::profiler::Profiler;
+use mini_pl::scanner::Scanner;
+use mini_pl::trace::Tracer;
 use std::io::Read;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let mut command_line: std::env::Args = std::env::args();
     command_line.next().unwrap();
-    let source = command_line.next().unwrap();
+    let mut big_integers = false;
+    let mut debug = false;
+    let mut source = command_line.next().unwrap();
+    if source == "fmt" {
+        return format_files(command_line.collect());
+    }
+    if source == "lint" {
+        return lint_files(command_line.collect());
+    }
+    if source == "generate" {
+        return generate(command_line.collect());
+    }
+    if source == "test" {
+        return test_files(command_line.collect());
+    }
+    if source == "compile" {
+        return compile(command_line.collect());
+    }
+    if source == "debug" {
+        debug = true;
+        source = command_line.next().unwrap();
+    }
+    let mut tracer = None;
+    let mut profile = false;
+    let mut folded_stacks = None;
+    let mut limits = Limits::default();
+    let mut fold_constants = false;
+    let mut eliminate_dead_code = false;
+    let mut optimize_loops = false;
+    let mut remarks = false;
+    let mut dump_ast = false;
+    let mut dump_ir = false;
+    let mut timeout = None;
+    loop {
+        match source.as_str() {
+            "--big-int" => big_integers = true,
+            "--fold" => fold_constants = true,
+            "--dce" => eliminate_dead_code = true,
+            "--remarks" => remarks = true,
+            flag if flag.starts_with("--opt-level=") => {
+                let level: u8 = flag["--opt-level=".len()..].parse()?;
+                fold_constants = level >= 1;
+                eliminate_dead_code = level >= 1;
+                optimize_loops = level >= 2;
+            }
+            "--dump-ast" => dump_ast = true,
+            "--dump-ir" => dump_ir = true,
+            "--trace" => tracer = Some(Tracer::text(Box::new(std::io::stderr()))),
+            flag if flag.starts_with("--trace=") => {
+                let file = std::fs::File::create(&flag["--trace=".len()..])?;
+                tracer = Some(Tracer::json_lines(Box::new(file)));
+            }
+            "--profile" => profile = true,
+            flag if flag.starts_with("--profile=") => {
+                profile = true;
+                folded_stacks = Some(flag["--profile=".len()..].to_string());
+            }
+            flag if flag.starts_with("--max-steps=") => {
+                limits.fuel = Some(flag["--max-steps=".len()..].parse()?);
+            }
+            flag if flag.starts_with("--max-output=") => {
+                limits.output_bytes = Some(flag["--max-output=".len()..].parse()?);
+            }
+            flag if flag.starts_with("--max-string-bytes=") => {
+                limits.string_bytes = Some(flag["--max-string-bytes=".len()..].parse()?);
+            }
+            flag if flag.starts_with("--timeout=") => {
+                let seconds: f64 = flag["--timeout=".len()..].parse()?;
+                timeout = Some(Duration::try_from_secs_f64(seconds)?);
+            }
+            _ => break,
+        }
+        source = command_line.next().unwrap();
+    }
     let mut file = std::fs::File::open(source).unwrap();
     let mut input = String::new();
     file.read_to_string(&mut input)?;
 
+    if dump_ast || dump_ir {
+        let passes = Passes {
+            fold_constants,
+            optimize_loops,
+            eliminate_dead_code,
+        };
+        dump(&input, &passes, big_integers, dump_ast, dump_ir);
+    }
     if !input.is_empty() {
-        let lexer = Scanner::new(input);
+        let lexer = Scanner::new(input.clone());
         let parser = Parser::new(lexer);
         let mut interpreter = Interpreter::new(parser);
+        interpreter.big_integers = big_integers;
+        interpreter.fold_constants = fold_constants;
+        interpreter.eliminate_dead_code = eliminate_dead_code;
+        interpreter.optimize_loops = optimize_loops;
+        interpreter.tracer = tracer;
+        interpreter.limits = limits;
+        interpreter.deadline = timeout.and_then(|timeout| Instant::now().checked_add(timeout));
+        if profile {
+            interpreter.profiler = Some(Profiler::new());
+        }
+        if debug {
+            let console = Console::new(input.clone(), std::io::stdin(), std::io::stdout());
+            interpreter.debugger = Some(Box::new(console));
+        }
         let result = interpreter.interpret();
-        println!("{}", result);
-        Ok(())
+        if remarks {
+            for remark in &interpreter.remarks {
+                eprintln!("{}", remark);
+            }
+        }
+        if let Some(profiler) = &interpreter.profiler {
+            eprint!("{}", profiler.report(&input));
+            if let Some(path) = folded_stacks {
+                std::fs::write(path, profiler.folded())?;
+            }
+        }
+        match result {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                eprintln!("{}", e);
+                // exceeded limits and timeouts are told apart from errors in the program
+                match e {
+                    Error::Limit(_) | Error::Interrupted(_) => std::process::exit(2),
+                    _ => std::process::exit(1),
+                }
+            }
+        }
     } else {
         println!("No input received");
         Ok(())
     }
 }
+
+// `fmt [--check] <file>...` rewrites the files in canonical layout, with `--check`
+// it only reports the files that would change and fails if there are any
+fn format_files(args: Vec<String>) -> Result<(), Box<dyn std::error::Error>> {
+    let check = args.iter().any(|arg| arg == "--check");
+    let mut unformatted = false;
+    for path in args.iter().filter(|arg| *arg != "--check") {
+        let source = std::fs::read_to_string(path)?;
+        let formatted = match formatter::format(&source) {
+            Ok(formatted) => formatted,
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                std::process::exit(1);
+            }
+        };
+        if formatted == source {
+            continue;
+        }
+        if check {
+            eprintln!("{} is not formatted", path);
+            unformatted = true;
+        } else {
+            std::fs::write(path, formatted)?;
+        }
+    }
+    if unformatted {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+struct Passes {
+    fold_constants: bool,
+    optimize_loops: bool,
+    eliminate_dead_code: bool,
+}
+
+// prints the tree as source to stderr, and again after each optimisation that is on,
+// then the IR lowered from the result; errors are left for the run to report
+fn dump(source: &str, passes: &Passes, big_integers: bool, ast: bool, ir: bool) {
+    let mut tree = match Parser::new(Scanner::new(source.to_string())).parse() {
+        Ok(tree) => tree,
+        Err(_) => return,
+    };
+    let print = |heading: &str, tree: &Node| {
+        if ast {
+            eprint!("// {}\n{}", heading, formatter::print(tree));
+        }
+    };
+    print("parsed", &tree);
+    if Checker::new().check(&tree).is_err() {
+        return;
+    }
+    if passes.fold_constants {
+        tree = match fold::fold(tree, big_integers) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        print("folded", &tree);
+    }
+    if passes.optimize_loops {
+        tree = loops::optimize_loops(tree, big_integers).0;
+        print("loops optimized", &tree);
+    }
+    if passes.eliminate_dead_code {
+        tree = optimizer::eliminate_dead_code(tree, big_integers).0;
+        print("dead code removed", &tree);
+    }
+    if ir {
+        match ir::lower(&tree) {
+            Ok(function) => match ir::verify(&function) {
+                Ok(()) => eprint!("// ir\n{}", function),
+                Err(e) => eprintln!("{}", e),
+            },
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+}
+
+// `generate [--seed <n>] [--count <n>]` prints random well-typed programs, separated by
+// blank lines; the same seed always gives the same programs
+fn generate(args: Vec<String>) -> Result<(), Box<dyn std::error::Error>> {
+    let (mut seed, mut count): (u64, u64) = (0, 1);
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let value = args.next().ok_or("expected a number")?.parse()?;
+        match arg.as_str() {
+            "--seed" => seed = value,
+            "--count" => count = value,
+            _ => return Err(format!("unknown option {}", arg).into()),
+        }
+    }
+    for i in 0..count {
+        if i > 0 {
+            println!();
+        }
+        print!("{}", Generator::new(seed.wrapping_add(i)).program());
+    }
+    Ok(())
+}
+
+// `test [--bless] <path>...` runs every `.mpl` file in the paths, directories searched
+// recursively, and compares its output with the sibling `.out` and `.err` files
+fn test_files(args: Vec<String>) -> Result<(), Box<dyn std::error::Error>> {
+    let bless = args.iter().any(|arg| arg == "--bless");
+    let paths: Vec<PathBuf> = args
+        .iter()
+        .filter(|arg| *arg != "--bless")
+        .map(PathBuf::from)
+        .collect();
+    let interpreter = std::env::current_exe()?;
+    if !golden::run(&interpreter, &paths, bless, &mut std::io::stdout())? {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+// `compile [--i32] <file>` prints the program as LLVM IR, `compile --runtime` the C
+// runtime it links with
+fn compile(args: Vec<String>) -> Result<(), Box<dyn std::error::Error>> {
+    let mut int = Int::I64;
+    let mut path = None;
+    for arg in args {
+        match arg.as_str() {
+            "--i32" => int = Int::I32,
+            "--runtime" => {
+                print!("{}", llvm::RUNTIME);
+                return Ok(());
+            }
+            _ => path = Some(arg),
+        }
+    }
+    let source = std::fs::read_to_string(path.ok_or("expected a file")?)?;
+    match llvm::compile(&source, int) {
+        Ok(module) => print!("{}", module),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+    Ok(())
+}
+
+// `lint [--rules] [--json] [--enable <rules>] [--disable <rules>] <file>...` where <rules> is a
+// comma separated list of rule ids or `all`; fails if anything was reported
+fn lint_files(args: Vec<String>) -> Result<(), Box<dyn std::error::Error>> {
+    let mut linter = Linter::new();
+    let mut json = false;
+    let mut paths = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => json = true,
+            "--rules" => {
+                for (id, description) in linter::RULES {
+                    println!("{:20} {}", id, description);
+                }
+                return Ok(());
+            }
+            "--enable" | "--disable" => {
+                let rules = args.next().ok_or("expected a list of rules")?;
+                for rule in rules.split(',') {
+                    if arg == "--enable" {
+                        linter.enable(rule)?;
+                    } else {
+                        linter.disable(rule)?;
+                    }
+                }
+            }
+            _ => paths.push(arg),
+        }
+    }
+
+    let mut reports = Vec::new();
+    let mut found = false;
+    for path in paths {
+        let source = std::fs::read_to_string(&path)?;
+        let checked = Parser::new(Scanner::new(source)).parse().and_then(|tree| {
+            Checker::new().check(&tree)?;
+            Ok(tree)
+        });
+        let tree = match checked {
+            Ok(tree) => tree,
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                std::process::exit(1);
+            }
+        };
+        for warning in linter.lint(&tree) {
+            found = true;
+            if json {
+                let mut report = warning.to_json();
+                if let Json::Object(members) = &mut report {
+                    members.insert(0, (String::from("file"), path.as_str().into()));
+                }
+                reports.push(report);
+            } else {
+                println!("{}:{}", path, warning);
+            }
+        }
+    }
+    if json {
+        println!("{}", Json::Array(reports));
+    }
+    if found {
+        std::process::exit(1);
+    }
+    Ok(())
+}
@@ -1,4 +1,5 @@
-use crate::tokens::{Token, Value};
+use crate::tokens::{Span, Token, TokenType, Value};
+use std::fmt::{self, Display, Formatter};
 
 #[derive(Debug)]
 pub enum Node {
@@ -6,7 +7,10 @@ pub enum Node {
     ForLoop(Box<ForLoop>),
     BinOp(Box<BinOp>),
     Num(Num),
+    Real(Real),
     Str(Str),
+    Char(Char),
+    Index(Box<Index>),
     UnaryOp(Box<UnaryOp>),
     Program(Program),
     Assign(Box<Assign>),
@@ -17,9 +21,73 @@ pub enum Node {
     PrintVar(Box<PrintVar>),
     PrintStr(Box<PrintStr>),
     Read(Box<Read>),
+    Call(Box<Call>),
     NoOp,
 }
 
+impl Node {
+    /// Source span of a statement, from its first token to its last one (without
+    /// the separating `;`), or of an expression, from its first operand to its last
+    /// (without enclosing parentheses). `None` for the empty statement.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Node::VarDecl(n) => Some(n.span),
+            Node::DeclAssign(n) => Some(n.span),
+            Node::Assign(n) => Some(n.span),
+            Node::PrintVar(n) => Some(n.span),
+            Node::PrintStr(n) => Some(n.span),
+            Node::Read(n) => Some(n.span),
+            Node::ForLoop(n) => Some(n.span),
+            Node::IfStatement(n) => Some(n.span),
+            Node::Var(n) => Some(n.token.span),
+            Node::Num(n) => Some(n.token.span),
+            Node::Real(n) => Some(n.token.span),
+            Node::Str(n) => Some(n.token.span),
+            Node::Char(n) => Some(n.token.span),
+            Node::Call(n) => Some(join(n.token.span, n.args.last())),
+            Node::Index(n) => Some(join(n.target.span()?, Some(&n.index))),
+            Node::UnaryOp(n) => Some(join(n.op.span, Some(&n.expr))),
+            Node::BoolExpr(n) if n.op.type_ == TokenType::Not => {
+                Some(join(n.op.span, Some(&n.right)))
+            }
+            Node::BoolExpr(n) => Some(join(n.left.span()?, Some(&n.right))),
+            Node::BinOp(n) => Some(join(n.left.span()?, Some(&n.right))),
+            Node::Program(_) | Node::NoOp => None,
+        }
+    }
+
+    /// The span, or `None` for a statement an optimization added that has no place
+    /// in the source, such as the declaration of a temporary.
+    pub fn source_span(&self) -> Option<Span> {
+        self.span().filter(|span| span.line > 0)
+    }
+
+    pub fn set_span(&mut self, span: Span) {
+        match self {
+            Node::VarDecl(n) => n.span = span,
+            Node::DeclAssign(n) => n.span = span,
+            Node::Assign(n) => n.span = span,
+            Node::PrintVar(n) => n.span = span,
+            Node::PrintStr(n) => n.span = span,
+            Node::Read(n) => n.span = span,
+            Node::ForLoop(n) => n.span = span,
+            Node::IfStatement(n) => n.span = span,
+            _ => {}
+        }
+    }
+}
+
+// extends `start` to the end of `last`
+fn join(start: Span, last: Option<&Node>) -> Span {
+    match last.and_then(Node::span) {
+        Some(last) => Span {
+            end: last.end,
+            ..start
+        },
+        None => start,
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Var {
     pub token: Token,
@@ -48,12 +116,56 @@ impl Type {
             token,
         }
     }
+
+    pub fn data_type(&self) -> DataType {
+        match self.token.type_ {
+            TokenType::Integer => DataType::Int,
+            TokenType::Str => DataType::Str,
+            TokenType::Bool => DataType::Bool,
+            TokenType::Char => DataType::Char,
+            TokenType::Real => DataType::Real,
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DataType {
+    Int,
+    Str,
+    Bool,
+    Char,
+    Real,
+}
+
+impl DataType {
+    pub fn is_numeric(&self) -> bool {
+        matches!(self, DataType::Int | DataType::Real)
+    }
+
+    // int converts implicitly to real, nothing else converts
+    pub fn accepts(&self, found: DataType) -> bool {
+        *self == found || (*self == DataType::Real && found == DataType::Int)
+    }
+}
+
+impl Display for DataType {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            DataType::Int => write!(f, "int"),
+            DataType::Str => write!(f, "string"),
+            DataType::Bool => write!(f, "bool"),
+            DataType::Char => write!(f, "char"),
+            DataType::Real => write!(f, "real"),
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct VarDecl {
     pub var_node: Var,
     pub type_node: Type,
+    pub span: Span,
 }
 
 impl VarDecl {
@@ -61,6 +173,7 @@ impl VarDecl {
         VarDecl {
             var_node,
             type_node,
+            span: Span::default(),
         }
     }
 }
@@ -68,33 +181,47 @@ impl VarDecl {
 #[derive(Debug)]
 pub struct Read {
     pub var_node: Var,
+    pub span: Span,
 }
 
 impl Read {
     pub fn new(var_node: Var) -> Self {
-        Read { var_node }
+        Read {
+            var_node,
+            span: Span::default(),
+        }
     }
 }
 
 #[derive(Debug)]
 pub struct PrintStr {
+    pub token: Token,
     pub value: Value,
+    pub span: Span,
 }
 
 impl PrintStr {
-    pub fn new(value: Value) -> Self {
-        PrintStr { value }
+    pub fn new(token: Token) -> Self {
+        PrintStr {
+            value: token.value.clone(),
+            token,
+            span: Span::default(),
+        }
     }
 }
 
 #[derive(Debug)]
 pub struct PrintVar {
     pub var_node: Var,
+    pub span: Span,
 }
 
 impl PrintVar {
     pub fn new(var_node: Var) -> Self {
-        PrintVar { var_node }
+        PrintVar {
+            var_node,
+            span: Span::default(),
+        }
     }
 }
 
@@ -117,6 +244,7 @@ pub struct Assign {
     pub token: Token,
     pub op: Token,
     pub right: Node,
+    pub span: Span,
 }
 
 impl Assign {
@@ -126,6 +254,7 @@ impl Assign {
             token: op.clone(),
             op,
             right,
+            span: Span::default(),
         }
     }
 }
@@ -137,6 +266,7 @@ pub struct DeclAssign {
     pub type_node: Type,
     pub op: Token,
     pub right: Node,
+    pub span: Span,
 }
 
 impl DeclAssign {
@@ -147,6 +277,7 @@ impl DeclAssign {
             token: op.clone(),
             op,
             right,
+            span: Span::default(),
         }
     }
 }
@@ -169,6 +300,8 @@ pub struct IfStatement {
     pub bool_expr: Node,
     pub statements: Vec<Node>,
     pub else_statements: Vec<Node>,
+    pub span: Span,
+    pub else_span: Option<Span>,
 }
 
 impl IfStatement {
@@ -177,6 +310,8 @@ impl IfStatement {
             bool_expr,
             statements,
             else_statements,
+            span: Span::default(),
+            else_span: None,
         }
     }
 }
@@ -187,6 +322,7 @@ pub struct ForLoop {
     pub start: Node,
     pub end: Node,
     pub statements: Vec<Node>,
+    pub span: Span,
 }
 
 impl ForLoop {
@@ -196,6 +332,7 @@ impl ForLoop {
             start,
             end,
             statements,
+            span: Span::default(),
         }
     }
 }
@@ -234,6 +371,53 @@ impl Str {
     }
 }
 
+#[derive(Debug)]
+pub struct Real {
+    pub token: Token,
+    pub value: Value,
+}
+
+impl Real {
+    pub fn new(token: Token) -> Self {
+        Real {
+            value: token.value.clone(),
+            token,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Char {
+    pub token: Token,
+    pub value: Value,
+}
+
+impl Char {
+    pub fn new(token: Token) -> Self {
+        Char {
+            value: token.value.clone(),
+            token,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Index {
+    pub token: Token,
+    pub target: Node,
+    pub index: Node,
+}
+
+impl Index {
+    pub fn new(token: Token, target: Node, index: Node) -> Self {
+        Index {
+            token,
+            target,
+            index,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Num {
     pub token: Token,
@@ -265,3 +449,20 @@ impl UnaryOp {
         }
     }
 }
+
+#[derive(Debug)]
+pub struct Call {
+    pub token: Token,
+    pub value: Value,
+    pub args: Vec<Node>,
+}
+
+impl Call {
+    pub fn new(token: Token, args: Vec<Node>) -> Self {
+        Call {
+            value: token.value.clone(),
+            token,
+            args,
+        }
+    }
+}
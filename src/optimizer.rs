@@ -0,0 +1,349 @@
+use crate::builtins::BUILTINS;
+use crate::dataflow::{self, Cfg, Kind};
+use crate::nodes::{DataType, Node, VarDecl};
+use crate::tokens::{Span, TokenType, Value};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::{self, Display, Formatter};
+use std::mem;
+
+/// A note on what an optimisation did to the program, or chose not to do.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Remark {
+    /// The transformation the remark is about, e.g. `dce`.
+    pub pass: &'static str,
+    pub message: String,
+    pub span: Span,
+}
+
+impl Display for Remark {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}: remark[{}]: {}", self.span, self.pass, self.message)
+    }
+}
+
+/// Dead code elimination over a checked tree, driven by liveness:
+///
+/// - an assignment whose value is never read is removed
+/// - an initial value that is never read is dropped, leaving the plain declaration
+/// - a declaration of a variable no statement uses is removed, so it never takes
+///   space in the interpreter's variables
+///
+/// Stores whose expression may fail at runtime, e.g. by overflow or a bad index, are
+/// kept so the program fails the same way. `read` statements and loop variables are
+/// never removed. Repeats until nothing changes, since a removal can make the
+/// statements feeding it dead too.
+pub fn eliminate_dead_code(mut tree: Node, big_integers: bool) -> (Node, Vec<Remark>) {
+    let mut remarks = Vec::new();
+    loop {
+        let cfg = Cfg::new(&tree);
+        let mut pass = Pass {
+            dead: dead_stores(&cfg),
+            mentioned: mentioned(&cfg),
+            types: Types::new(&tree, big_integers),
+            removed: Vec::new(),
+            kept: Vec::new(),
+        };
+        if let Node::Program(program) = &mut tree {
+            program.children = pass.statements(mem::take(&mut program.children));
+        }
+        if pass.removed.is_empty() {
+            remarks.extend(pass.kept);
+            break;
+        }
+        remarks.extend(pass.removed);
+    }
+    remarks.sort_by_key(|remark| remark.span.start);
+    (tree, remarks)
+}
+
+struct Pass {
+    // spans of the assignments and declarations whose value is never read, as start
+    // and end offsets since statements an optimisation added may share a start
+    dead: BTreeSet<(usize, usize)>,
+    // variables some statement other than their declaration reads or writes
+    mentioned: BTreeSet<String>,
+    types: Types,
+    removed: Vec<Remark>,
+    kept: Vec<Remark>,
+}
+
+impl Pass {
+    fn statements(&mut self, statements: Vec<Node>) -> Vec<Node> {
+        let mut kept = Vec::with_capacity(statements.len());
+        for statement in statements {
+            if let Some(statement) = self.statement(statement) {
+                kept.push(statement);
+            }
+        }
+        kept
+    }
+
+    fn statement(&mut self, statement: Node) -> Option<Node> {
+        let span = statement.span().unwrap_or_default();
+        match statement {
+            Node::VarDecl(n) => {
+                let name = dataflow::name(&n.var_node);
+                if !self.mentioned.contains(&name) {
+                    self.remove(
+                        format!("removed declaration of {}, it is never used", name),
+                        span,
+                    );
+                    return None;
+                }
+                Some(Node::VarDecl(n))
+            }
+            Node::DeclAssign(n) => {
+                let name = dataflow::name(&n.left);
+                if !self.dead.contains(&(span.start, span.end)) {
+                    return Some(Node::DeclAssign(n));
+                }
+                if self.types.may_fail(&n.right) {
+                    self.keep(&name, span);
+                    return Some(Node::DeclAssign(n));
+                }
+                if !self.mentioned.contains(&name) {
+                    self.remove(
+                        format!("removed declaration of {}, it is never used", name),
+                        span,
+                    );
+                    return None;
+                }
+                self.remove(
+                    format!("removed initial value of {}, it is never read", name),
+                    span,
+                );
+                let mut declaration = VarDecl::new(n.left, n.type_node);
+                declaration.span = span;
+                Some(Node::VarDecl(Box::new(declaration)))
+            }
+            Node::Assign(n) => {
+                if !self.dead.contains(&(span.start, span.end)) {
+                    return Some(Node::Assign(n));
+                }
+                let name = dataflow::name(&n.left);
+                if self.types.may_fail(&n.right) {
+                    self.keep(&name, span);
+                    return Some(Node::Assign(n));
+                }
+                self.remove(
+                    format!("removed assignment to {}, its value is never read", name),
+                    span,
+                );
+                None
+            }
+            Node::ForLoop(mut n) => {
+                n.statements = self.statements(mem::take(&mut n.statements));
+                Some(Node::ForLoop(n))
+            }
+            Node::IfStatement(mut n) => {
+                n.statements = self.statements(mem::take(&mut n.statements));
+                n.else_statements = self.statements(mem::take(&mut n.else_statements));
+                Some(Node::IfStatement(n))
+            }
+            statement => Some(statement),
+        }
+    }
+
+    fn remove(&mut self, message: String, span: Span) {
+        self.removed.push(Remark {
+            pass: "dce",
+            message,
+            span,
+        });
+    }
+
+    fn keep(&mut self, name: &str, span: Span) {
+        let message = format!(
+            "kept store to {} although its value is never read, evaluating it may fail",
+            name
+        );
+        self.kept.push(Remark {
+            pass: "dce",
+            message,
+            span,
+        });
+    }
+}
+
+/// The declared types of a checked program's variables, for what the passes need to
+/// know about its expressions.
+pub(crate) struct Types {
+    variables: HashMap<String, DataType>,
+    big_integers: bool,
+}
+
+impl Types {
+    pub(crate) fn new(program: &Node, big_integers: bool) -> Self {
+        let mut types = Types {
+            variables: HashMap::new(),
+            big_integers,
+        };
+        if let Node::Program(program) = program {
+            types.declare(&program.children);
+        }
+        types
+    }
+
+    pub(crate) fn big_integers(&self) -> bool {
+        self.big_integers
+    }
+
+    pub(crate) fn variable(&self, name: &str) -> DataType {
+        self.variables[name]
+    }
+
+    pub(crate) fn is_declared(&self, name: &str) -> bool {
+        self.variables.contains_key(name)
+    }
+
+    pub(crate) fn insert(&mut self, name: String, data_type: DataType) {
+        self.variables.insert(name, data_type);
+    }
+
+    // every name is declared once, so nesting does not matter
+    fn declare(&mut self, statements: &[Node]) {
+        for statement in statements {
+            match statement {
+                Node::VarDecl(n) => {
+                    let name = dataflow::name(&n.var_node);
+                    self.variables.insert(name, n.type_node.data_type());
+                }
+                Node::DeclAssign(n) => {
+                    let name = dataflow::name(&n.left);
+                    self.variables.insert(name, n.type_node.data_type());
+                }
+                Node::ForLoop(n) => self.declare(&n.statements),
+                Node::IfStatement(n) => {
+                    self.declare(&n.statements);
+                    self.declare(&n.else_statements);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Whether evaluating `expr` may stop the program with a runtime error.
+    pub(crate) fn may_fail(&self, expr: &Node) -> bool {
+        match expr {
+            Node::BinOp(n) => {
+                if self.may_fail(&n.left) || self.may_fail(&n.right) {
+                    return true;
+                }
+                if self.type_of(expr) != DataType::Int {
+                    return false;
+                }
+                match n.op.type_ {
+                    // only a known divisor rules out division by zero and `MIN / -1`
+                    TokenType::Div | TokenType::Mod => !matches!(
+                        &n.right,
+                        Node::Num(num) if !matches!(num.value, Value::Number(0) | Value::Number(-1))
+                    ),
+                    _ => !self.big_integers,
+                }
+            }
+            Node::UnaryOp(n) => {
+                self.may_fail(&n.expr)
+                    || (n.op.type_ == TokenType::Minus
+                        && self.type_of(&n.expr) == DataType::Int
+                        && !self.big_integers
+                        && !matches!(n.expr, Node::Num(_)))
+            }
+            Node::BoolExpr(n) => self.may_fail(&n.left) || self.may_fail(&n.right),
+            // literals too large for 64 bits fail without big integers
+            Node::Num(n) => matches!(n.value, Value::BigNumber(_)) && !self.big_integers,
+            Node::Call(_) | Node::Index(_) => true,
+            _ => false,
+        }
+    }
+
+    pub(crate) fn type_of(&self, expr: &Node) -> DataType {
+        match expr {
+            Node::Num(_) => DataType::Int,
+            Node::Real(_) => DataType::Real,
+            Node::Str(_) => DataType::Str,
+            Node::Char(_) | Node::Index(_) => DataType::Char,
+            Node::BoolExpr(_) => DataType::Bool,
+            Node::Var(var) => self.variables[&dataflow::name(var)],
+            Node::UnaryOp(n) => self.type_of(&n.expr),
+            Node::Call(n) => match &n.value {
+                Value::String(name) => BUILTINS[name.to_lowercase().as_str()].returns,
+                _ => unreachable!(),
+            },
+            Node::BinOp(n) => match (self.type_of(&n.left), self.type_of(&n.right)) {
+                (DataType::Str, _) | (DataType::Char, _) => DataType::Str,
+                (DataType::Real, _) | (_, DataType::Real) => DataType::Real,
+                (left, _) => left,
+            },
+            _ => unreachable!(),
+        }
+    }
+}
+
+fn dead_stores(cfg: &Cfg) -> BTreeSet<(usize, usize)> {
+    let live = dataflow::liveness(cfg);
+    (0..cfg.points.len())
+        .filter(|&i| matches!(cfg.points[i].kind, Kind::Assignment | Kind::Declaration))
+        .filter(|&i| {
+            let def = cfg.points[i].def.as_ref().unwrap();
+            !live.after[i].contains(def)
+        })
+        .map(|i| (cfg.points[i].span.start, cfg.points[i].span.end))
+        .collect()
+}
+
+fn mentioned(cfg: &Cfg) -> BTreeSet<String> {
+    let mut names = BTreeSet::new();
+    for point in &cfg.points {
+        names.extend(point.uses.iter().cloned());
+        if point.kind != Kind::Declaration {
+            names.extend(point.def.iter().cloned());
+        }
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formatter;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    #[test]
+    fn removes_dead_stores_and_declarations() {
+        let text = "var a : int := 1;
+var b : int := 2;
+var unused : string := \"x\";
+var i : int;
+a := a * 3;
+b := 10 / 0;
+for i in 1..3 do
+    a := a + i;
+end for;
+print a;
+b := a / 2;";
+        let tree = Parser::new(Scanner::new(text.to_string())).parse().unwrap();
+        let (tree, remarks) = eliminate_dead_code(tree, false);
+        let expected = "var a : int := 1;
+var b : int;
+var i : int;
+a := a * 3;
+b := 10 / 0;
+for i in 1..3 do
+    a := a + i;
+end for;
+print a;
+";
+        assert_eq!(formatter::print(&tree), expected);
+        let remarks: Vec<String> = remarks.iter().map(Remark::to_string).collect();
+        assert_eq!(
+            remarks,
+            [
+                "2:1: remark[dce]: removed initial value of b, it is never read",
+                "3:1: remark[dce]: removed declaration of unused, it is never used",
+                "6:1: remark[dce]: kept store to b although its value is never read, evaluating it may fail",
+                "11:1: remark[dce]: removed assignment to b, its value is never read",
+            ]
+        );
+    }
+}
@@ -1,330 +1,403 @@
+use crate::error::Error;
 use crate::nodes::{
-    Assign, BinOp, BoolExpr, DeclAssign, ForLoop, IfStatement, Node, Num, PrintStr, PrintVar,
-    Program, Read, Str, Type, UnaryOp, Var, VarDecl,
+    Assign, BinOp, BoolExpr, Call, Char, DeclAssign, ForLoop, IfStatement, Index, Node, Num,
+    PrintStr, PrintVar, Program, Read, Real, Str, Type, UnaryOp, Var, VarDecl,
 };
 use crate::scanner::Scanner;
-use crate::tokens::{Token, TokenType, Value};
+use crate::tokens::{Span, Token, TokenType};
+use std::fmt::{self, Display, Formatter};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxError {
+    pub message: String,
+    pub span: Span,
+}
+
+impl Display for SyntaxError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Syntax error at {}: {}", self.span, self.message)
+    }
+}
+
+impl std::error::Error for SyntaxError {}
+
+// lexical or syntax error
+type ParseResult<T> = Result<T, Error>;
 
 pub struct Parser {
     scanner: Scanner,
     current_token: Option<Token>,
+    // end offset of the last token eaten, closes statement spans
+    previous_end: usize,
 }
 
 impl Parser {
-    pub fn new(mut scanner: Scanner) -> Self {
-        let current_token = Some(scanner.get_next_token());
+    pub fn new(scanner: Scanner) -> Self {
         Parser {
             scanner,
-            current_token,
+            current_token: None,
+            previous_end: 0,
         }
     }
 
-    pub fn parse(&mut self) -> Node {
-        let node = self.program();
+    pub fn parse(&mut self) -> ParseResult<Node> {
+        self.current_token = Some(self.scanner.get_next_token()?);
+        let node = self.program()?;
         if let TokenType::EOF = self.current_token.as_ref().unwrap().type_ {
-            return node;
+            Ok(node)
         } else {
-            self.error();
+            Err(self.error("expected ; or end of input"))
         }
-        unreachable!()
     }
 
-    fn program(&mut self) -> Node {
-        let nodes = self.statement_list();
+    fn program(&mut self) -> ParseResult<Node> {
+        let nodes = self.statement_list()?;
         let mut root = Program::new();
         for node in nodes {
             root.children.push(node);
         }
-        Node::Program(root)
+        Ok(Node::Program(root))
     }
 
-    fn statement_list(&mut self) -> Vec<Node> {
-        let node = self.statement();
+    fn statement_list(&mut self) -> ParseResult<Vec<Node>> {
+        let node = self.statement()?;
         let mut results = vec![node];
 
         while let TokenType::Semi = self.current_token.as_ref().unwrap().type_ {
-            self.eat(TokenType::Semi);
-            results.push(self.statement());
+            self.eat(TokenType::Semi)?;
+            results.push(self.statement()?);
         }
         if let TokenType::ID = self.current_token.as_ref().unwrap().type_ {
-            self.error();
+            return Err(self.error("expected ; between statements"));
         }
-        results
+        Ok(results)
     }
 
-    fn statement(&mut self) -> Node {
-        match self.current_token.as_ref().unwrap().type_ {
-            TokenType::ID => self.assignment_statement(),
-            TokenType::Var => self.declaration_statement(),
-            TokenType::Print => self.print_statement(),
-            TokenType::Read => self.read_statement(),
-            TokenType::For => self.for_loop(),
-            TokenType::If => self.if_statement(),
+    fn statement(&mut self) -> ParseResult<Node> {
+        let start = self.current_token.as_ref().unwrap().span;
+        let mut node = match self.current_token.as_ref().unwrap().type_ {
+            TokenType::ID => self.assignment_statement()?,
+            TokenType::Var => self.declaration_statement()?,
+            TokenType::Print => self.print_statement()?,
+            TokenType::Read => self.read_statement()?,
+            TokenType::For => self.for_loop()?,
+            TokenType::If => self.if_statement()?,
             _ => self.empty(),
-        }
+        };
+        node.set_span(Span {
+            end: self.previous_end,
+            ..start
+        });
+        Ok(node)
     }
 
     fn empty(&self) -> Node {
         Node::NoOp
     }
 
-    fn variable(&mut self) -> Var {
+    fn variable(&mut self) -> ParseResult<Var> {
         let node = Var::new(self.current_token.clone().unwrap());
-        self.eat(TokenType::ID);
-        node
+        self.eat(TokenType::ID)?;
+        Ok(node)
     }
 
-    fn print_statement(&mut self) -> Node {
-        let mut node = Node::NoOp;
-        self.eat(TokenType::Print);
+    fn print_statement(&mut self) -> ParseResult<Node> {
+        self.eat(TokenType::Print)?;
         match self.current_token.as_ref().unwrap().type_ {
             TokenType::ID => {
-                let var_node = self.variable();
-                node = Node::PrintVar(Box::new(PrintVar::new(var_node)))
+                let var_node = self.variable()?;
+                Ok(Node::PrintVar(Box::new(PrintVar::new(var_node))))
             }
             TokenType::StringLiteral => {
                 let string_token = self.current_token.clone().unwrap();
-                self.eat(TokenType::StringLiteral);
-                node = Node::PrintStr(Box::new(PrintStr::new(Value::String(
-                    string_token.value.to_string(),
-                ))));
+                self.eat(TokenType::StringLiteral)?;
+                Ok(Node::PrintStr(Box::new(PrintStr::new(string_token))))
             }
-            _ => self.error(),
+            _ => Err(self.error("expected a variable or a string literal after print")),
         }
-        node
     }
 
-    fn read_statement(&mut self) -> Node {
-        let mut node = Node::NoOp;
-        self.eat(TokenType::Read);
-        match self.current_token.as_ref().unwrap().type_ {
-            TokenType::ID => {
-                let var_node = self.variable();
-                node = Node::Read(Box::new(Read::new(var_node)))
-            }
-            _ => self.error(),
-        }
-        node
+    fn read_statement(&mut self) -> ParseResult<Node> {
+        self.eat(TokenType::Read)?;
+        let var_node = self.variable()?;
+        Ok(Node::Read(Box::new(Read::new(var_node))))
     }
 
-    fn assignment_statement(&mut self) -> Node {
-        let left = self.variable();
+    fn assignment_statement(&mut self) -> ParseResult<Node> {
+        let left = self.variable()?;
         let token = self.current_token.clone().unwrap();
-        self.eat(TokenType::Assign);
-        let right = self.expr();
-        Node::Assign(Box::new(Assign::new(left, token, right)))
+        self.eat(TokenType::Assign)?;
+        let right = self.expr()?;
+        Ok(Node::Assign(Box::new(Assign::new(left, token, right))))
     }
 
-    fn declaration_statement(&mut self) -> Node {
-        let mut node = Node::NoOp;
-        if let TokenType::Var = self.current_token.as_ref().unwrap().type_ {
-            self.eat(TokenType::Var);
-            let var_node = self.variable();
-            self.eat(TokenType::Colon);
-            match self.current_token.as_ref().unwrap().type_ {
-                TokenType::Integer => {
-                    let type_node = Type::new(self.current_token.clone().unwrap());
-                    self.eat(TokenType::Integer);
-                    match self.current_token.as_ref().unwrap().type_ {
-                        TokenType::Semi => {
-                            node = Node::VarDecl(Box::new(VarDecl::new(var_node, type_node)));
-                        }
-                        TokenType::Assign => {
-                            let token = self.current_token.clone().unwrap();
-                            self.eat(TokenType::Assign);
-                            let right = self.expr();
-                            node = Node::DeclAssign(Box::new(DeclAssign::new(
-                                var_node, type_node, token, right,
-                            )));
-                        }
-                        _ => self.error(),
-                    }
-                }
-                TokenType::Str => {
-                    let type_node = Type::new(self.current_token.clone().unwrap());
-                    self.eat(TokenType::Str);
-                    match self.current_token.as_ref().unwrap().type_ {
-                        TokenType::Semi => {
-                            //no assign
-                            node = Node::VarDecl(Box::new(VarDecl::new(var_node, type_node)));
-                        }
-                        TokenType::Assign => {
-                            //declaration assignment
-                            let token = self.current_token.clone().unwrap();
-                            self.eat(TokenType::Assign);
-                            let string_token = self.current_token.clone().unwrap();
-                            let right = match self.current_token.as_ref().unwrap().type_ {
-                                TokenType::StringLiteral => {
-                                    self.eat(TokenType::StringLiteral);
-                                    Node::Str(Str::new(string_token))
-                                }
-                                _ => self.expr(),
-                            };
-                            node = Node::DeclAssign(Box::new(DeclAssign::new(
-                                var_node, type_node, token, right,
-                            )));
-                        }
-                        _ => self.error(),
-                    }
-                }
-                TokenType::Bool => {
-                    let type_node = Type::new(self.current_token.clone().unwrap());
-                    self.eat(TokenType::Bool);
-                    match self.current_token.as_ref().unwrap().type_ {
-                        TokenType::Semi => {
-                            //no assign
-                            node = Node::VarDecl(Box::new(VarDecl::new(var_node, type_node)));
-                        }
-                        TokenType::Assign => {
-                            //declaration assignment
-                            let token = self.current_token.clone().unwrap();
-                            self.eat(TokenType::Assign);
-                            let right = self.bool_expr();
-                            node = Node::DeclAssign(Box::new(DeclAssign::new(
-                                var_node, type_node, token, right,
-                            )));
-                        }
-                        _ => self.error(),
+    fn declaration_statement(&mut self) -> ParseResult<Node> {
+        self.eat(TokenType::Var)?;
+        let var_node = self.variable()?;
+        self.eat(TokenType::Colon)?;
+        let type_token = self.current_token.clone().unwrap();
+        match type_token.type_ {
+            TokenType::Integer
+            | TokenType::Str
+            | TokenType::Bool
+            | TokenType::Char
+            | TokenType::Real => {
+                let type_node = Type::new(type_token.clone());
+                self.eat(type_token.type_)?;
+                match self.current_token.as_ref().unwrap().type_ {
+                    TokenType::Assign => {
+                        //declaration assignment
+                        let token = self.current_token.clone().unwrap();
+                        self.eat(TokenType::Assign)?;
+                        let right = self.expr()?;
+                        Ok(Node::DeclAssign(Box::new(DeclAssign::new(
+                            var_node, type_node, token, right,
+                        ))))
                     }
+                    //no assign
+                    _ => Ok(Node::VarDecl(Box::new(VarDecl::new(var_node, type_node)))),
                 }
-                _ => self.error(),
             }
+            _ => Err(self.error("expected a type")),
         }
-        node
     }
 
-    fn if_statement(&mut self) -> Node {
-        self.eat(TokenType::If);
-        let bool_expr = self.bool_expr();
-        self.eat(TokenType::Do);
-        let statements = self.statement_list();
-        match self.current_token.clone().unwrap().type_ {
-            TokenType::Else => {
-                self.eat(TokenType::Else);
-            }
-            _ => {}
-        };
-        let else_statements = self.statement_list();
-        let node = Node::IfStatement(Box::new(IfStatement::new(
-            bool_expr,
-            statements,
-            else_statements,
-        )));
-        self.eat(TokenType::End);
-        self.eat(TokenType::If);
-        node
+    fn if_statement(&mut self) -> ParseResult<Node> {
+        self.eat(TokenType::If)?;
+        let bool_expr = self.expr()?;
+        self.eat(TokenType::Do)?;
+        let statements = self.statement_list()?;
+        let mut else_span = None;
+        if let TokenType::Else = self.current_token.as_ref().unwrap().type_ {
+            else_span = Some(self.current_token.as_ref().unwrap().span);
+            self.eat(TokenType::Else)?;
+        }
+        let else_statements = self.statement_list()?;
+        let mut if_statement = IfStatement::new(bool_expr, statements, else_statements);
+        if_statement.else_span = else_span;
+        self.eat(TokenType::End)?;
+        self.eat(TokenType::If)?;
+        Ok(Node::IfStatement(Box::new(if_statement)))
     }
 
-    fn for_loop(&mut self) -> Node {
-        let mut node = Node::NoOp;
-        self.eat(TokenType::For);
-        let var = self.variable();
-        self.eat(TokenType::In);
-        let start = self.expr();
-        self.eat(TokenType::To);
-        let end = self.expr();
-        self.eat(TokenType::Do);
-        let statements = self.statement_list();
-        if !statements.is_empty() {
-            node = Node::ForLoop(Box::new(ForLoop::new(var, start, end, statements)));
-        }
-        self.eat(TokenType::End);
-        self.eat(TokenType::For);
-        node
+    fn for_loop(&mut self) -> ParseResult<Node> {
+        self.eat(TokenType::For)?;
+        let var = self.variable()?;
+        self.eat(TokenType::In)?;
+        let start = self.expr()?;
+        self.eat(TokenType::To)?;
+        let end = self.expr()?;
+        self.eat(TokenType::Do)?;
+        let statements = self.statement_list()?;
+        self.eat(TokenType::End)?;
+        self.eat(TokenType::For)?;
+        Ok(Node::ForLoop(Box::new(ForLoop::new(
+            var, start, end, statements,
+        ))))
     }
 
-    fn factor(&mut self) -> Node {
+    fn factor(&mut self) -> ParseResult<Node> {
         let token = self.current_token.clone().unwrap();
         match &token.type_ {
             TokenType::Plus => {
-                self.eat(TokenType::Plus);
-                Node::UnaryOp(Box::new(UnaryOp::new(token, self.factor())))
+                self.eat(TokenType::Plus)?;
+                Ok(Node::UnaryOp(Box::new(UnaryOp::new(token, self.factor()?))))
             }
             TokenType::Minus => {
-                self.eat(TokenType::Minus);
-                Node::UnaryOp(Box::new(UnaryOp::new(token, self.factor())))
+                self.eat(TokenType::Minus)?;
+                Ok(Node::UnaryOp(Box::new(UnaryOp::new(token, self.factor()?))))
             }
-            TokenType::Integer => {
-                self.eat(TokenType::Integer);
-                Node::Num(Num::new(token))
-            }
-            TokenType::LeftParen => {
-                self.eat(TokenType::LeftParen);
-                let node = self.expr();
-                self.eat(TokenType::RightParen);
-                node
+            TokenType::Not => {
+                self.eat(TokenType::Not)?;
+                Ok(Node::BoolExpr(Box::new(BoolExpr::new(
+                    Node::NoOp,
+                    token,
+                    self.factor()?,
+                ))))
             }
-            _ => Node::Var(self.variable()),
+            _ => self.postfix(),
         }
     }
 
-    fn term(&mut self) -> Node {
-        let mut node = self.factor();
+    fn postfix(&mut self) -> ParseResult<Node> {
+        let mut node = self.primary()?;
 
-        while let TokenType::Mul | TokenType::Div = self.current_token.as_ref().unwrap().type_ {
+        while let TokenType::LeftBracket = self.current_token.as_ref().unwrap().type_ {
             let token = self.current_token.clone().unwrap();
-            match token.type_ {
-                TokenType::Mul => self.eat(TokenType::Mul),
-                TokenType::Div => self.eat(TokenType::Div),
-                _ => unimplemented!(),
-            }
-            node = Node::BinOp(Box::new(BinOp::new(node, token, self.factor())));
+            self.eat(TokenType::LeftBracket)?;
+            let index = self.expr()?;
+            self.eat(TokenType::RightBracket)?;
+            node = Node::Index(Box::new(Index::new(token, node, index)));
         }
-        node
+        Ok(node)
     }
 
-    fn bool_expr(&mut self) -> Node {
-        let mut token = self.current_token.clone().unwrap();
+    fn primary(&mut self) -> ParseResult<Node> {
+        let token = self.current_token.clone().unwrap();
         match &token.type_ {
-            TokenType::Not => {
-                self.eat(TokenType::Not);
-                let right = self.expr();
-                return Node::BoolExpr(Box::new(BoolExpr::new(Node::NoOp, token, right)));
+            TokenType::Integer => {
+                self.eat(TokenType::Integer)?;
+                Ok(Node::Num(Num::new(token)))
+            }
+            TokenType::RealLiteral => {
+                self.eat(TokenType::RealLiteral)?;
+                Ok(Node::Real(Real::new(token)))
+            }
+            TokenType::StringLiteral => {
+                self.eat(TokenType::StringLiteral)?;
+                Ok(Node::Str(Str::new(token)))
+            }
+            TokenType::CharLiteral => {
+                self.eat(TokenType::CharLiteral)?;
+                Ok(Node::Char(Char::new(token)))
             }
-            _ => {}
+            TokenType::LeftParen => {
+                self.eat(TokenType::LeftParen)?;
+                let node = self.expr()?;
+                self.eat(TokenType::RightParen)?;
+                Ok(node)
+            }
+            TokenType::ID => {
+                let var_node = self.variable()?;
+                match self.current_token.as_ref().unwrap().type_ {
+                    TokenType::LeftParen => self.call(var_node.token),
+                    _ => Ok(Node::Var(var_node)),
+                }
+            }
+            _ => Err(self.error("expected an expression")),
         }
-        let left = self.expr();
-        token = self.current_token.clone().unwrap();
-        match &token.type_ {
-            TokenType::LessThan => self.eat(TokenType::LessThan),
-            TokenType::Equal => self.eat(TokenType::Equal),
-            TokenType::And => self.eat(TokenType::And),
-            _ => {
-                return Node::BoolExpr(Box::new(BoolExpr::new(
-                    left,
-                    Token::new(TokenType::Semi, Value::None),
-                    Node::NoOp,
-                )))
+    }
+
+    fn call(&mut self, token: Token) -> ParseResult<Node> {
+        let mut args = Vec::new();
+        self.eat(TokenType::LeftParen)?;
+        if self.current_token.as_ref().unwrap().type_ != TokenType::RightParen {
+            args.push(self.expr()?);
+            while let TokenType::Comma = self.current_token.as_ref().unwrap().type_ {
+                self.eat(TokenType::Comma)?;
+                args.push(self.expr()?);
             }
         }
-        let right = self.expr();
-        Node::BoolExpr(Box::new(BoolExpr::new(left, token, right)))
+        self.eat(TokenType::RightParen)?;
+        Ok(Node::Call(Box::new(Call::new(token, args))))
     }
 
-    fn expr(&mut self) -> Node {
-        let mut node = self.term();
+    fn term(&mut self) -> ParseResult<Node> {
+        let mut node = self.factor()?;
+
+        while let TokenType::Mul | TokenType::Div | TokenType::Mod =
+            self.current_token.as_ref().unwrap().type_
+        {
+            let token = self.current_token.clone().unwrap();
+            self.eat(token.type_.clone())?;
+            node = Node::BinOp(Box::new(BinOp::new(node, token, self.factor()?)));
+        }
+        Ok(node)
+    }
+
+    fn simple_expr(&mut self) -> ParseResult<Node> {
+        let mut node = self.term()?;
 
         while let TokenType::Plus | TokenType::Minus = self.current_token.as_ref().unwrap().type_ {
             let token = self.current_token.clone().unwrap();
-            match token.type_ {
-                TokenType::Plus => self.eat(TokenType::Plus),
-                TokenType::Minus => self.eat(TokenType::Minus),
-                _ => unimplemented!(),
+            self.eat(token.type_.clone())?;
+            node = Node::BinOp(Box::new(BinOp::new(node, token, self.term()?)));
+        }
+        Ok(node)
+    }
+
+    // comparisons do not chain, `a < b < c` is a syntax error
+    fn comparison(&mut self) -> ParseResult<Node> {
+        let left = self.simple_expr()?;
+        let token = self.current_token.clone().unwrap();
+        match token.type_ {
+            TokenType::Equal
+            | TokenType::NotEqual
+            | TokenType::LessThan
+            | TokenType::LessEqual
+            | TokenType::GreaterThan
+            | TokenType::GreaterEqual => {
+                self.eat(token.type_.clone())?;
+                let right = self.simple_expr()?;
+                Ok(Node::BoolExpr(Box::new(BoolExpr::new(left, token, right))))
             }
-            node = Node::BinOp(Box::new(BinOp::new(node, token, self.term())));
+            _ => Ok(left),
         }
-        node
     }
 
-    fn error(&self) {
-        panic!("Syntax error");
+    fn conjunction(&mut self) -> ParseResult<Node> {
+        let mut node = self.comparison()?;
+
+        while let TokenType::And = self.current_token.as_ref().unwrap().type_ {
+            let token = self.current_token.clone().unwrap();
+            self.eat(TokenType::And)?;
+            node = Node::BoolExpr(Box::new(BoolExpr::new(node, token, self.comparison()?)));
+        }
+        Ok(node)
     }
 
-    fn eat(&mut self, token_type: TokenType) {
+    fn expr(&mut self) -> ParseResult<Node> {
+        let mut node = self.conjunction()?;
+
+        while let TokenType::Or = self.current_token.as_ref().unwrap().type_ {
+            let token = self.current_token.clone().unwrap();
+            self.eat(TokenType::Or)?;
+            node = Node::BoolExpr(Box::new(BoolExpr::new(node, token, self.conjunction()?)));
+        }
+        Ok(node)
+    }
+
+    // located at the current token
+    fn error(&self, message: &str) -> Error {
+        let token = self.current_token.as_ref().unwrap();
+        let found = match token.type_ {
+            TokenType::EOF => String::from("end of input"),
+            _ => format!("'{}'", token.value),
+        };
+        Error::Syntax(SyntaxError {
+            message: format!("{}, found {}", message, found),
+            span: token.span,
+        })
+    }
+
+    fn eat(&mut self, token_type: TokenType) -> ParseResult<()> {
         if self.current_token.as_ref().unwrap().type_ == token_type {
-            self.current_token = Some(self.scanner.get_next_token());
+            self.previous_end = self.current_token.as_ref().unwrap().span.end;
+            self.current_token = Some(self.scanner.get_next_token()?);
+            Ok(())
         } else {
-            self.error();
+            Err(self.error(&format!("expected {}", token_type)))
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> ParseResult<Node> {
+        Parser::new(Scanner::new(text.to_string())).parse()
+    }
+
+    #[test]
+    fn reports_located_errors() {
+        assert!(parse("var x : int := 1; print x;").is_ok());
+        let error = |text: &str| parse(text).unwrap_err().to_string();
+        assert_eq!(
+            error("var x : int := 1\nprint x;"),
+            "Syntax error at 2:1: expected ; or end of input, found 'print'"
+        );
+        assert_eq!(
+            error("for i in 1..3 do print i; end if;"),
+            "Syntax error at 1:31: expected for, found 'if'"
+        );
+        assert_eq!(
+            error("x := (1 + ;"),
+            "Syntax error at 1:11: expected an expression, found ';'"
+        );
+        assert_eq!(
+            error("var s : string := \"open"),
+            "Lexical error at 1:19: unterminated string literal"
+        );
+    }
+}
@@ -0,0 +1,275 @@
+use crate::nodes::Node;
+use crate::tokens::Span;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+/// Executions and time of one statement. `total` includes the statements nested
+/// in a `for` or `if`, `self_time` does not.
+#[derive(Debug, Clone)]
+pub struct StatementProfile {
+    pub kind: &'static str,
+    pub span: Span,
+    pub count: usize,
+    pub total: Duration,
+    pub self_time: Duration,
+}
+
+/// Executions and self time of the statements starting on one line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineProfile {
+    pub line: usize,
+    pub count: usize,
+    pub self_time: Duration,
+}
+
+struct Frame {
+    // `None` for empty statements, which are not profiled
+    start: Option<usize>,
+    label: String,
+    entered: Instant,
+    children: Duration,
+}
+
+/// Counts executions and measures time per statement while a program runs.
+#[derive(Default)]
+pub struct Profiler {
+    // keyed by the statement's start offset
+    statements: HashMap<usize, StatementProfile>,
+    stack: Vec<Frame>,
+    // self time per path of enclosing statements
+    stacks: HashMap<String, Duration>,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Profiler {
+            statements: HashMap::new(),
+            stack: Vec::new(),
+            stacks: HashMap::new(),
+        }
+    }
+
+    pub fn enter(&mut self, statement: &Node) {
+        let span = match statement.source_span() {
+            Some(span) => span,
+            None => {
+                self.stack.push(Frame {
+                    start: None,
+                    label: String::new(),
+                    entered: Instant::now(),
+                    children: Duration::default(),
+                });
+                return;
+            }
+        };
+        let kind = kind(statement);
+        self.stack.push(Frame {
+            start: Some(span.start),
+            label: format!("{}@{}", kind, span),
+            entered: Instant::now(),
+            children: Duration::default(),
+        });
+        self.statements
+            .entry(span.start)
+            .or_insert(StatementProfile {
+                kind,
+                span,
+                count: 0,
+                total: Duration::default(),
+                self_time: Duration::default(),
+            })
+            .count += 1;
+    }
+
+    pub fn exit(&mut self) {
+        let (frame, start) = match self.stack.pop() {
+            Some(
+                frame @ Frame {
+                    start: Some(start), ..
+                },
+            ) => (frame, start),
+            _ => return,
+        };
+        let elapsed = frame.entered.elapsed();
+        let self_time = elapsed.saturating_sub(frame.children);
+        if let Some(parent) = self.stack.last_mut() {
+            parent.children += elapsed;
+        }
+        let statement = self.statements.get_mut(&start).unwrap();
+        statement.total += elapsed;
+        statement.self_time += self_time;
+
+        let mut path = String::from("program");
+        for enclosing in &self.stack {
+            path.push(';');
+            path.push_str(&enclosing.label);
+        }
+        path.push(';');
+        path.push_str(&frame.label);
+        *self.stacks.entry(path).or_default() += self_time;
+    }
+
+    /// Statements by descending total time.
+    pub fn statements(&self) -> Vec<StatementProfile> {
+        let mut statements: Vec<StatementProfile> = self.statements.values().cloned().collect();
+        statements.sort_by(|a, b| b.total.cmp(&a.total).then(a.span.start.cmp(&b.span.start)));
+        statements
+    }
+
+    /// Lines by descending self time.
+    pub fn lines(&self) -> Vec<LineProfile> {
+        let mut lines: HashMap<usize, LineProfile> = HashMap::new();
+        for statement in self.statements.values() {
+            let line = lines.entry(statement.span.line).or_insert(LineProfile {
+                line: statement.span.line,
+                count: 0,
+                self_time: Duration::default(),
+            });
+            line.count += statement.count;
+            line.self_time += statement.self_time;
+        }
+        let mut lines: Vec<LineProfile> = lines.into_values().collect();
+        lines.sort_by(|a, b| b.self_time.cmp(&a.self_time).then(a.line.cmp(&b.line)));
+        lines
+    }
+
+    /// The hot-spot report, `source` is the program the profile was taken of.
+    pub fn report(&self, source: &str) -> String {
+        let source: Vec<&str> = source.lines().collect();
+        let text = |line: usize| {
+            line.checked_sub(1)
+                .and_then(|i| source.get(i))
+                .map_or("", |text| text.trim())
+        };
+        let mut report = String::from("statements by total time\n");
+        report.push_str("     count    total ms     self ms  location  statement\n");
+        for s in self.statements() {
+            let _ = writeln!(
+                report,
+                "{:>10} {:>11.3} {:>11.3}  {:<8}  {}",
+                s.count,
+                milliseconds(s.total),
+                milliseconds(s.self_time),
+                s.span.to_string(),
+                text(s.span.line)
+            );
+        }
+        report.push_str("\nlines by self time\n");
+        report.push_str("     count     self ms  line\n");
+        for l in self.lines() {
+            let _ = writeln!(
+                report,
+                "{:>10} {:>11.3}  {:>4} | {}",
+                l.count,
+                milliseconds(l.self_time),
+                l.line,
+                text(l.line)
+            );
+        }
+        report
+    }
+
+    /// Folded stacks for flame graph tools: the enclosing statements separated by
+    /// `;` and the self time in nanoseconds.
+    pub fn folded(&self) -> String {
+        let mut stacks: Vec<(&String, &Duration)> = self.stacks.iter().collect();
+        stacks.sort();
+        stacks
+            .into_iter()
+            .map(|(path, time)| format!("{} {}\n", path, time.as_nanos()))
+            .collect()
+    }
+}
+
+fn kind(statement: &Node) -> &'static str {
+    match statement {
+        Node::VarDecl(_) | Node::DeclAssign(_) => "var",
+        Node::Assign(_) => "assign",
+        Node::Read(_) => "read",
+        Node::PrintVar(_) | Node::PrintStr(_) => "print",
+        Node::ForLoop(_) => "for",
+        Node::IfStatement(_) => "if",
+        _ => unreachable!(),
+    }
+}
+
+fn milliseconds(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Interpreter;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    #[test]
+    fn counts_statements_and_lines() {
+        let text = "var n : int := 0;
+var i : int;
+for i in 0..99 do
+    if i % 3 = 0 do n := n + i; end if;
+end for;";
+        let mut interpreter = Interpreter::new(Parser::new(Scanner::new(text.to_string())));
+        interpreter.profiler = Some(Profiler::new());
+        interpreter.interpret().unwrap();
+        let profiler = interpreter.profiler.unwrap();
+
+        let mut counts: Vec<(String, usize)> = profiler
+            .statements()
+            .iter()
+            .map(|s| (format!("{} {}", s.kind, s.span), s.count))
+            .collect();
+        counts.sort();
+        assert_eq!(
+            counts,
+            [
+                (String::from("assign 4:21"), 34),
+                (String::from("for 3:1"), 1),
+                (String::from("if 4:5"), 100),
+                (String::from("var 1:1"), 1),
+                (String::from("var 2:1"), 1),
+            ]
+        );
+        // the loop encloses everything else it ran
+        assert_eq!(profiler.statements()[0].kind, "for");
+        let line = profiler.lines().into_iter().find(|l| l.line == 4).unwrap();
+        assert_eq!(line.count, 134);
+
+        let folded = profiler.folded();
+        let stacks: Vec<&str> = folded
+            .lines()
+            .map(|line| line.rsplit_once(' ').unwrap().0)
+            .collect();
+        assert_eq!(
+            stacks,
+            [
+                "program;for@3:1",
+                "program;for@3:1;if@4:5",
+                "program;for@3:1;if@4:5;assign@4:21",
+                "program;var@1:1",
+                "program;var@2:1",
+            ]
+        );
+        let report = profiler.report(text);
+        assert!(report.contains("   4 | if i % 3 = 0 do n := n + i; end if;"));
+    }
+
+    #[test]
+    fn skips_the_declarations_of_temporaries() {
+        let text = "var s : int;
+var i : int;
+for i in 1..3 do
+    s := s + i * 4;
+end for;";
+        let mut interpreter = Interpreter::new(Parser::new(Scanner::new(text.to_string())));
+        interpreter.optimize_loops = true;
+        interpreter.profiler = Some(Profiler::new());
+        interpreter.interpret().unwrap();
+        let profiler = interpreter.profiler.unwrap();
+        assert!(profiler.statements().iter().all(|s| s.span.line > 0));
+        assert!(profiler.report(text).contains("   4 | s := s + i * 4;"));
+    }
+}
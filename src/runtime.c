@@ -0,0 +1,630 @@
+// Runtime for programs compiled by `mini-pl compile`, see src/llvm.rs. It prints,
+// reads and implements the builtins exactly as the interpreter does, and stops the
+// program with the interpreter's messages:
+//
+//     cc program.o runtime.c -lm
+#define _GNU_SOURCE
+#include <inttypes.h>
+#include <locale.h>
+#include <math.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <wctype.h>
+
+// UTF-8, not NUL terminated; strings are never changed once made
+typedef struct {
+    int64_t length;
+    const char *bytes;
+} mpl_string;
+
+static mpl_string *new_string(const char *bytes, int64_t length) {
+    mpl_string *s = malloc(sizeof(mpl_string) + length);
+    if (s == NULL) {
+        fputs("out of memory\n", stderr);
+        exit(1);
+    }
+    memcpy(s + 1, bytes, length);
+    s->bytes = (const char *)(s + 1);
+    s->length = length;
+    return s;
+}
+
+// errors are printed as `Runtime error at line:column: message`
+static void error_start(int32_t line, int32_t column) {
+    fflush(stdout);
+    fprintf(stderr, "Runtime error at %d:%d: ", line, column);
+}
+
+static _Noreturn void error_end(void) {
+    fputc('\n', stderr);
+    exit(1);
+}
+
+_Noreturn void mpl_overflow(int32_t op, int64_t left, int64_t right, int32_t line,
+                            int32_t column) {
+    error_start(line, column);
+    fprintf(stderr, "integer overflow in %" PRId64 " %c %" PRId64, left, op, right);
+    error_end();
+}
+
+_Noreturn void mpl_division_by_zero(int32_t line, int32_t column) {
+    error_start(line, column);
+    fputs("division by zero", stderr);
+    error_end();
+}
+
+_Noreturn void mpl_too_large(int64_t n, int32_t bits, int32_t line, int32_t column) {
+    error_start(line, column);
+    fprintf(stderr, "integer %" PRId64 " does not fit in %d bits", n, bits);
+    error_end();
+}
+
+// characters
+
+static int64_t char_count(const mpl_string *s) {
+    int64_t count = 0;
+    for (int64_t i = 0; i < s->length; i++) {
+        count += ((unsigned char)s->bytes[i] & 0xC0) != 0x80;
+    }
+    return count;
+}
+
+// the byte offset of character `index`, which must be at most the length
+static int64_t byte_offset(const mpl_string *s, int64_t index) {
+    int64_t i = 0;
+    for (; index > 0; index--) {
+        i++;
+        while (i < s->length && ((unsigned char)s->bytes[i] & 0xC0) == 0x80) {
+            i++;
+        }
+    }
+    return i;
+}
+
+static int32_t decode(const char *p, int *length) {
+    const unsigned char *b = (const unsigned char *)p;
+    if (b[0] < 0x80) {
+        *length = 1;
+        return b[0];
+    }
+    if (b[0] < 0xE0) {
+        *length = 2;
+        return (b[0] & 0x1F) << 6 | (b[1] & 0x3F);
+    }
+    if (b[0] < 0xF0) {
+        *length = 3;
+        return (b[0] & 0x0F) << 12 | (b[1] & 0x3F) << 6 | (b[2] & 0x3F);
+    }
+    *length = 4;
+    return (b[0] & 0x07) << 18 | (b[1] & 0x3F) << 12 | (b[2] & 0x3F) << 6 | (b[3] & 0x3F);
+}
+
+static int encode(int32_t c, char *out) {
+    if (c < 0x80) {
+        out[0] = c;
+        return 1;
+    }
+    if (c < 0x800) {
+        out[0] = 0xC0 | c >> 6;
+        out[1] = 0x80 | (c & 0x3F);
+        return 2;
+    }
+    if (c < 0x10000) {
+        out[0] = 0xE0 | c >> 12;
+        out[1] = 0x80 | (c >> 6 & 0x3F);
+        out[2] = 0x80 | (c & 0x3F);
+        return 3;
+    }
+    out[0] = 0xF0 | c >> 18;
+    out[1] = 0x80 | (c >> 12 & 0x3F);
+    out[2] = 0x80 | (c >> 6 & 0x3F);
+    out[3] = 0x80 | (c & 0x3F);
+    return 4;
+}
+
+// reals are shown as Rust's `{:?}` shows them: the shortest digits that read back
+// as the same value, with a fraction, and with an exponent when tiny or huge
+static void format_real(double x, char *out) {
+    if (isnan(x)) {
+        strcpy(out, "NaN");
+        return;
+    }
+    if (isinf(x)) {
+        strcpy(out, x < 0 ? "-inf" : "inf");
+        return;
+    }
+    if (x == 0) {
+        strcpy(out, signbit(x) ? "-0.0" : "0.0");
+        return;
+    }
+    char scientific[32];
+    for (int precision = 0; precision < 17; precision++) {
+        snprintf(scientific, sizeof scientific, "%.*e", precision, x);
+        if (strtod(scientific, NULL) == x) {
+            break;
+        }
+    }
+    // split `-d.ddde-5` into its sign, digits without trailing zeros and exponent
+    char digits[32];
+    int count = 0;
+    char *p = scientific;
+    if (*p == '-') {
+        *out++ = '-';
+        p++;
+    }
+    for (; *p != 'e'; p++) {
+        if (*p != '.') {
+            digits[count++] = *p;
+        }
+    }
+    int exponent = atoi(p + 1);
+    while (count > 1 && digits[count - 1] == '0') {
+        count--;
+    }
+    double magnitude = fabs(x);
+    if (magnitude < 1e-4 || magnitude >= 1e16) {
+        *out++ = digits[0];
+        if (count > 1) {
+            *out++ = '.';
+            memcpy(out, digits + 1, count - 1);
+            out += count - 1;
+        }
+        sprintf(out, "e%d", exponent);
+        return;
+    }
+    if (exponent < 0) {
+        *out++ = '0';
+        *out++ = '.';
+        for (int i = -1; i > exponent; i--) {
+            *out++ = '0';
+        }
+        memcpy(out, digits, count);
+        out[count] = '\0';
+        return;
+    }
+    for (int i = 0; i <= exponent; i++) {
+        *out++ = i < count ? digits[i] : '0';
+    }
+    *out++ = '.';
+    if (count <= exponent + 1) {
+        *out++ = '0';
+    }
+    for (int i = exponent + 1; i < count; i++) {
+        *out++ = digits[i];
+    }
+    *out = '\0';
+}
+
+// print
+
+void mpl_print_int(int64_t n) {
+    printf("%" PRId64 "\n", n);
+}
+
+void mpl_print_real(double x) {
+    char text[40];
+    format_real(x, text);
+    puts(text);
+}
+
+void mpl_print_bool(int32_t b) {
+    puts(b ? "true" : "false");
+}
+
+void mpl_print_char(int32_t c) {
+    char bytes[4];
+    fwrite(bytes, 1, encode(c, bytes), stdout);
+    putchar('\n');
+}
+
+void mpl_print_string(const mpl_string *s) {
+    fwrite(s->bytes, 1, s->length, stdout);
+    putchar('\n');
+}
+
+// read
+
+// the next line without its line break, empty at the end of input
+static mpl_string *read_line(void) {
+    char *line = NULL;
+    size_t capacity = 0;
+    ssize_t length = getline(&line, &capacity, stdin);
+    if (length < 0) {
+        free(line);
+        return new_string("", 0);
+    }
+    if (length > 0 && line[length - 1] == '\n') {
+        length--;
+    }
+    mpl_string *s = new_string(line, length);
+    free(line);
+    return s;
+}
+
+static int is_space(char c) {
+    return c == ' ' || (c >= '\t' && c <= '\r');
+}
+
+static mpl_string trim(const mpl_string *s) {
+    int64_t start = 0, end = s->length;
+    while (start < end && is_space(s->bytes[start])) {
+        start++;
+    }
+    while (end > start && is_space(s->bytes[end - 1])) {
+        end--;
+    }
+    mpl_string trimmed = {end - start, s->bytes + start};
+    return trimmed;
+}
+
+enum parsed { PARSED, TOO_LARGE, NOT_A_NUMBER };
+
+// an optional sign and decimal digits, as Rust's `i64::from_str`
+static enum parsed parse_int(mpl_string s, int64_t *n) {
+    int64_t i = 0;
+    int negative = 0;
+    if (s.length > 0 && (s.bytes[0] == '-' || s.bytes[0] == '+')) {
+        negative = s.bytes[0] == '-';
+        i++;
+    }
+    if (i == s.length) {
+        return NOT_A_NUMBER;
+    }
+    int64_t value = 0;
+    enum parsed result = PARSED;
+    for (; i < s.length; i++) {
+        char c = s.bytes[i];
+        if (c < '0' || c > '9') {
+            return NOT_A_NUMBER;
+        }
+        // accumulated negatively so the smallest int can be read
+        int digit = c - '0';
+        if (__builtin_mul_overflow(value, 10, &value) ||
+            __builtin_sub_overflow(value, digit, &value)) {
+            result = TOO_LARGE;
+        }
+    }
+    if (result == PARSED && !negative && __builtin_sub_overflow(0, value, &value)) {
+        result = TOO_LARGE;
+    }
+    *n = value;
+    return result;
+}
+
+// what Rust's `f64::from_str` accepts: decimal notation or inf, infinity and nan
+static int is_real(mpl_string s) {
+    int64_t i = 0;
+    if (i < s.length && (s.bytes[i] == '-' || s.bytes[i] == '+')) {
+        i++;
+    }
+    const char *words[] = {"inf", "infinity", "nan"};
+    for (int w = 0; w < 3; w++) {
+        int64_t length = strlen(words[w]);
+        if (s.length - i == length && strncasecmp(s.bytes + i, words[w], length) == 0) {
+            return 1;
+        }
+    }
+    int64_t digits = 0;
+    for (; i < s.length && s.bytes[i] >= '0' && s.bytes[i] <= '9'; i++) {
+        digits++;
+    }
+    if (i < s.length && s.bytes[i] == '.') {
+        for (i++; i < s.length && s.bytes[i] >= '0' && s.bytes[i] <= '9'; i++) {
+            digits++;
+        }
+    }
+    if (digits == 0) {
+        return 0;
+    }
+    if (i < s.length && (s.bytes[i] == 'e' || s.bytes[i] == 'E')) {
+        i++;
+        if (i < s.length && (s.bytes[i] == '-' || s.bytes[i] == '+')) {
+            i++;
+        }
+        int64_t start = i;
+        for (; i < s.length && s.bytes[i] >= '0' && s.bytes[i] <= '9'; i++) {
+        }
+        if (i == start) {
+            return 0;
+        }
+    }
+    return i == s.length;
+}
+
+// as Rust's `{:?}` shows a string, for the exotic characters escaped by code
+static void write_debug(const mpl_string *s) {
+    fputc('"', stderr);
+    for (int64_t i = 0; i < s->length; i++) {
+        unsigned char c = s->bytes[i];
+        switch (c) {
+        case '"':
+            fputs("\\\"", stderr);
+            break;
+        case '\\':
+            fputs("\\\\", stderr);
+            break;
+        case '\n':
+            fputs("\\n", stderr);
+            break;
+        case '\r':
+            fputs("\\r", stderr);
+            break;
+        case '\t':
+            fputs("\\t", stderr);
+            break;
+        case '\0':
+            fputs("\\0", stderr);
+            break;
+        default:
+            if (c < 0x20 || c == 0x7F) {
+                fprintf(stderr, "\\u{%x}", c);
+            } else {
+                fputc(c, stderr);
+            }
+        }
+    }
+    fputc('"', stderr);
+}
+
+static _Noreturn void not_numeric(const mpl_string *input, int32_t line, int32_t column) {
+    error_start(line, column);
+    fputs("cannot read non-numeric value ", stderr);
+    write_debug(input);
+    fputs(" into numeric variable", stderr);
+    error_end();
+}
+
+int64_t mpl_read_int(int32_t line, int32_t column) {
+    mpl_string *input = read_line();
+    mpl_string trimmed = trim(input);
+    int64_t n;
+    switch (parse_int(trimmed, &n)) {
+    case PARSED:
+        return n;
+    case TOO_LARGE: {
+        // shown as the interpreter's big integers are: no plus sign or leading zeros
+        error_start(line, column);
+        fputs("integer ", stderr);
+        const char *digits = trimmed.bytes;
+        int64_t length = trimmed.length;
+        if (*digits == '-') {
+            fputc('-', stderr);
+        }
+        if (*digits == '-' || *digits == '+') {
+            digits++;
+            length--;
+        }
+        while (length > 1 && *digits == '0') {
+            digits++;
+            length--;
+        }
+        fwrite(digits, 1, length, stderr);
+        fputs(" does not fit in 64 bits", stderr);
+        error_end();
+    }
+    default:
+        not_numeric(input, line, column);
+    }
+}
+
+double mpl_read_real(int32_t line, int32_t column) {
+    mpl_string *input = read_line();
+    mpl_string trimmed = trim(input);
+    if (!is_real(trimmed)) {
+        not_numeric(input, line, column);
+    }
+    char *text = strndup(trimmed.bytes, trimmed.length);
+    double x = strtod(text, NULL);
+    free(text);
+    return x;
+}
+
+mpl_string *mpl_read_string(void) {
+    return read_line();
+}
+
+// operators
+
+mpl_string *mpl_concat(const mpl_string *left, const mpl_string *right) {
+    mpl_string *s = malloc(sizeof(mpl_string) + left->length + right->length);
+    if (s == NULL) {
+        fputs("out of memory\n", stderr);
+        exit(1);
+    }
+    char *bytes = (char *)(s + 1);
+    memcpy(bytes, left->bytes, left->length);
+    memcpy(bytes + left->length, right->bytes, right->length);
+    s->bytes = bytes;
+    s->length = left->length + right->length;
+    return s;
+}
+
+mpl_string *mpl_char_to_string(int32_t c) {
+    char bytes[4];
+    return new_string(bytes, encode(c, bytes));
+}
+
+// byte by byte, a prefix first, which orders UTF-8 by code point
+int32_t mpl_compare(const mpl_string *left, const mpl_string *right) {
+    int64_t length = left->length < right->length ? left->length : right->length;
+    int order = memcmp(left->bytes, right->bytes, length);
+    if (order != 0) {
+        return order < 0 ? -1 : 1;
+    }
+    return (left->length > right->length) - (left->length < right->length);
+}
+
+int32_t mpl_index(const mpl_string *s, int64_t index, int32_t line, int32_t column) {
+    int64_t count = char_count(s);
+    if (index < 0 || index >= count) {
+        error_start(line, column);
+        fprintf(stderr, "index %" PRId64 " out of bounds for string of length %" PRId64,
+                index, count);
+        error_end();
+    }
+    int length;
+    return decode(s->bytes + byte_offset(s, index), &length);
+}
+
+// builtins, see src/builtins.rs; every one takes the position of the call
+
+int64_t mpl_abs(int64_t n, int32_t line, int32_t column) {
+    if (n == INT64_MIN) {
+        error_start(line, column);
+        fprintf(stderr, "abs(%" PRId64 ") does not fit in an int", n);
+        error_end();
+    }
+    return n < 0 ? -n : n;
+}
+
+int64_t mpl_min(int64_t a, int64_t b, int32_t line, int32_t column) {
+    return a < b ? a : b;
+}
+
+int64_t mpl_max(int64_t a, int64_t b, int32_t line, int32_t column) {
+    return a > b ? a : b;
+}
+
+int64_t mpl_pow(int64_t base, int64_t exponent, int32_t line, int32_t column) {
+    if (exponent < 0) {
+        error_start(line, column);
+        fprintf(stderr, "pow: negative exponent %" PRId64, exponent);
+        error_end();
+    }
+    // anything beyond 2^64 overflows, only the parity matters for bases -1, 0 and 1
+    int64_t clamped = exponent > 64 ? 64 + exponent % 2 : exponent;
+    int64_t result = 1;
+    for (int64_t i = 0; i < clamped; i++) {
+        if (__builtin_mul_overflow(result, base, &result)) {
+            error_start(line, column);
+            fprintf(stderr, "pow(%" PRId64 ", %" PRId64 ") does not fit in an int", base,
+                    exponent);
+            error_end();
+        }
+    }
+    return result;
+}
+
+// integer square root, rounded down
+int64_t mpl_sqrt(int64_t n, int32_t line, int32_t column) {
+    if (n < 0) {
+        error_start(line, column);
+        fprintf(stderr, "sqrt of negative number %" PRId64, n);
+        error_end();
+    }
+    int64_t low = 0, high = (n < 3037000499 ? n : 3037000499) + 1;
+    while (high - low > 1) {
+        int64_t mid = (low + high) / 2;
+        if (mid * mid <= n) {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    return low;
+}
+
+mpl_string *mpl_to_string(int64_t n, int32_t line, int32_t column) {
+    char text[24];
+    return new_string(text, sprintf(text, "%" PRId64, n));
+}
+
+int64_t mpl_to_int(const mpl_string *s, int32_t line, int32_t column) {
+    int64_t n;
+    if (parse_int(trim(s), &n) != PARSED) {
+        error_start(line, column);
+        fputs("cannot convert \"", stderr);
+        fwrite(s->bytes, 1, s->length, stderr);
+        fputs("\" to int", stderr);
+        error_end();
+    }
+    return n;
+}
+
+double mpl_to_real(double x, int32_t line, int32_t column) {
+    return x;
+}
+
+static int64_t real_to_int(double x, const char *name, int32_t line, int32_t column) {
+    // INT64_MAX is not exactly representable, 2^63 is the first value out of range
+    if (isnan(x) || x < -9223372036854775808.0 || x >= 9223372036854775808.0) {
+        char text[40];
+        format_real(x, text);
+        error_start(line, column);
+        fprintf(stderr, "%s(%s) does not fit in an int", name, text);
+        error_end();
+    }
+    return (int64_t)x;
+}
+
+int64_t mpl_trunc(double x, int32_t line, int32_t column) {
+    return real_to_int(trunc(x), "trunc", line, column);
+}
+
+// halfway cases away from zero, as Rust's `f64::round`
+int64_t mpl_round(double x, int32_t line, int32_t column) {
+    return real_to_int(round(x), "round", line, column);
+}
+
+// strings are indexed by character, not by byte
+
+int64_t mpl_length(const mpl_string *s, int32_t line, int32_t column) {
+    return char_count(s);
+}
+
+mpl_string *mpl_substring(const mpl_string *s, int64_t start, int64_t length, int32_t line,
+                          int32_t column) {
+    int64_t count = char_count(s);
+    if (start < 0 || length < 0 || start > count || length > count - start) {
+        error_start(line, column);
+        fprintf(stderr,
+                "substring(%" PRId64 ", %" PRId64 ") out of bounds for string of length %" PRId64,
+                start, length, count);
+        error_end();
+    }
+    int64_t from = byte_offset(s, start);
+    mpl_string rest = {s->length - from, s->bytes + from};
+    return new_string(rest.bytes, byte_offset(&rest, length));
+}
+
+// character position of the first occurrence, -1 when not found
+int64_t mpl_index_of(const mpl_string *s, const mpl_string *part, int32_t line,
+                     int32_t column) {
+    const char *found = memmem(s->bytes, s->length, part->bytes, part->length);
+    if (found == NULL) {
+        return -1;
+    }
+    mpl_string prefix = {found - s->bytes, s->bytes};
+    return char_count(&prefix);
+}
+
+// character by character, so mappings to several characters such as ß to SS are
+// not made
+static mpl_string *map_case(const mpl_string *s, wint_t (*map)(wint_t)) {
+    static int locale_set = 0;
+    if (!locale_set) {
+        setlocale(LC_CTYPE, "C.UTF-8");
+        locale_set = 1;
+    }
+    char *bytes = malloc(s->length * 2 + 4);
+    int64_t length = 0;
+    for (int64_t i = 0; i < s->length;) {
+        int size;
+        int32_t c = decode(s->bytes + i, &size);
+        length += encode(map(c), bytes + length);
+        i += size;
+    }
+    mpl_string *mapped = new_string(bytes, length);
+    free(bytes);
+    return mapped;
+}
+
+mpl_string *mpl_upper(const mpl_string *s, int32_t line, int32_t column) {
+    return map_case(s, towupper);
+}
+
+mpl_string *mpl_lower(const mpl_string *s, int32_t line, int32_t column) {
+    return map_case(s, towlower);
+}
@@ -1,11 +1,15 @@
-use crate::tokens::{Token, TokenType, Value};
+use crate::bigint::BigInt;
+use crate::tokens::{Span, Token, TokenType, Value};
 use phf::phf_map;
+use std::fmt::{self, Display, Formatter};
 
 const RESERVED_KEYWORDS: phf::Map<&'static str, TokenType> = phf_map! {
     "bool" => TokenType::Bool,
     "var" => TokenType::Var,
     "int" => TokenType::Integer,
     "string" => TokenType::Str,
+    "char" => TokenType::Char,
+    "real" => TokenType::Real,
     "print" => TokenType::Print,
     "read" => TokenType::Read,
     "if" => TokenType::If,
@@ -16,135 +20,270 @@ const RESERVED_KEYWORDS: phf::Map<&'static str, TokenType> = phf_map! {
     "in" => TokenType::In,
 };
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct LexError {
+    pub message: String,
+    pub span: Span,
+}
+
+impl Display for LexError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Lexical error at {}: {}", self.span, self.message)
+    }
+}
+
+impl std::error::Error for LexError {}
+
+/// Works over the characters of the source; `pos` is always the byte offset of
+/// `current_char`, so spans can slice the original text.
 pub struct Scanner {
     text: String,
     pos: usize,
     current_char: Option<char>,
+    line: usize,
+    column: usize,
+    token_start: Span,
+    trivia: bool,
+    finished: bool,
 }
 
 impl Scanner {
     pub fn new(text: String) -> Self {
         Scanner {
-            text: text.clone(),
+            current_char: text.chars().next(),
+            text,
             pos: 0,
-            current_char: Some(text.as_bytes()[0] as char),
+            line: 1,
+            column: 1,
+            token_start: Span::default(),
+            trivia: false,
+            finished: false,
         }
     }
 
-    pub fn get_next_token(&mut self) -> Token {
-        while let Some(c) = self.current_char {
-            if c.is_whitespace() {
-                self.skip_whitespace();
-                continue;
-            }
-            if c.is_numeric() {
-                return Token::new(TokenType::Integer, Value::Number(self.integer()));
+    /// A lossless scanner that also yields whitespace and comment tokens, so the
+    /// source can be rebuilt exactly by concatenating the token spans.
+    pub fn with_trivia(text: String) -> Self {
+        Scanner {
+            trivia: true,
+            ..Scanner::new(text)
+        }
+    }
+
+    pub fn get_next_token(&mut self) -> Result<Token, LexError> {
+        loop {
+            let mut token = self.scan()?;
+            token.span = Span {
+                end: self.pos,
+                ..self.token_start
+            };
+            if self.trivia || !token.type_.is_trivia() {
+                return Ok(token);
             }
-            match c {
-                '!' => {
+        }
+    }
+
+    fn mark(&mut self) {
+        self.token_start = self.location();
+    }
+
+    // empty span at the scanner position
+    fn location(&self) -> Span {
+        Span {
+            start: self.pos,
+            end: self.pos,
+            line: self.line,
+            column: self.column,
+        }
+    }
+
+    fn scan(&mut self) -> Result<Token, LexError> {
+        self.mark();
+        let c = match self.current_char {
+            Some(c) => c,
+            None => return Ok(Token::new(TokenType::EOF, Value::None)),
+        };
+        if c.is_whitespace() {
+            return Ok(self.whitespace());
+        }
+        if c.is_ascii_digit() {
+            return Ok(self.number());
+        }
+        match c {
+            '!' => {
+                if let Some('=') = self.peek() {
                     self.advance();
-                    return Token::new(TokenType::Not, Value::Char(c));
-                }
-                '&' => {
                     self.advance();
-                    return Token::new(TokenType::And, Value::Char(c));
+                    return Ok(Token::new(
+                        TokenType::NotEqual,
+                        Value::String(String::from("!=")),
+                    ));
                 }
-                '=' => {
+                self.advance();
+                Ok(Token::new(TokenType::Not, Value::Char(c)))
+            }
+            '&' => {
+                self.advance();
+                Ok(Token::new(TokenType::And, Value::Char(c)))
+            }
+            '|' => {
+                self.advance();
+                Ok(Token::new(TokenType::Or, Value::Char(c)))
+            }
+            '=' => {
+                self.advance();
+                Ok(Token::new(TokenType::Equal, Value::Char(c)))
+            }
+            '<' => match self.peek() {
+                Some('=') => {
                     self.advance();
-                    return Token::new(TokenType::Equal, Value::Char(c));
-                }
-                '<' => {
                     self.advance();
-                    return Token::new(TokenType::LessThan, Value::Char(c));
+                    Ok(Token::new(
+                        TokenType::LessEqual,
+                        Value::String(String::from("<=")),
+                    ))
                 }
-                '+' => {
+                Some('>') => {
                     self.advance();
-                    return Token::new(TokenType::Plus, Value::Char(c));
-                }
-                '-' => {
                     self.advance();
-                    return Token::new(TokenType::Minus, Value::Char(c));
+                    Ok(Token::new(
+                        TokenType::NotEqual,
+                        Value::String(String::from("<>")),
+                    ))
                 }
-                '*' => {
+                _ => {
                     self.advance();
-                    return Token::new(TokenType::Mul, Value::Char(c));
-                }
-                '/' => {
-                    match self.peek() {
-                        Some('/') => self.skip_comment(),
-                        Some('*') => self.skip_comment(),
-                        _ => {
-                            self.advance();
-                            return Token::new(TokenType::Div, Value::Char(c));
-                        }
-                    }
-                    continue;
+                    Ok(Token::new(TokenType::LessThan, Value::Char(c)))
                 }
-                '(' => {
+            },
+            '>' => {
+                if let Some('=') = self.peek() {
                     self.advance();
-                    return Token::new(TokenType::LeftParen, Value::Char(c));
-                }
-                ')' => {
                     self.advance();
-                    return Token::new(TokenType::RightParen, Value::Char(c));
+                    return Ok(Token::new(
+                        TokenType::GreaterEqual,
+                        Value::String(String::from(">=")),
+                    ));
                 }
-                ':' => {
-                    if let Some('=') = self.peek() {
-                        self.advance();
-                        self.advance();
-                        return Token::new(TokenType::Assign, Value::String(String::from(":=")));
-                    } else {
-                        self.advance();
-                        return Token::new(TokenType::Colon, Value::Char(c));
-                    }
+                self.advance();
+                Ok(Token::new(TokenType::GreaterThan, Value::Char(c)))
+            }
+            '+' => {
+                self.advance();
+                Ok(Token::new(TokenType::Plus, Value::Char(c)))
+            }
+            '-' => {
+                self.advance();
+                Ok(Token::new(TokenType::Minus, Value::Char(c)))
+            }
+            '*' => {
+                self.advance();
+                Ok(Token::new(TokenType::Mul, Value::Char(c)))
+            }
+            '/' => match self.peek() {
+                Some('/') | Some('*') => self.comment(),
+                _ => {
+                    self.advance();
+                    Ok(Token::new(TokenType::Div, Value::Char(c)))
                 }
-                ';' => {
+            },
+            '%' => {
+                self.advance();
+                Ok(Token::new(TokenType::Mod, Value::Char(c)))
+            }
+            ',' => {
+                self.advance();
+                Ok(Token::new(TokenType::Comma, Value::Char(c)))
+            }
+            '(' => {
+                self.advance();
+                Ok(Token::new(TokenType::LeftParen, Value::Char(c)))
+            }
+            ')' => {
+                self.advance();
+                Ok(Token::new(TokenType::RightParen, Value::Char(c)))
+            }
+            '[' => {
+                self.advance();
+                Ok(Token::new(TokenType::LeftBracket, Value::Char(c)))
+            }
+            ']' => {
+                self.advance();
+                Ok(Token::new(TokenType::RightBracket, Value::Char(c)))
+            }
+            ':' => {
+                if let Some('=') = self.peek() {
+                    self.advance();
                     self.advance();
-                    return Token::new(TokenType::Semi, Value::Char(c));
+                    Ok(Token::new(
+                        TokenType::Assign,
+                        Value::String(String::from(":=")),
+                    ))
+                } else {
+                    self.advance();
+                    Ok(Token::new(TokenType::Colon, Value::Char(c)))
                 }
-                '.' => {
-                    if let Some('.') = self.peek() {
-                        self.advance();
-                        self.advance();
-                        return Token::new(TokenType::To, Value::String(String::from("..")));
-                    } else {
-                        self.error()
-                    }
+            }
+            ';' => {
+                self.advance();
+                Ok(Token::new(TokenType::Semi, Value::Char(c)))
+            }
+            '.' => match self.peek() {
+                Some('.') => {
+                    self.advance();
+                    self.advance();
+                    Ok(Token::new(TokenType::To, Value::String(String::from(".."))))
                 }
-                '\"' => {
-                    let token = self.string_literal();
-                    if token.type_ == TokenType::StringLiteral {
-                        return token;
-                    } else {
-                        self.error()
-                    }
+                Some(ch) if ch.is_ascii_digit() => Ok(self.number()),
+                _ => {
+                    self.advance();
+                    Err(self.error(String::from("unexpected character '.'")))
                 }
-                c => {
-                    if c.is_alphanumeric() || c == '_' {
-                        return self.id();
-                    } else {
-                        self.error()
-                    }
+            },
+            '\'' => self.char_literal(),
+            '\"' => self.string_literal(),
+            c => {
+                if c.is_alphanumeric() || c == '_' {
+                    Ok(self.id())
+                } else {
+                    self.advance();
+                    Err(self.error(format!("unexpected character {:?}", c)))
                 }
             }
         }
-        Token::new(TokenType::EOF, Value::None)
     }
 
-    fn error(&self) {
-        panic!("Lexical error, invalid token");
+    // covers the text from the start of the current token up to the scanner position
+    fn error(&self, message: String) -> LexError {
+        self.error_from(self.token_start, message)
+    }
+
+    fn error_from(&self, start: Span, message: String) -> LexError {
+        LexError {
+            message,
+            span: Span {
+                end: self.pos,
+                ..start
+            },
+        }
     }
 
     fn advance(&mut self) {
-        self.pos += 1;
-        if self.pos > self.text.len() - 1 {
-            self.current_char = None;
+        let c = match self.current_char {
+            Some(c) => c,
+            None => return,
+        };
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
         } else {
-            self.current_char = Some(self.text.as_bytes()[self.pos] as char);
+            self.column += 1;
         }
+        self.pos += c.len_utf8();
+        self.current_char = self.text[self.pos..].chars().next();
     }
 
-    fn skip_whitespace(&mut self) {
+    fn whitespace(&mut self) -> Token {
         while let Some(c) = self.current_char {
             if c.is_whitespace() {
                 self.advance();
@@ -152,86 +291,128 @@ impl Scanner {
                 break;
             }
         }
+        Token::new(TokenType::Whitespace, Value::String(self.lexeme()))
     }
 
-    fn skip_comment(&mut self) {
+    // `// ...` up to but not including the newline, or `/* ... */` where block
+    // comments nest and must be closed
+    fn comment(&mut self) -> Result<Token, LexError> {
         self.advance();
-        if let Some(c) = self.current_char {
-            match c {
-                '/' => {
+        if let Some('/') = self.current_char {
+            while let Some(ch) = self.current_char {
+                match ch {
+                    '\n' => break,
+                    _ => self.advance(),
+                }
+            }
+            return Ok(Token::new(TokenType::Comment, Value::String(self.lexeme())));
+        }
+        self.advance();
+        let mut depth = 1;
+        while let Some(ch) = self.current_char {
+            match (ch, self.peek()) {
+                ('*', Some('/')) => {
+                    self.advance();
                     self.advance();
-                    while let Some(ch) = self.current_char {
-                        match ch {
-                            '\n' => return,
-                            _ => self.advance(),
-                        }
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(Token::new(TokenType::Comment, Value::String(self.lexeme())));
                     }
                 }
-                '*' => {
+                ('/', Some('*')) => {
                     self.advance();
-                    while let Some(ch) = self.current_char {
-                        match ch {
-                            '*' => match self.peek() {
-                                Some('/') => {
-                                    self.advance();
-                                    self.advance();
-                                    return;
-                                }
-                                _ => self.advance(),
-                            },
-                            _ => self.advance(),
-                        }
-                    }
+                    self.advance();
+                    depth += 1;
                 }
-                _ => {}
+                _ => self.advance(),
             }
         }
+        // point at the outermost opening `/*`
+        Err(LexError {
+            message: String::from("unterminated block comment"),
+            span: Span {
+                end: self.token_start.start + 2,
+                ..self.token_start
+            },
+        })
+    }
+
+    // source text of the token scanned so far
+    fn lexeme(&self) -> String {
+        self.text[self.token_start.start..self.pos].to_string()
     }
 
     fn peek(&self) -> Option<char> {
-        if self.pos > self.text.len() {
-            None
-        } else {
-            Some(self.text.as_bytes()[self.pos + 1] as char)
-        }
+        self.peek_at(1)
     }
 
-    fn integer(&mut self) -> i32 {
-        let mut result = String::new();
+    // the character `offset` characters after the current one
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.text[self.pos..].chars().nth(offset)
+    }
+
+    fn digits(&mut self, result: &mut String) {
         while let Some(n) = self.current_char {
-            if n.is_numeric() {
+            if n.is_ascii_digit() {
                 result.push(n);
                 self.advance();
             } else {
                 break;
             }
         }
-        result.parse().unwrap()
     }
 
-    fn string_literal(&mut self) -> Token {
+    // integer literal, or a real literal with a fraction and/or exponent part;
+    // a dot only starts a fraction when a digit follows, so `1..5` stays a range
+    fn number(&mut self) -> Token {
+        let mut result = String::new();
+        let mut is_real = false;
+        self.digits(&mut result);
+        if self.current_char == Some('.') && self.peek_at(1).is_some_and(|c| c.is_ascii_digit()) {
+            is_real = true;
+            result.push('.');
+            self.advance();
+            self.digits(&mut result);
+        }
+        if let Some('e') | Some('E') = self.current_char {
+            let exponent_digit = match self.peek_at(1) {
+                Some('+') | Some('-') => self.peek_at(2),
+                c => c,
+            };
+            if exponent_digit.is_some_and(|c| c.is_ascii_digit()) {
+                is_real = true;
+                result.push('e');
+                self.advance();
+                if let Some(sign @ '+') | Some(sign @ '-') = self.current_char {
+                    result.push(sign);
+                    self.advance();
+                }
+                self.digits(&mut result);
+            }
+        }
+        if is_real {
+            Token::new(TokenType::RealLiteral, Value::Real(result.parse().unwrap()))
+        } else {
+            // literals beyond i64 are kept exact, the interpreter decides if they are allowed
+            let value = match result.parse() {
+                Ok(n) => Value::Number(n),
+                Err(_) => Value::BigNumber(BigInt::parse(&result).unwrap()),
+            };
+            Token::new(TokenType::Integer, value)
+        }
+    }
+
+    // a string ends at the closing quote, a newline inside one is an error
+    fn string_literal(&mut self) -> Result<Token, LexError> {
         self.advance();
         let mut result = String::new();
         while let Some(c) = self.current_char {
             match c {
-                '\n' => {
-                    self.error();
-                    break;
-                }
-                ';' => {
-                    self.error();
-                    break;
-                }
-                '\\' => {
-                    self.advance();
-                    if let Some(ch) = self.peek() {
-                        result.push(ch);
-                    }
-                    self.advance();
-                }
+                '\n' => break,
+                '\\' => result.push(self.escape()?),
                 '\"' => {
                     self.advance();
-                    return Token::new(TokenType::StringLiteral, Value::String(result.clone()));
+                    return Ok(Token::new(TokenType::StringLiteral, Value::String(result)));
                 }
                 _ => {
                     result.push(c);
@@ -239,7 +420,74 @@ impl Scanner {
                 }
             }
         }
-        Token::new(TokenType::EOF, Value::None)
+        Err(self.error(String::from("unterminated string literal")))
+    }
+
+    fn char_literal(&mut self) -> Result<Token, LexError> {
+        self.advance();
+        let c = match self.current_char {
+            Some('\'') | Some('\n') | None => {
+                return Err(self.error(String::from("empty character literal")));
+            }
+            Some('\\') => self.escape()?,
+            Some(c) => {
+                self.advance();
+                c
+            }
+        };
+        if self.current_char != Some('\'') {
+            return Err(self.error(String::from("unterminated character literal")));
+        }
+        self.advance();
+        Ok(Token::new(TokenType::CharLiteral, Value::Char(c)))
+    }
+
+    // `\n`, `\t`, `\"`, `\'`, `\\` or `\u{...}` with 1 to 6 hex digits,
+    // starting at the backslash
+    fn escape(&mut self) -> Result<char, LexError> {
+        let start = self.location();
+        self.advance();
+        let c = match self.current_char {
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('"') => '"',
+            Some('\'') => '\'',
+            Some('\\') => '\\',
+            Some('u') => return self.unicode_escape(start),
+            Some(c) if c != '\n' => {
+                self.advance();
+                return Err(self.error_from(start, format!("invalid escape sequence \\{}", c)));
+            }
+            _ => return Err(self.error_from(start, String::from("incomplete escape sequence"))),
+        };
+        self.advance();
+        Ok(c)
+    }
+
+    fn unicode_escape(&mut self, start: Span) -> Result<char, LexError> {
+        self.advance();
+        if self.current_char != Some('{') {
+            return Err(self.error_from(start, String::from("expected { after \\u")));
+        }
+        self.advance();
+        let mut digits = String::new();
+        while let Some(c) = self.current_char.filter(|c| c.is_ascii_hexdigit()) {
+            digits.push(c);
+            self.advance();
+        }
+        if self.current_char != Some('}') || digits.is_empty() || digits.len() > 6 {
+            return Err(self.error_from(
+                start,
+                String::from("unicode escape must be \\u{...} with 1 to 6 hex digits"),
+            ));
+        }
+        self.advance();
+        u32::from_str_radix(&digits, 16)
+            .ok()
+            .and_then(std::char::from_u32)
+            .ok_or_else(|| {
+                self.error_from(start, format!("\\u{{{}}} is not a valid character", digits))
+            })
     }
 
     fn id(&mut self) -> Token {
@@ -257,3 +505,128 @@ impl Scanner {
         )
     }
 }
+
+/// Yields tokens up to, but not including, EOF and stops after the first error.
+impl Iterator for Scanner {
+    type Item = Result<Token, LexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        match self.get_next_token() {
+            Ok(Token {
+                type_: TokenType::EOF,
+                ..
+            }) => {
+                self.finished = true;
+                None
+            }
+            Err(e) => {
+                self.finished = true;
+                Some(Err(e))
+            }
+            token => Some(token),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scan_all(text: &str) -> Vec<Token> {
+        Scanner::new(text.to_string())
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn scans_unicode_text() {
+        let text = "var pää : string := \"hyvää yötä ✓\"; // äö\nprint pää";
+        let tokens = scan_all(text);
+        assert_eq!(tokens[1].value, Value::String(String::from("pää")));
+        assert_eq!(tokens[5].value, Value::String(String::from("hyvää yötä ✓")));
+        for token in &tokens {
+            let source = &text[token.span.start..token.span.end];
+            assert!(source.contains(&token.value.to_string()[..]), "{}", source);
+        }
+        assert_eq!((tokens[8].span.line, tokens[8].span.column), (2, 7));
+        assert_eq!(tokens.len(), 9);
+
+        assert_eq!(scan_all("x :")[1].type_, TokenType::Colon);
+        assert!(scan_all("").is_empty());
+    }
+
+    #[test]
+    fn string_escapes() {
+        let tokens = scan_all(r#"print "a;b\t\"q\" \\ \u{e4}\u{1F600}\n"; 'x' '\'' '\u{41}'"#);
+        assert_eq!(
+            tokens[1].value,
+            Value::String(String::from("a;b\t\"q\" \\ ä😀\n"))
+        );
+        assert_eq!(tokens[2].type_, TokenType::Semi);
+        assert_eq!(tokens[4].value, Value::Char('\''));
+        assert_eq!(tokens[5].value, Value::Char('A'));
+
+        let error = |text: &str| {
+            let mut scanner = Scanner::new(text.to_string());
+            scanner.find_map(Result::err).unwrap().to_string()
+        };
+        assert_eq!(
+            error("x := \"ab\\qc\";"),
+            "Lexical error at 1:9: invalid escape sequence \\q"
+        );
+        assert_eq!(
+            error("x := \"\\u{110000}\";"),
+            "Lexical error at 1:7: \\u{110000} is not a valid character"
+        );
+        assert_eq!(
+            error("x := 1;\ny := \"open;\nz := 2;"),
+            "Lexical error at 2:6: unterminated string literal"
+        );
+    }
+
+    #[test]
+    fn nested_block_comments() {
+        let tokens = scan_all("x /* a /* b */ c */ y /*/ z */ w // /* \n v");
+        let names: Vec<_> = tokens.iter().map(|t| t.value.to_string()).collect();
+        assert_eq!(names, vec!["x", "y", "w", "v"]);
+
+        let mut scanner = Scanner::new(String::from("x := 1;\n  /* a /* b */\nprint x;"));
+        let error = scanner.find_map(Result::err).unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Lexical error at 2:3: unterminated block comment"
+        );
+        assert_eq!((error.span.start, error.span.end), (10, 12));
+    }
+
+    #[test]
+    fn lossless_trivia() {
+        let text = "var x : int;  // note\n/* block\n */ print x;\n";
+        let tokens = Scanner::with_trivia(text.to_string())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let rebuilt: String = tokens
+            .iter()
+            .map(|t| &text[t.span.start..t.span.end])
+            .collect();
+        assert_eq!(rebuilt, text);
+        let comments: Vec<_> = tokens
+            .iter()
+            .filter(|t| t.type_ == TokenType::Comment)
+            .map(|t| t.value.to_string())
+            .collect();
+        assert_eq!(comments, vec!["// note", "/* block\n */"]);
+        assert_eq!(scan_all(text).len(), 8);
+
+        let mut scanner = Scanner::new(String::from("x := 1 # 2"));
+        let error = scanner.find_map(Result::err).unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Lexical error at 1:8: unexpected character '#'"
+        );
+        assert!(scanner.next().is_none());
+    }
+}
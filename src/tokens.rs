@@ -1,25 +1,34 @@
+use crate::bigint::BigInt;
 use std::fmt::{self, Display, Formatter};
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum TokenType {
     Str,
     Bool,
+    Char,
+    Real,
     Var,
     Integer,
     Plus,
     Minus,
     Mul,
     Div,
+    Mod,
     RightParen,
     LeftParen,
+    RightBracket,
+    LeftBracket,
     ID,
     Assign,
     Semi,
     Colon,
+    Comma,
     EOF,
     Print,
     Read,
     StringLiteral,
+    CharLiteral,
+    RealLiteral,
     For,
     End,
     If,
@@ -28,15 +37,83 @@ pub enum TokenType {
     In,
     To,
     Equal,
+    NotEqual,
     LessThan,
+    LessEqual,
+    GreaterThan,
+    GreaterEqual,
     And,
+    Or,
     Not,
+    Whitespace,
+    Comment,
+}
+
+impl Display for TokenType {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let text = match self {
+            TokenType::Str => "string",
+            TokenType::Bool => "bool",
+            TokenType::Char => "char",
+            TokenType::Real => "real",
+            TokenType::Var => "var",
+            TokenType::Integer => "int",
+            TokenType::Plus => "+",
+            TokenType::Minus => "-",
+            TokenType::Mul => "*",
+            TokenType::Div => "/",
+            TokenType::Mod => "%",
+            TokenType::RightParen => ")",
+            TokenType::LeftParen => "(",
+            TokenType::RightBracket => "]",
+            TokenType::LeftBracket => "[",
+            TokenType::ID => "an identifier",
+            TokenType::Assign => ":=",
+            TokenType::Semi => ";",
+            TokenType::Colon => ":",
+            TokenType::Comma => ",",
+            TokenType::EOF => "end of input",
+            TokenType::Print => "print",
+            TokenType::Read => "read",
+            TokenType::StringLiteral => "a string literal",
+            TokenType::CharLiteral => "a character literal",
+            TokenType::RealLiteral => "a real literal",
+            TokenType::For => "for",
+            TokenType::End => "end",
+            TokenType::If => "if",
+            TokenType::Else => "else",
+            TokenType::Do => "do",
+            TokenType::In => "in",
+            TokenType::To => "..",
+            TokenType::Equal => "=",
+            TokenType::NotEqual => "<>",
+            TokenType::LessThan => "<",
+            TokenType::LessEqual => "<=",
+            TokenType::GreaterThan => ">",
+            TokenType::GreaterEqual => ">=",
+            TokenType::And => "&",
+            TokenType::Or => "|",
+            TokenType::Not => "!",
+            TokenType::Whitespace => "whitespace",
+            TokenType::Comment => "a comment",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+impl TokenType {
+    /// Whitespace and comments, which only a lossless scanner reports.
+    pub fn is_trivia(&self) -> bool {
+        matches!(self, TokenType::Whitespace | TokenType::Comment)
+    }
 }
 
 #[derive(Clone, PartialEq, Debug)]
 pub enum Value {
     Boolean(bool),
-    Number(i32),
+    Number(i64),
+    BigNumber(BigInt),
+    Real(f64),
     Char(char),
     String(String),
     None,
@@ -47,6 +124,9 @@ impl Display for Value {
         match self {
             Value::Boolean(b) => write!(f, "{}", b),
             Value::Number(n) => write!(f, "{}", n),
+            Value::BigNumber(n) => write!(f, "{}", n),
+            // Debug keeps the fraction and uses exponents for tiny and huge values: 3.0, 1e-7
+            Value::Real(r) => write!(f, "{:?}", r),
             Value::Char(c) => write!(f, "{}", c),
             Value::String(s) => write!(f, "{}", s),
             Value::None => write!(f, ""),
@@ -54,15 +134,35 @@ impl Display for Value {
     }
 }
 
+/// Source location: byte offsets into the text plus the 1-based line and column of `start`.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
 #[derive(Clone, PartialEq, Debug)]
 pub struct Token {
     pub type_: TokenType,
     pub value: Value,
+    pub span: Span,
 }
 
 impl Token {
     pub fn new(type_: TokenType, value: Value) -> Self {
-        Token { type_, value }
+        Token {
+            type_,
+            value,
+            span: Span::default(),
+        }
     }
 }
 
@@ -0,0 +1,190 @@
+use crate::debugger;
+use crate::json::Json;
+use crate::nodes::Node;
+use crate::tokens::{Span, Value};
+use std::io::Write;
+
+/// Logs what a running program does: every statement before it runs, every
+/// variable write and every loop iteration. Either as readable lines or as one
+/// JSON object per line.
+pub struct Tracer {
+    output: Box<dyn Write>,
+    json: bool,
+}
+
+impl Tracer {
+    pub fn text(output: Box<dyn Write>) -> Self {
+        Tracer {
+            output,
+            json: false,
+        }
+    }
+
+    pub fn json_lines(output: Box<dyn Write>) -> Self {
+        Tracer { output, json: true }
+    }
+
+    pub fn statement(&mut self, statement: &Node) {
+        let kind = match statement {
+            Node::VarDecl(_) | Node::DeclAssign(_) => "var",
+            Node::Assign(_) => "assign",
+            Node::Read(_) => "read",
+            Node::PrintVar(_) | Node::PrintStr(_) => "print",
+            Node::ForLoop(_) => "for",
+            Node::IfStatement(_) => "if",
+            _ => return,
+        };
+        let span = match statement.source_span() {
+            Some(span) => span,
+            None => return,
+        };
+        let text = format!("{} {}", span, kind);
+        self.emit(text, "statement", span, vec![("kind", kind.into())]);
+    }
+
+    /// `old` is `None` when the write declares the variable. Declarations without a
+    /// place in the source are not traced.
+    pub fn write(&mut self, name: &str, old: Option<&Value>, new: &Value, span: Span) {
+        if old.is_none() && span.line == 0 {
+            return;
+        }
+        let text = match old {
+            Some(old) => format!(
+                "{} write {}: {} -> {}",
+                span,
+                name,
+                debugger::show(old),
+                debugger::show(new)
+            ),
+            None => format!("{} write {} = {}", span, name, debugger::show(new)),
+        };
+        let members = vec![
+            ("name", name.into()),
+            ("old", old.map_or(Json::Null, value)),
+            ("new", value(new)),
+        ];
+        self.emit(text, "write", span, members);
+    }
+
+    pub fn iteration(&mut self, name: &str, counter: i64, span: Span) {
+        let text = format!("{} iteration {} = {}", span, name, counter);
+        let members = vec![("name", name.into()), ("value", integer(counter))];
+        self.emit(text, "iteration", span, members);
+    }
+
+    fn emit(&mut self, text: String, event: &str, span: Span, members: Vec<(&str, Json)>) {
+        let line = if self.json {
+            let mut object = vec![
+                ("event", event.into()),
+                ("line", span.line.into()),
+                ("column", span.column.into()),
+            ];
+            object.extend(members);
+            Json::object(object).to_string()
+        } else {
+            format!("trace: {}", text)
+        };
+        // a trace that cannot be written must not change how the program runs
+        let _ = writeln!(self.output, "{}", line);
+    }
+}
+
+// numbers too large for a double keep their digits as a string
+fn value(value: &Value) -> Json {
+    match value {
+        Value::Boolean(b) => Json::Bool(*b),
+        Value::Number(n) => integer(*n),
+        Value::Real(r) => Json::Number(*r),
+        Value::BigNumber(n) => n.to_string().into(),
+        Value::Char(c) => c.to_string().into(),
+        Value::String(s) => s.as_str().into(),
+        Value::None => Json::Null,
+    }
+}
+
+fn integer(n: i64) -> Json {
+    // every integer up to 2^53 is exactly a double
+    const EXACT: i64 = 1 << 53;
+    if (-EXACT..=EXACT).contains(&n) {
+        Json::Number(n as f64)
+    } else {
+        n.to_string().into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Interpreter;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    fn trace(json: bool) -> String {
+        let path =
+            std::env::temp_dir().join(format!("mini-pl-trace-{}-{}", std::process::id(), json));
+        let file = Box::new(std::fs::File::create(&path).unwrap());
+        let text = "var s : string := \"a\";
+var i : int;
+for i in 1..2 do
+    s := s + 'b';
+end for;";
+        let mut interpreter = Interpreter::new(Parser::new(Scanner::new(text.to_string())));
+        interpreter.tracer = Some(if json {
+            Tracer::json_lines(file)
+        } else {
+            Tracer::text(file)
+        });
+        interpreter.interpret().unwrap();
+        drop(interpreter);
+        let trace = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        trace
+    }
+
+    #[test]
+    fn traces_statements_writes_and_iterations() {
+        let expected = "trace: 1:1 var
+trace: 1:5 write s = \"a\"
+trace: 2:1 var
+trace: 2:5 write i = 0
+trace: 3:1 for
+trace: 3:5 iteration i = 1
+trace: 4:5 assign
+trace: 4:5 write s: \"a\" -> \"ab\"
+trace: 3:5 iteration i = 2
+trace: 4:5 assign
+trace: 4:5 write s: \"ab\" -> \"abb\"
+";
+        assert_eq!(trace(false), expected);
+
+        let lines: Vec<Json> = trace(true)
+            .lines()
+            .map(|line| Json::parse(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 11);
+        assert_eq!(
+            lines[7].to_string(),
+            r#"{"event":"write","line":4,"column":5,"name":"s","old":"a","new":"ab"}"#
+        );
+        assert_eq!(
+            lines[5].to_string(),
+            r#"{"event":"iteration","line":3,"column":5,"name":"i","value":1}"#
+        );
+    }
+
+    #[test]
+    fn keeps_the_digits_of_large_integers() {
+        assert_eq!(
+            value(&Value::Number(1 << 53)).to_string(),
+            "9007199254740992"
+        );
+        assert_eq!(
+            value(&Value::Number((1 << 53) + 1)).to_string(),
+            r#""9007199254740993""#
+        );
+        assert_eq!(
+            value(&Value::Number(i64::MIN)).to_string(),
+            r#""-9223372036854775808""#
+        );
+    }
+}
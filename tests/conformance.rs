@@ -0,0 +1,11 @@
+use std::path::{Path, PathBuf};
+
+// every example and rule of docs/MiniPL.pdf, see tests/spec
+#[test]
+fn spec_suite() {
+    let interpreter = Path::new(env!("CARGO_BIN_EXE_mini-pl"));
+    let suite = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/spec");
+    let mut report = Vec::new();
+    let passed = mini_pl::golden::run(interpreter, &[suite], false, &mut report).unwrap();
+    assert!(passed, "{}", String::from_utf8_lossy(&report));
+}
@@ -0,0 +1,107 @@
+use mini_pl::generator::Generator;
+use mini_pl::golden::{self, Outcome};
+use mini_pl::llvm::{self, Int};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// llc and a C compiler, when installed
+struct Toolchain {
+    cc: &'static str,
+    dir: PathBuf,
+    runtime: PathBuf,
+}
+
+impl Toolchain {
+    fn find() -> Option<Toolchain> {
+        let works = |tool: &str| Command::new(tool).arg("--version").output().is_ok();
+        let cc = ["clang", "cc", "gcc"].iter().find(|cc| works(cc))?;
+        if !works("llc") {
+            return None;
+        }
+        let dir = std::env::temp_dir().join(format!("mini-pl-llvm-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("runtime.c");
+        fs::write(&source, llvm::RUNTIME).unwrap();
+        let runtime = dir.join("runtime.o");
+        run(Command::new(cc)
+            .arg("-c")
+            .arg(&source)
+            .arg("-o")
+            .arg(&runtime));
+        Some(Toolchain { cc, dir, runtime })
+    }
+
+    // the binary for `source`, or `None` when the compiler rejects it
+    fn build(&self, source: &str, name: &str) -> Option<PathBuf> {
+        let module = llvm::compile(source, Int::I64).ok()?;
+        let ll = self.dir.join(format!("{}.ll", name));
+        let object = self.dir.join(format!("{}.o", name));
+        let binary = self.dir.join(name);
+        fs::write(&ll, module).unwrap();
+        run(Command::new("llc")
+            .args(["-relocation-model=pic", "-filetype=obj", "-o"])
+            .arg(&object)
+            .arg(&ll));
+        run(Command::new(self.cc)
+            .arg(&object)
+            .arg(&self.runtime)
+            .args(["-lm", "-o"])
+            .arg(&binary));
+        Some(binary)
+    }
+}
+
+impl Drop for Toolchain {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn run(command: &mut Command) {
+    let output = command.output().unwrap();
+    assert!(
+        output.status.success(),
+        "{:?} failed:\n{}",
+        command,
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+// compiled programs print, read and fail exactly as the interpreter does; skipped
+// without llc and a C compiler
+#[test]
+fn compiled_programs_agree_with_the_interpreter() {
+    let toolchain = match Toolchain::find() {
+        Some(toolchain) => toolchain,
+        None => {
+            eprintln!("llc or a C compiler is missing, not compiling");
+            return;
+        }
+    };
+    let suite = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/spec");
+    for (i, program) in golden::discover(&[suite]).unwrap().iter().enumerate() {
+        let source = fs::read_to_string(program).unwrap();
+        let binary = match toolchain.build(&source, &format!("spec{}", i)) {
+            Some(binary) => binary,
+            None => continue,
+        };
+        let expected = Outcome::expected(program).unwrap();
+        let outcome = Outcome::of(&binary, program).unwrap();
+        assert_eq!(outcome, expected, "{}", program.display());
+    }
+
+    let interpreter = Path::new(env!("CARGO_BIN_EXE_mini-pl"));
+    for seed in 0..50 {
+        let source = Generator::new(seed).program().to_string();
+        let program = toolchain.dir.join(format!("generated{}.mpl", seed));
+        fs::write(&program, &source).unwrap();
+        let binary = match toolchain.build(&source, &format!("generated{}", seed)) {
+            Some(binary) => binary,
+            None => continue,
+        };
+        let expected = Outcome::of(interpreter, &program).unwrap();
+        let outcome = Outcome::of(&binary, &program).unwrap();
+        assert_eq!(outcome, expected, "seed {}\n{}", seed, source);
+    }
+}